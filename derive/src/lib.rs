@@ -0,0 +1,415 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident, Member, Path, Result, Type};
+
+/// Implements `RecursiveSizeBalancedTree` and `IterativeSizeBalancedTree`
+/// for a storage keeping its nodes in an indexable field (usually a `Vec`).
+///
+/// ```ignore
+/// #[derive(SizeBalancedTree)]
+/// #[tree(link = usize)]
+/// struct Storage {
+///     #[tree(nodes)]
+///     nodes: Vec<Node>,
+/// }
+/// ```
+///
+/// Node fields default to `left`, `right` and `size` and can be renamed with
+/// `#[tree(nodes, left = .., right = .., size = ..)]`. Nodes are ordered by
+/// id unless `#[tree(compare = path)]` names a
+/// `fn(&Self, Link, Link) -> Ordering`.
+#[proc_macro_derive(SizeBalancedTree, attributes(tree))]
+pub fn derive_size_balanced_tree(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_size_balanced_tree(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Implements the linked list traits for a storage keeping its elements in
+/// an indexable field (usually a `Vec`).
+///
+/// ```ignore
+/// #[derive(LinkedList)]
+/// #[list(link = usize)]
+/// struct Storage {
+///     #[list(nodes)]
+///     elements: Vec<Element>,
+///     #[list(first)]
+///     first: usize,
+///     #[list(last)]
+///     last: usize,
+///     #[list(size)]
+///     size: usize,
+/// }
+/// ```
+///
+/// `LinkedList` is always implemented, with element fields defaulting to
+/// `prev` and `next` (renamed with `#[list(nodes, previous = .., next = ..)]`).
+/// Fields marked `first`, `last` and `size` add `AbsoluteLinkedList` and
+/// `AbsoluteCircularLinkedList`; a field marked `heads`, indexed by head and
+/// holding `first`, `last` and `size`, adds `RelativeLinkedList` and
+/// `RelativeCircularLinkedList`.
+#[proc_macro_derive(LinkedList, attributes(list))]
+pub fn derive_linked_list(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_linked_list(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct NodesField {
+    member: Member,
+    names: Vec<(&'static str, Ident)>,
+}
+
+impl NodesField {
+    fn name(&self, key: &str) -> &Ident {
+        &self
+            .names
+            .iter()
+            .find(|(name, _)| *name == key)
+            .expect("node field names are initialized for every key")
+            .1
+    }
+}
+
+fn struct_fields(input: &DeriveInput) -> Result<&Fields> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(Error::new_spanned(
+            &input.ident,
+            "only structs can be derived as node storages",
+        )),
+    }
+}
+
+fn member(field: &syn::Field, index: usize) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(index.into()),
+    }
+}
+
+fn parse_link(input: &DeriveInput, attribute: &str) -> Result<(Type, Option<Path>)> {
+    let mut link = None;
+    let mut compare = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident(attribute))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("link") {
+                link = Some(meta.value()?.parse::<Type>()?);
+                Ok(())
+            } else if attribute == "tree" && meta.path.is_ident("compare") {
+                compare = Some(meta.value()?.parse::<Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported attribute"))
+            }
+        })?;
+    }
+    let link = link.ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            format!("missing `#[{}(link = ..)]` attribute", attribute),
+        )
+    })?;
+    Ok((link, compare))
+}
+
+fn parse_nodes(
+    fields: &Fields,
+    attribute: &str,
+    defaults: &[(&'static str, &str)],
+    markers: &mut Vec<(String, Member)>,
+) -> Result<Option<NodesField>> {
+    let mut nodes = None;
+    for (index, field) in fields.iter().enumerate() {
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident(attribute))
+        {
+            let mut is_nodes = false;
+            let mut names: Vec<(&'static str, Ident)> = defaults
+                .iter()
+                .map(|(key, name)| (*key, Ident::new(name, Span::call_site())))
+                .collect();
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("nodes") {
+                    is_nodes = true;
+                    return Ok(());
+                }
+                if let Some(entry) = names.iter_mut().find(|(key, _)| meta.path.is_ident(key)) {
+                    entry.1 = meta.value()?.parse::<Ident>()?;
+                    return Ok(());
+                }
+                match meta.path.get_ident() {
+                    Some(ident) => {
+                        markers.push((ident.to_string(), member(field, index)));
+                        Ok(())
+                    }
+                    None => Err(meta.error("unsupported attribute")),
+                }
+            })?;
+            if is_nodes {
+                if nodes.is_some() {
+                    return Err(Error::new_spanned(
+                        field,
+                        "only one field can hold the nodes",
+                    ));
+                }
+                nodes = Some(NodesField {
+                    member: member(field, index),
+                    names,
+                });
+            }
+        }
+    }
+    Ok(nodes)
+}
+
+fn find_marker<'a>(markers: &'a [(String, Member)], name: &str) -> Option<&'a Member> {
+    markers
+        .iter()
+        .find(|(marker, _)| marker == name)
+        .map(|(_, member)| member)
+}
+
+fn expand_size_balanced_tree(input: &DeriveInput) -> Result<TokenStream2> {
+    let fields = struct_fields(input)?;
+    let (link, compare) = parse_link(input, "tree")?;
+    let mut markers = Vec::new();
+    let defaults = [("left", "left"), ("right", "right"), ("size", "size")];
+    let nodes = parse_nodes(fields, "tree", &defaults, &mut markers)?
+        .ok_or_else(|| Error::new_spanned(&input.ident, "missing `#[tree(nodes)]` field"))?;
+    if let Some((marker, _)) = markers.first() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            format!("unsupported field marker `{}`", marker),
+        ));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote!(where));
+    where_clause
+        .predicates
+        .push(syn::parse_quote!(#link: ::platform_trees::LinkType));
+    let nodes_member = &nodes.member;
+    let left = nodes.name("left");
+    let right = nodes.name("right");
+    let size = nodes.name("size");
+    let node = quote!(self.#nodes_member[::platform_trees::__private::index(node)]);
+    let (is_left, is_right) = match compare {
+        Some(compare) => (
+            quote!(#compare(self, first, second) == ::core::cmp::Ordering::Less),
+            quote!(#compare(self, first, second) == ::core::cmp::Ordering::Greater),
+        ),
+        None => (quote!(first < second), quote!(first > second)),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::platform_trees::RecursiveSizeBalancedTree<#link>
+            for #name #type_generics #where_clause
+        {
+            unsafe fn get_mut_left_reference(&mut self, node: #link) -> *mut #link {
+                &mut #node.#left
+            }
+
+            unsafe fn get_mut_right_reference(&mut self, node: #link) -> *mut #link {
+                &mut #node.#right
+            }
+
+            unsafe fn get_left_reference(&self, node: #link) -> *const #link {
+                &#node.#left
+            }
+
+            unsafe fn get_right_reference(&self, node: #link) -> *const #link {
+                &#node.#right
+            }
+
+            unsafe fn get_left(&self, node: #link) -> #link {
+                #node.#left
+            }
+
+            unsafe fn get_right(&self, node: #link) -> #link {
+                #node.#right
+            }
+
+            unsafe fn get_size(&self, node: #link) -> #link {
+                #node.#size
+            }
+
+            unsafe fn set_left(&mut self, node: #link, left: #link) {
+                #node.#left = left;
+            }
+
+            unsafe fn set_right(&mut self, node: #link, right: #link) {
+                #node.#right = right;
+            }
+
+            unsafe fn set_size(&mut self, node: #link, size: #link) {
+                #node.#size = size;
+            }
+
+            unsafe fn first_is_to_the_left_of_second(&self, first: #link, second: #link) -> bool {
+                #is_left
+            }
+
+            unsafe fn first_is_to_the_right_of_second(&self, first: #link, second: #link) -> bool {
+                #is_right
+            }
+        }
+
+        impl #impl_generics ::platform_trees::IterativeSizeBalancedTree<#link>
+            for #name #type_generics #where_clause
+        {
+        }
+    })
+}
+
+fn expand_linked_list(input: &DeriveInput) -> Result<TokenStream2> {
+    let fields = struct_fields(input)?;
+    let (link, _) = parse_link(input, "list")?;
+    let mut markers = Vec::new();
+    let defaults = [("previous", "prev"), ("next", "next")];
+    let nodes = parse_nodes(fields, "list", &defaults, &mut markers)?
+        .ok_or_else(|| Error::new_spanned(&input.ident, "missing `#[list(nodes)]` field"))?;
+    for (marker, _) in &markers {
+        if !["first", "last", "size", "heads"].contains(&marker.as_str()) {
+            return Err(Error::new_spanned(
+                &input.ident,
+                format!("unsupported field marker `{}`", marker),
+            ));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote!(where));
+    where_clause
+        .predicates
+        .push(syn::parse_quote!(#link: ::platform_trees::LinkType));
+    let nodes_member = &nodes.member;
+    let previous = nodes.name("previous");
+    let next = nodes.name("next");
+    let element = quote!(self.#nodes_member[::platform_trees::__private::index(element)]);
+
+    let mut expanded = quote! {
+        impl #impl_generics ::platform_trees::LinkedList<#link>
+            for #name #type_generics #where_clause
+        {
+            fn get_previous(&self, element: #link) -> #link {
+                #element.#previous
+            }
+
+            fn get_next(&self, element: #link) -> #link {
+                #element.#next
+            }
+
+            fn set_previous(&mut self, element: #link, previous: #link) {
+                #element.#previous = previous;
+            }
+
+            fn set_next(&mut self, element: #link, next: #link) {
+                #element.#next = next;
+            }
+        }
+    };
+
+    let first = find_marker(&markers, "first");
+    let last = find_marker(&markers, "last");
+    let size = find_marker(&markers, "size");
+    match (first, last, size) {
+        (Some(first), Some(last), Some(size)) => expanded.extend(quote! {
+            impl #impl_generics ::platform_trees::AbsoluteLinkedList<#link>
+                for #name #type_generics #where_clause
+            {
+                fn get_first(&self) -> #link {
+                    self.#first
+                }
+
+                fn get_last(&self) -> #link {
+                    self.#last
+                }
+
+                fn get_size(&self) -> #link {
+                    self.#size
+                }
+
+                fn set_first(&mut self, element: #link) {
+                    self.#first = element;
+                }
+
+                fn set_last(&mut self, element: #link) {
+                    self.#last = element;
+                }
+
+                fn set_size(&mut self, size: #link) {
+                    self.#size = size;
+                }
+            }
+
+            impl #impl_generics ::platform_trees::AbsoluteCircularLinkedList<#link>
+                for #name #type_generics #where_clause
+            {
+            }
+        }),
+        (None, None, None) => {}
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "`first`, `last` and `size` fields must be marked together",
+            ))
+        }
+    }
+
+    if let Some(heads) = find_marker(&markers, "heads") {
+        let head = quote!(self.#heads[::platform_trees::__private::index(head)]);
+        expanded.extend(quote! {
+            impl #impl_generics ::platform_trees::RelativeLinkedList<#link>
+                for #name #type_generics #where_clause
+            {
+                fn get_first(&self, head: #link) -> #link {
+                    #head.first
+                }
+
+                fn get_last(&self, head: #link) -> #link {
+                    #head.last
+                }
+
+                fn get_size(&self, head: #link) -> #link {
+                    #head.size
+                }
+
+                fn set_first(&mut self, head: #link, element: #link) {
+                    #head.first = element;
+                }
+
+                fn set_last(&mut self, head: #link, element: #link) {
+                    #head.last = element;
+                }
+
+                fn set_size(&mut self, head: #link, size: #link) {
+                    #head.size = size;
+                }
+            }
+
+            impl #impl_generics ::platform_trees::RelativeCircularLinkedList<#link>
+                for #name #type_generics #where_clause
+            {
+            }
+        });
+    }
+
+    Ok(expanded)
+}
@@ -0,0 +1,218 @@
+use platform_trees::{
+    AbsoluteCircularLinkedList, AbsoluteLinkedList, IterativeSizeBalancedTree, LinkType,
+    LinkedList as _, RecursiveSizeBalancedTree, RelativeCircularLinkedList, RelativeLinkedList,
+};
+use platform_trees_derive::{LinkedList, SizeBalancedTree};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct TreeNode {
+    left: usize,
+    right: usize,
+    size: usize,
+}
+
+#[derive(SizeBalancedTree)]
+#[tree(link = usize)]
+struct Tree {
+    #[tree(nodes)]
+    nodes: Vec<TreeNode>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct KeyedNode {
+    key: u32,
+    lesser: u32,
+    greater: u32,
+    count: u32,
+}
+
+#[derive(SizeBalancedTree)]
+#[tree(link = u32, compare = KeyedTree::compare)]
+struct KeyedTree {
+    #[tree(nodes, left = lesser, right = greater, size = count)]
+    nodes: Vec<KeyedNode>,
+}
+
+impl KeyedTree {
+    fn compare(&self, first: u32, second: u32) -> Ordering {
+        self.nodes[first as usize]
+            .key
+            .cmp(&self.nodes[second as usize].key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct GenericNode<T> {
+    left: T,
+    right: T,
+    size: T,
+}
+
+#[derive(SizeBalancedTree)]
+#[tree(link = T)]
+struct GenericTree<T> {
+    #[tree(nodes)]
+    nodes: Vec<GenericNode<T>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Element {
+    prev: usize,
+    next: usize,
+}
+
+#[derive(LinkedList)]
+#[list(link = usize)]
+struct AbsoluteList {
+    #[list(nodes)]
+    elements: Vec<Element>,
+    #[list(first)]
+    first: usize,
+    #[list(last)]
+    last: usize,
+    #[list(size)]
+    size: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Head {
+    first: usize,
+    last: usize,
+    size: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RenamedElement {
+    before: usize,
+    after: usize,
+}
+
+#[derive(LinkedList)]
+#[list(link = usize)]
+struct RelativeList {
+    #[list(nodes, previous = before, next = after)]
+    elements: Vec<RenamedElement>,
+    #[list(heads)]
+    heads: Vec<Head>,
+}
+
+#[derive(LinkedList)]
+#[list(link = usize)]
+struct PlainList(#[list(nodes)] Vec<Element>);
+
+#[test]
+fn size_balanced_tree_with_default_fields() {
+    let mut tree = Tree {
+        nodes: vec![TreeNode::default(); 21],
+    };
+    let mut root = 0;
+    unsafe {
+        for node in 1..=20 {
+            tree.attach(&mut root, node);
+        }
+        assert_eq!(tree.get_size(root), 20);
+        for node in 1..=20 {
+            assert!(tree.contains(node, root));
+        }
+        tree.detach(&mut root, 7);
+        assert!(!tree.contains(7, root));
+        assert_eq!(tree.get_leftest(root), 1);
+        assert_eq!(tree.get_rightest(root), 20);
+    }
+}
+
+#[test]
+fn size_balanced_tree_with_renamed_fields_and_comparer() {
+    let keys = [50, 10, 40, 20, 30];
+    let mut tree = KeyedTree {
+        nodes: vec![KeyedNode::default(); keys.len() + 1],
+    };
+    for (index, key) in keys.iter().enumerate() {
+        tree.nodes[index + 1].key = *key;
+    }
+    let mut root = 0;
+    unsafe {
+        for node in 1..=keys.len() as u32 {
+            tree.attach(&mut root, node);
+        }
+        assert_eq!(tree.get_size(root), 5);
+        assert_eq!(tree.nodes[tree.get_leftest(root) as usize].key, 10);
+        assert_eq!(tree.nodes[tree.get_rightest(root) as usize].key, 50);
+        assert!(tree.first_is_to_the_left_of_second(2, 1));
+        assert!(tree.first_is_to_the_right_of_second(3, 4));
+    }
+}
+
+fn fill_generic<T: LinkType>(count: u8) -> (GenericTree<T>, T) {
+    let mut tree = GenericTree {
+        nodes: vec![GenericNode::default(); count as usize + 1],
+    };
+    let mut root = T::funty(0);
+    unsafe {
+        for node in 1..=count {
+            tree.attach(&mut root, T::funty(node));
+        }
+    }
+    (tree, root)
+}
+
+#[test]
+fn size_balanced_tree_with_generic_link() {
+    let (tree, root) = fill_generic::<u16>(30);
+    unsafe {
+        assert_eq!(tree.get_size(root), 30);
+        assert!(tree.contains(15, root));
+    }
+}
+
+#[test]
+fn absolute_linked_list() {
+    let mut list = AbsoluteList {
+        elements: vec![Element::default(); 6],
+        first: 0,
+        last: 0,
+        size: 0,
+    };
+    for element in 1..=5 {
+        list.attach_as_last(element);
+    }
+    AbsoluteCircularLinkedList::detach(&mut list, 3);
+
+    assert_eq!(list.get_first(), 1);
+    assert_eq!(list.get_last(), 5);
+    assert_eq!(AbsoluteLinkedList::get_size(&list), 4);
+    assert_eq!(list.get_next(2), 4);
+    assert_eq!(list.get_previous(1), 5);
+}
+
+#[test]
+fn relative_linked_list_with_renamed_fields() {
+    let mut list = RelativeList {
+        elements: vec![RenamedElement::default(); 7],
+        heads: vec![Head::default(); 3],
+    };
+    for element in 1..=3 {
+        list.attach_as_last(1, element);
+    }
+    for element in 4..=6 {
+        list.attach_as_first(2, element);
+    }
+
+    assert_eq!(list.get_first(1), 1);
+    assert_eq!(list.get_last(1), 3);
+    assert_eq!(list.get_first(2), 6);
+    assert_eq!(RelativeLinkedList::get_size(&list, 2), 3);
+    assert_eq!(list.elements[2].after, 3);
+    assert_eq!(list.elements[4].before, 5);
+}
+
+#[test]
+fn plain_linked_list_on_tuple_struct() {
+    let mut list = PlainList(vec![Element::default(); 3]);
+    list.set_next(1, 2);
+    list.set_previous(2, 1);
+
+    assert_eq!(list.get_next(1), 2);
+    assert_eq!(list.get_previous(2), 1);
+}
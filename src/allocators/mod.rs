@@ -0,0 +1,3 @@
+mod node_allocator;
+
+pub use node_allocator::NodeAllocator;
@@ -0,0 +1,64 @@
+use crate::{AbsoluteCircularLinkedList, AbsoluteLinkedList, LinkType};
+
+/// Node id allocator keeping released ids in an absolute circular list.
+///
+/// Fresh ids are handed out by growing the high-water mark returned by
+/// `get_allocated`; released ids are attached to the list and reused first.
+/// An id that is in use must have its `next` set to `0`, which is exactly the
+/// state `detach` leaves it in.
+pub trait NodeAllocator<T: LinkType>: AbsoluteCircularLinkedList<T> {
+    fn get_allocated(&self) -> T;
+
+    fn set_allocated(&mut self, allocated: T);
+
+    fn is_unused(&self, node: T) -> bool {
+        node != T::funty(0) && self.get_next(node) != T::funty(0)
+    }
+
+    fn get_unused_count(&self) -> T {
+        AbsoluteLinkedList::get_size(self)
+    }
+
+    fn get_used_count(&self) -> T {
+        self.get_allocated() - self.get_unused_count()
+    }
+
+    fn allocate(&mut self) -> T {
+        let first = self.get_first();
+        if first != T::funty(0) {
+            self.detach(first);
+            first
+        } else {
+            let node = self.get_allocated() + T::funty(1);
+            self.set_allocated(node);
+            node
+        }
+    }
+
+    /// Releases `node` for reuse. Freeing `0`, an id above the high-water
+    /// mark or an id that is already free does nothing.
+    fn free(&mut self, node: T) {
+        if node == T::funty(0) || node > self.get_allocated() || self.is_unused(node) {
+            return;
+        }
+        if node == self.get_allocated() {
+            self.set_allocated(node - T::funty(1));
+            self.compact();
+        } else {
+            self.attach_as_first(node);
+        }
+    }
+
+    fn compact(&mut self) -> T {
+        let mut reclaimed = T::funty(0);
+        loop {
+            let last = self.get_allocated();
+            if !self.is_unused(last) {
+                return reclaimed;
+            }
+            self.detach(last);
+            self.set_allocated(last - T::funty(1));
+            reclaimed += T::funty(1);
+        }
+    }
+}
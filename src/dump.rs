@@ -0,0 +1,347 @@
+use crate::{LinkType, RecursiveSizeBalancedTree, RelativeLinkedList};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Links of one tree node in a [`TreeDump`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeNodeDump<T> {
+    pub id: T,
+    pub left: T,
+    pub right: T,
+    pub size: T,
+}
+
+/// Portable copy of the structure of one size-balanced tree.
+///
+/// Nodes are listed in pre-order, so a dump of the same tree is always
+/// the same. Keys are not part of the dump: the storage it is restored into
+/// must order the same ids the same way.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeDump<T> {
+    pub root: T,
+    pub nodes: Vec<TreeNodeDump<T>>,
+}
+
+/// Links of one list element in a [`ListsDump`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListNodeDump<T> {
+    pub id: T,
+    pub previous: T,
+    pub next: T,
+}
+
+/// Head fields of one list in a [`ListsDump`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListHeadDump<T> {
+    pub head: T,
+    pub first: T,
+    pub last: T,
+    pub size: T,
+}
+
+/// Portable copy of a set of circular relative lists.
+///
+/// Elements are listed per head, in list order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListsDump<T> {
+    pub heads: Vec<ListHeadDump<T>>,
+    pub nodes: Vec<ListNodeDump<T>>,
+}
+
+/// Inconsistency found while validating a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpError<T> {
+    /// The null id (`0`) is listed as a node.
+    NullNode,
+    /// A node is listed twice.
+    DuplicateNode(T),
+    /// A link or head points to a node that is not listed.
+    MissingNode(T),
+    /// A node is reached twice, through a cycle or from two parents or
+    /// lists.
+    SharedNode(T),
+    /// A listed node is not reachable from any root or head.
+    UnreachableNode(T),
+    /// A stored size does not match the number of nodes reached.
+    WrongSize { node: T, expected: T, found: T },
+    /// Two nodes are in an order the storage does not agree with.
+    OutOfOrder { left: T, right: T },
+    /// A list element whose neighbors do not link back to it.
+    BrokenLink(T),
+}
+
+impl<T: fmt::Display> fmt::Display for DumpError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpError::NullNode => write!(f, "null node is listed in the dump"),
+            DumpError::DuplicateNode(node) => write!(f, "node {} is listed twice", node),
+            DumpError::MissingNode(node) => write!(f, "node {} is linked but not listed", node),
+            DumpError::SharedNode(node) => write!(f, "node {} is reached twice", node),
+            DumpError::UnreachableNode(node) => write!(f, "node {} is not reachable", node),
+            DumpError::WrongSize {
+                node,
+                expected,
+                found,
+            } => write!(
+                f,
+                "node {} has size {} but {} nodes were reached",
+                node, found, expected
+            ),
+            DumpError::OutOfOrder { left, right } => {
+                write!(f, "node {} is not to the left of node {}", left, right)
+            }
+            DumpError::BrokenLink(node) => write!(f, "links around node {} do not match", node),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug + fmt::Display> std::error::Error for DumpError<T> {}
+
+/// Maps node ids to their position in a dump, rejecting null and duplicate
+/// ids.
+fn index_nodes<T: LinkType>(
+    ids: impl Iterator<Item = T>,
+) -> Result<BTreeMap<T, usize>, DumpError<T>> {
+    let mut indices = BTreeMap::new();
+    for (index, id) in ids.enumerate() {
+        if id == T::funty(0) {
+            return Err(DumpError::NullNode);
+        }
+        if indices.insert(id, index).is_some() {
+            return Err(DumpError::DuplicateNode(id));
+        }
+    }
+    Ok(indices)
+}
+
+impl<T: LinkType> TreeDump<T> {
+    /// Dumps the tree at `root` of `storage`.
+    ///
+    /// # Safety
+    ///
+    /// `root` must be `0` or the root of a well-formed tree of `storage`.
+    pub unsafe fn capture<S: RecursiveSizeBalancedTree<T>>(storage: &S, root: T) -> Self {
+        let mut nodes = Vec::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if node == T::funty(0) {
+                continue;
+            }
+            let left = storage.get_left(node);
+            let right = storage.get_right(node);
+            nodes.push(TreeNodeDump {
+                id: node,
+                left,
+                right,
+                size: storage.get_size(node),
+            });
+            stack.push(right);
+            stack.push(left);
+        }
+        Self { root, nodes }
+    }
+
+    /// Checks that the dump describes one tree: every listed node is reached
+    /// exactly once from `root`, every link points to a listed node, and
+    /// every size matches its subtree.
+    pub fn validate(&self) -> Result<(), DumpError<T>> {
+        self.in_order().map(|_| ())
+    }
+
+    /// Validates the dump and returns its node indices in order.
+    fn in_order(&self) -> Result<Vec<usize>, DumpError<T>> {
+        let indices = index_nodes(self.nodes.iter().map(|node| node.id))?;
+        let find = |id: T| indices.get(&id).copied().ok_or(DumpError::MissingNode(id));
+        let child_index = |id: T| {
+            if id == T::funty(0) {
+                Ok(None)
+            } else {
+                find(id).map(Some)
+            }
+        };
+
+        // Pre-order walk, which also rejects cycles and shared subtrees.
+        let mut reached = vec![false; self.nodes.len()];
+        let mut pre_order = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<usize> = child_index(self.root)?.into_iter().collect();
+        while let Some(index) = stack.pop() {
+            if reached[index] {
+                return Err(DumpError::SharedNode(self.nodes[index].id));
+            }
+            reached[index] = true;
+            pre_order.push(index);
+            let node = &self.nodes[index];
+            stack.extend(child_index(node.right)?);
+            stack.extend(child_index(node.left)?);
+        }
+        if let Some(index) = reached.iter().position(|&reached| !reached) {
+            return Err(DumpError::UnreachableNode(self.nodes[index].id));
+        }
+
+        // Children come after their parent in pre-order, so the reverse
+        // order counts every subtree before the node above it.
+        let mut counts = vec![T::funty(0); self.nodes.len()];
+        for &index in pre_order.iter().rev() {
+            let node = &self.nodes[index];
+            let count_of = |id: T| {
+                child_index(id).map(|child| child.map_or(T::funty(0), |child| counts[child]))
+            };
+            let expected = count_of(node.left)? + count_of(node.right)? + T::funty(1);
+            if node.size != expected {
+                return Err(DumpError::WrongSize {
+                    node: node.id,
+                    expected,
+                    found: node.size,
+                });
+            }
+            counts[index] = expected;
+        }
+
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack = Vec::new();
+        let mut current = child_index(self.root)?;
+        while current.is_some() || !stack.is_empty() {
+            while let Some(index) = current {
+                stack.push(index);
+                current = child_index(self.nodes[index].left)?;
+            }
+            let index = stack.pop().unwrap();
+            order.push(index);
+            current = child_index(self.nodes[index].right)?;
+        }
+        Ok(order)
+    }
+
+    /// Validates the dump, checks that `storage` orders its nodes the same
+    /// way, writes their links and sizes, and returns the root.
+    ///
+    /// Nothing is written when validation fails.
+    ///
+    /// # Safety
+    ///
+    /// Every listed id must be a valid node of `storage`, and none of them
+    /// may belong to another tree of it.
+    pub unsafe fn restore<S: RecursiveSizeBalancedTree<T>>(
+        &self,
+        storage: &mut S,
+    ) -> Result<T, DumpError<T>> {
+        let order = self.in_order()?;
+        for pair in order.windows(2) {
+            let (left, right) = (self.nodes[pair[0]].id, self.nodes[pair[1]].id);
+            if !storage.first_is_to_the_left_of_second(left, right) {
+                return Err(DumpError::OutOfOrder { left, right });
+            }
+        }
+        for node in &self.nodes {
+            storage.set_left(node.id, node.left);
+            storage.set_right(node.id, node.right);
+            storage.set_size(node.id, node.size);
+        }
+        Ok(self.root)
+    }
+}
+
+impl<T: LinkType> ListsDump<T> {
+    /// Dumps the circular lists of `storage` at `heads`.
+    pub fn capture<S: RelativeLinkedList<T>>(storage: &S, heads: &[T]) -> Self {
+        let mut dump = Self::default();
+        for &head in heads {
+            let first = storage.get_first(head);
+            let size = storage.get_size(head);
+            dump.heads.push(ListHeadDump {
+                head,
+                first,
+                last: storage.get_last(head),
+                size,
+            });
+            let mut element = first;
+            let mut index = T::funty(0);
+            while index < size {
+                dump.nodes.push(ListNodeDump {
+                    id: element,
+                    previous: storage.get_previous(element),
+                    next: storage.get_next(element),
+                });
+                element = storage.get_next(element);
+                index += T::funty(1);
+            }
+        }
+        dump
+    }
+
+    /// Checks that every head describes a circular list of `size` listed
+    /// elements whose links agree in both directions, and that every listed
+    /// element is in exactly one list.
+    pub fn validate(&self) -> Result<(), DumpError<T>> {
+        let indices = index_nodes(self.nodes.iter().map(|node| node.id))?;
+        let find = |id: T| indices.get(&id).copied().ok_or(DumpError::MissingNode(id));
+        let mut reached = BTreeSet::new();
+        for head in &self.heads {
+            if head.first == T::funty(0) {
+                if head.size != T::funty(0) {
+                    return Err(DumpError::WrongSize {
+                        node: head.head,
+                        expected: T::funty(0),
+                        found: head.size,
+                    });
+                }
+                if head.last != T::funty(0) {
+                    return Err(DumpError::BrokenLink(head.head));
+                }
+                continue;
+            }
+            let mut element = head.first;
+            let mut count = T::funty(0);
+            loop {
+                let node = &self.nodes[find(element)?];
+                if !reached.insert(element) {
+                    return Err(DumpError::SharedNode(element));
+                }
+                count += T::funty(1);
+                let next = &self.nodes[find(node.next)?];
+                if next.previous != element {
+                    return Err(DumpError::BrokenLink(element));
+                }
+                if node.next == head.first {
+                    if element != head.last {
+                        return Err(DumpError::BrokenLink(element));
+                    }
+                    break;
+                }
+                element = node.next;
+            }
+            if count != head.size {
+                return Err(DumpError::WrongSize {
+                    node: head.head,
+                    expected: count,
+                    found: head.size,
+                });
+            }
+        }
+        if let Some(node) = self.nodes.iter().find(|node| !reached.contains(&node.id)) {
+            return Err(DumpError::UnreachableNode(node.id));
+        }
+        Ok(())
+    }
+
+    /// Validates the dump and writes every head and element into `storage`.
+    ///
+    /// Nothing is written when validation fails.
+    pub fn restore<S: RelativeLinkedList<T>>(&self, storage: &mut S) -> Result<(), DumpError<T>> {
+        self.validate()?;
+        for node in &self.nodes {
+            storage.set_previous(node.id, node.previous);
+            storage.set_next(node.id, node.next);
+        }
+        for head in &self.heads {
+            storage.set_first(head.head, head.first);
+            storage.set_last(head.head, head.last);
+            storage.set_size(head.head, head.size);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,16 @@
+/// Tells an iteration whether to go on or stop early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Break,
+}
+
+impl Flow {
+    pub fn is_continue(self) -> bool {
+        self == Flow::Continue
+    }
+
+    pub fn is_break(self) -> bool {
+        self == Flow::Break
+    }
+}
@@ -0,0 +1,20 @@
+/// SplitMix64 finalizer: a fast, well-distributed bijection on `u64`.
+///
+/// Used to derive per-node randomness (treap priorities, skip list levels, shards)
+/// from node ids, so storages need no extra field for it.
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Hashes a node id of any width, folding the high half of `u128` ids into
+/// the low one.
+///
+/// Ids that agree in their high 64 bits never collide, so every id that
+/// fits in a `u64` gets a distinct hash.
+pub(crate) fn link<T: crate::LinkType>(node: T) -> u64 {
+    let node = node.as_u128();
+    splitmix64(node as u64 ^ splitmix64((node >> 64) as u64))
+}
@@ -0,0 +1,302 @@
+use crate::{Flow, IterativeSizeBalancedTree, LinkType, RecursiveSizeBalancedTree};
+
+/// Upper bound on the height of a size-balanced tree addressed by 64-bit ids
+/// (`log_φ(√5 · (2^64 + 1.5)) - 2`, about 92).
+const MAX_DEPTH: usize = 128;
+
+/// Tree fields a link keeps for one of its indexes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexNode<T> {
+    pub left: T,
+    pub right: T,
+    pub size: T,
+}
+
+/// Selects one of the two trees of a [`DoubletsIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinksTree {
+    /// Links ordered by `(source, target)`.
+    Sources,
+    /// Links ordered by `(target, source)`.
+    Targets,
+}
+
+/// Doublets-style dual index: every link lives in a sources tree ordered by
+/// `(source, target)` and in a targets tree ordered by `(target, source)`.
+///
+/// Links with equal keys are ordered by id, so duplicates are allowed.
+pub trait DoubletsIndex<T: LinkType> {
+    fn get_source(&self, link: T) -> T;
+
+    fn get_target(&self, link: T) -> T;
+
+    fn get_index_node(&self, tree: LinksTree, link: T) -> &IndexNode<T>;
+
+    fn get_mut_index_node(&mut self, tree: LinksTree, link: T) -> &mut IndexNode<T>;
+
+    fn get_index_root(&self, tree: LinksTree) -> T;
+
+    fn set_index_root(&mut self, tree: LinksTree, root: T);
+
+    /// Attaches `link` to both trees.
+    ///
+    /// # Safety
+    ///
+    /// `link` must not be attached yet and its source and target must not
+    /// change until it is detached.
+    unsafe fn attach_link(&mut self, link: T) {
+        for tree in [LinksTree::Sources, LinksTree::Targets] {
+            let mut root = self.get_index_root(tree);
+            *self.get_mut_index_node(tree, link) = IndexNode::default();
+            IndexTree { links: self, tree }.attach(&mut root, link);
+            self.set_index_root(tree, root);
+        }
+    }
+
+    /// Detaches `link` from both trees.
+    ///
+    /// # Safety
+    ///
+    /// `link` must be attached.
+    unsafe fn detach_link(&mut self, link: T) {
+        for tree in [LinksTree::Sources, LinksTree::Targets] {
+            let mut root = self.get_index_root(tree);
+            IndexTree { links: self, tree }.detach(&mut root, link);
+            self.set_index_root(tree, root);
+        }
+    }
+
+    /// Returns a link with the given source and target, or `0` if none exists.
+    fn search(&self, source: T, target: T) -> T {
+        let key = (source, target);
+        let mut current = self.get_index_root(LinksTree::Sources);
+        while current != T::funty(0) {
+            let current_key = get_index_key(self, LinksTree::Sources, current);
+            let node = self.get_index_node(LinksTree::Sources, current);
+            if key < current_key {
+                current = node.left;
+            } else if key > current_key {
+                current = node.right;
+            } else {
+                return current;
+            }
+        }
+        T::funty(0)
+    }
+
+    fn count_by_source(&self, source: T) -> T {
+        count_by_key(self, LinksTree::Sources, source)
+    }
+
+    fn count_by_target(&self, target: T) -> T {
+        count_by_key(self, LinksTree::Targets, target)
+    }
+
+    /// Counts links with exactly the given source and target.
+    fn count(&self, source: T, target: T) -> T {
+        let key = (source, target);
+        let through = count_before(self, LinksTree::Sources, |link| {
+            get_index_key(self, LinksTree::Sources, link) <= key
+        });
+        let before = count_before(self, LinksTree::Sources, |link| {
+            get_index_key(self, LinksTree::Sources, link) < key
+        });
+        through - before
+    }
+
+    /// Counts the other links referring to `link` as source or target.
+    fn count_usages(&self, link: T) -> T {
+        let mut usages =
+            self.count_by_source(link) + self.count_by_target(link) - self.count(link, link);
+        if self.get_source(link) == link || self.get_target(link) == link {
+            usages -= T::funty(1);
+        }
+        usages
+    }
+
+    /// Visits links with the given source in `(source, target)` order.
+    fn each_by_source<H: FnMut(T) -> Flow>(&self, source: T, handler: H) -> Flow {
+        each_by_key(self, LinksTree::Sources, source, handler)
+    }
+
+    /// Visits links with the given target in `(target, source)` order.
+    fn each_by_target<H: FnMut(T) -> Flow>(&self, target: T, handler: H) -> Flow {
+        each_by_key(self, LinksTree::Targets, target, handler)
+    }
+
+    /// Visits every other link referring to `link`, each one exactly once:
+    /// first those using it as source, then the rest using it as target.
+    fn each_usage<H: FnMut(T) -> Flow>(&self, link: T, mut handler: H) -> Flow {
+        let flow = self.each_by_source(link, |usage| {
+            if usage == link {
+                Flow::Continue
+            } else {
+                handler(usage)
+            }
+        });
+        if flow.is_break() {
+            return Flow::Break;
+        }
+        self.each_by_target(link, |usage| {
+            if usage == link || self.get_source(usage) == link {
+                Flow::Continue
+            } else {
+                handler(usage)
+            }
+        })
+    }
+}
+
+fn get_index_key<T: LinkType, S: DoubletsIndex<T> + ?Sized>(
+    links: &S,
+    tree: LinksTree,
+    link: T,
+) -> (T, T) {
+    match tree {
+        LinksTree::Sources => (links.get_source(link), links.get_target(link)),
+        LinksTree::Targets => (links.get_target(link), links.get_source(link)),
+    }
+}
+
+/// Counts links of `tree` that satisfy `is_before`, which must hold for a
+/// prefix of the tree order.
+fn count_before<T: LinkType, S: DoubletsIndex<T> + ?Sized>(
+    links: &S,
+    tree: LinksTree,
+    is_before: impl Fn(T) -> bool,
+) -> T {
+    let mut count = T::funty(0);
+    let mut current = links.get_index_root(tree);
+    while current != T::funty(0) {
+        let node = links.get_index_node(tree, current);
+        if is_before(current) {
+            if node.left != T::funty(0) {
+                count += links.get_index_node(tree, node.left).size;
+            }
+            count += T::funty(1);
+            current = node.right;
+        } else {
+            current = node.left;
+        }
+    }
+    count
+}
+
+fn count_by_key<T: LinkType, S: DoubletsIndex<T> + ?Sized>(
+    links: &S,
+    tree: LinksTree,
+    first: T,
+) -> T {
+    let through = count_before(links, tree, |link| {
+        get_index_key(links, tree, link).0 <= first
+    });
+    let before = count_before(links, tree, |link| {
+        get_index_key(links, tree, link).0 < first
+    });
+    through - before
+}
+
+/// Visits the links of `tree` whose key starts with `first`, in tree order.
+///
+/// Those links form one run of the tree order, so `pending` only ever holds
+/// matching links on a single root path: the ones whose left subtree is
+/// being visited.
+fn each_by_key<T: LinkType, S: DoubletsIndex<T> + ?Sized, H: FnMut(T) -> Flow>(
+    links: &S,
+    tree: LinksTree,
+    first: T,
+    mut handler: H,
+) -> Flow {
+    let mut pending = [T::funty(0); MAX_DEPTH];
+    let mut depth = 0;
+    let mut current = links.get_index_root(tree);
+    loop {
+        while current != T::funty(0) {
+            let node = links.get_index_node(tree, current);
+            let current_first = get_index_key(links, tree, current).0;
+            if first < current_first {
+                current = node.left;
+            } else if first > current_first {
+                current = node.right;
+            } else {
+                pending[depth] = current;
+                depth += 1;
+                current = node.left;
+            }
+        }
+        if depth == 0 {
+            return Flow::Continue;
+        }
+        depth -= 1;
+        let link = pending[depth];
+        if handler(link).is_break() {
+            return Flow::Break;
+        }
+        current = links.get_index_node(tree, link).right;
+    }
+}
+
+struct IndexTree<'a, S: ?Sized> {
+    links: &'a mut S,
+    tree: LinksTree,
+}
+
+impl<'a, T: LinkType, S: DoubletsIndex<T> + ?Sized> RecursiveSizeBalancedTree<T>
+    for IndexTree<'a, S>
+{
+    unsafe fn get_mut_left_reference(&mut self, node: T) -> *mut T {
+        &mut self.links.get_mut_index_node(self.tree, node).left
+    }
+
+    unsafe fn get_mut_right_reference(&mut self, node: T) -> *mut T {
+        &mut self.links.get_mut_index_node(self.tree, node).right
+    }
+
+    unsafe fn get_left_reference(&self, node: T) -> *const T {
+        &self.links.get_index_node(self.tree, node).left
+    }
+
+    unsafe fn get_right_reference(&self, node: T) -> *const T {
+        &self.links.get_index_node(self.tree, node).right
+    }
+
+    unsafe fn get_left(&self, node: T) -> T {
+        self.links.get_index_node(self.tree, node).left
+    }
+
+    unsafe fn get_right(&self, node: T) -> T {
+        self.links.get_index_node(self.tree, node).right
+    }
+
+    unsafe fn get_size(&self, node: T) -> T {
+        self.links.get_index_node(self.tree, node).size
+    }
+
+    unsafe fn set_left(&mut self, node: T, left: T) {
+        self.links.get_mut_index_node(self.tree, node).left = left;
+    }
+
+    unsafe fn set_right(&mut self, node: T, right: T) {
+        self.links.get_mut_index_node(self.tree, node).right = right;
+    }
+
+    unsafe fn set_size(&mut self, node: T, size: T) {
+        self.links.get_mut_index_node(self.tree, node).size = size;
+    }
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool {
+        (get_index_key(self.links, self.tree, first), first)
+            < (get_index_key(self.links, self.tree, second), second)
+    }
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool {
+        (get_index_key(self.links, self.tree, first), first)
+            > (get_index_key(self.links, self.tree, second), second)
+    }
+}
+
+impl<'a, T: LinkType, S: DoubletsIndex<T> + ?Sized> IterativeSizeBalancedTree<T>
+    for IndexTree<'a, S>
+{
+}
@@ -0,0 +1,7 @@
+mod doublets_index;
+#[cfg(feature = "std")]
+mod sharded_index;
+
+pub use doublets_index::{DoubletsIndex, IndexNode, LinksTree};
+#[cfg(feature = "std")]
+pub use sharded_index::ShardedIndex;
@@ -0,0 +1,312 @@
+use crate::{hash, Flow, IterativeSizeBalancedTree, LinkType};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::vec::Vec;
+
+/// Storage and root of one shard of a [`ShardedIndex`].
+struct Shard<T, S> {
+    storage: S,
+    root: T,
+}
+
+/// Ordered index split across independent size-balanced trees, each behind
+/// its own lock, so writers to different shards do not contend.
+///
+/// A node belongs to the shard picked by a hash of its id. Single-node
+/// updates lock one shard; `attach_all` and `detach_all` update every shard
+/// from its own scoped thread. Ordered queries lock every shard in index
+/// order and combine them: `each` merges the shards' in-order sequences,
+/// while `rank` and `select` sum per-shard ranks computed from subtree sizes.
+///
+/// The comparison methods of every shard's storage must define the same
+/// total order, including on nodes of other shards, because merging and
+/// ranking compare nodes across shards.
+pub struct ShardedIndex<T, S> {
+    shards: Vec<Mutex<Shard<T, S>>>,
+}
+
+impl<T: LinkType, S: IterativeSizeBalancedTree<T>> ShardedIndex<T, S> {
+    /// Creates an index with one empty shard per storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `storages` is empty.
+    pub fn new(storages: Vec<S>) -> Self {
+        assert!(!storages.is_empty(), "a sharded index needs a shard");
+        Self {
+            shards: storages
+                .into_iter()
+                .map(|storage| {
+                    Mutex::new(Shard {
+                        storage,
+                        root: T::funty(0),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard that holds `node`.
+    pub fn shard_of(&self, node: T) -> usize {
+        (hash::splitmix64(node.as_u64()) % self.shards.len() as u64) as usize
+    }
+
+    fn lock(&self, shard: usize) -> MutexGuard<'_, Shard<T, S>> {
+        self.shards[shard].lock().unwrap()
+    }
+
+    fn lock_all(&self) -> Vec<MutexGuard<'_, Shard<T, S>>> {
+        (0..self.shards.len())
+            .map(|shard| self.lock(shard))
+            .collect()
+    }
+
+    /// # Safety
+    ///
+    /// Same contract as [`IterativeSizeBalancedTree::attach`] on the shard
+    /// of `node`.
+    pub unsafe fn attach(&self, node: T) {
+        let mut shard = self.lock(self.shard_of(node));
+        let Shard { storage, root } = &mut *shard;
+        storage.attach(root, node);
+    }
+
+    /// # Safety
+    ///
+    /// Same contract as [`IterativeSizeBalancedTree::detach`] on the shard
+    /// of `node`.
+    pub unsafe fn detach(&self, node: T) {
+        let mut shard = self.lock(self.shard_of(node));
+        let Shard { storage, root } = &mut *shard;
+        storage.detach(root, node);
+    }
+
+    /// Attaches `nodes`, updating the shards in parallel.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as [`ShardedIndex::attach`] for every node.
+    pub unsafe fn attach_all(&self, nodes: &[T])
+    where
+        T: Send + Sync,
+        S: Send,
+    {
+        self.update_all(nodes, |storage, root, node| storage.attach(root, node));
+    }
+
+    /// Detaches `nodes`, updating the shards in parallel.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as [`ShardedIndex::detach`] for every node.
+    pub unsafe fn detach_all(&self, nodes: &[T])
+    where
+        T: Send + Sync,
+        S: Send,
+    {
+        self.update_all(nodes, |storage, root, node| storage.detach(root, node));
+    }
+
+    unsafe fn update_all(&self, nodes: &[T], update: unsafe fn(&mut S, &mut T, T))
+    where
+        T: Send + Sync,
+        S: Send,
+    {
+        thread::scope(|scope| {
+            for index in 0..self.shards.len() {
+                scope.spawn(move || {
+                    let mut shard = self.lock(index);
+                    let Shard { storage, root } = &mut *shard;
+                    for &node in nodes.iter().filter(|&&node| self.shard_of(node) == index) {
+                        // SAFETY: guaranteed by the caller of `attach_all` or
+                        // `detach_all`.
+                        unsafe { update(storage, root, node) };
+                    }
+                });
+            }
+        });
+    }
+
+    /// Number of nodes in all shards.
+    pub fn len(&self) -> T {
+        let mut total = T::funty(0);
+        for index in 0..self.shards.len() {
+            let shard = self.lock(index);
+            total += unsafe { shard.storage.get_size_or_zero(shard.root) };
+        }
+        total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == T::funty(0)
+    }
+
+    /// # Safety
+    ///
+    /// `node` must be a valid node for the comparison methods.
+    pub unsafe fn contains(&self, node: T) -> bool {
+        let shard = self.lock(self.shard_of(node));
+        shard.storage.contains(node, shard.root)
+    }
+
+    /// Number of nodes in the index that are to the left of `node`, which
+    /// need not be in the index.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node for the comparison methods.
+    pub unsafe fn rank(&self, node: T) -> T {
+        let shards = self.lock_all();
+        let mut rank = T::funty(0);
+        for shard in &shards {
+            rank += count_left_of(&shard.storage, shard.root, node);
+        }
+        rank
+    }
+
+    /// Returns the node with `index` nodes of the index to its left, or `0`
+    /// when `index` is out of range.
+    pub fn select(&self, index: T) -> T {
+        let shards = self.lock_all();
+        for shard in &shards {
+            let (storage, root) = (&shard.storage, shard.root);
+            let mut low = T::funty(0);
+            let mut high = unsafe { storage.get_size_or_zero(root) };
+            while low < high {
+                let middle = low + (high - low) / T::funty(2);
+                let candidate = unsafe { select_in(storage, root, middle) };
+                let mut rank = T::funty(0);
+                for other in &shards {
+                    rank += unsafe { count_left_of(&other.storage, other.root, candidate) };
+                }
+                if rank < index {
+                    low = middle + T::funty(1);
+                } else if rank > index {
+                    high = middle;
+                } else {
+                    return candidate;
+                }
+            }
+        }
+        T::funty(0)
+    }
+
+    /// Calls `handler` with every node of the index in order, merging the
+    /// shards.
+    pub fn each<H: FnMut(T) -> Flow>(&self, mut handler: H) -> Flow {
+        let shards = self.lock_all();
+        let mut cursors: Vec<InOrder<T>> = shards
+            .iter()
+            .map(|shard| unsafe { InOrder::new(&shard.storage, shard.root) })
+            .collect();
+        loop {
+            let mut smallest: Option<usize> = None;
+            for (index, cursor) in cursors.iter().enumerate() {
+                let Some(head) = cursor.peek() else {
+                    continue;
+                };
+                let is_smaller = smallest.map_or(true, |smallest| unsafe {
+                    shards[index]
+                        .storage
+                        .first_is_to_the_left_of_second(head, cursors[smallest].peek().unwrap())
+                });
+                if is_smaller {
+                    smallest = Some(index);
+                }
+            }
+            let Some(index) = smallest else {
+                return Flow::Continue;
+            };
+            let node = unsafe { cursors[index].advance(&shards[index].storage) };
+            if handler(node).is_break() {
+                return Flow::Break;
+            }
+        }
+    }
+
+    /// Returns every shard's storage and root.
+    pub fn into_shards(self) -> Vec<(S, T)> {
+        self.shards
+            .into_iter()
+            .map(|shard| {
+                let shard = shard.into_inner().unwrap();
+                (shard.storage, shard.root)
+            })
+            .collect()
+    }
+}
+
+/// Counts the nodes of the tree at `root` that are to the left of `node`.
+unsafe fn count_left_of<T: LinkType, S: IterativeSizeBalancedTree<T>>(
+    storage: &S,
+    mut root: T,
+    node: T,
+) -> T {
+    let mut count = T::funty(0);
+    while root != T::funty(0) {
+        if storage.first_is_to_the_right_of_second(node, root) {
+            count += storage.get_left_size(root) + T::funty(1);
+            root = storage.get_right(root);
+        } else {
+            root = storage.get_left(root);
+        }
+    }
+    count
+}
+
+/// Returns the node of the tree at `root` with `index` nodes to its left,
+/// or `0` when there is none.
+unsafe fn select_in<T: LinkType, S: IterativeSizeBalancedTree<T>>(
+    storage: &S,
+    mut root: T,
+    mut index: T,
+) -> T {
+    while root != T::funty(0) {
+        let left_size = storage.get_left_size(root);
+        if index < left_size {
+            root = storage.get_left(root);
+        } else if index > left_size {
+            index -= left_size + T::funty(1);
+            root = storage.get_right(root);
+        } else {
+            return root;
+        }
+    }
+    T::funty(0)
+}
+
+/// In-order cursor over one tree, holding the path of nodes whose left
+/// subtrees have been visited but which have not been yielded yet.
+struct InOrder<T> {
+    stack: Vec<T>,
+}
+
+impl<T: LinkType> InOrder<T> {
+    unsafe fn new<S: IterativeSizeBalancedTree<T>>(storage: &S, root: T) -> Self {
+        let mut cursor = Self { stack: Vec::new() };
+        cursor.descend_left(storage, root);
+        cursor
+    }
+
+    fn peek(&self) -> Option<T> {
+        self.stack.last().copied()
+    }
+
+    /// Pops the current node and moves to its successor.
+    unsafe fn advance<S: IterativeSizeBalancedTree<T>>(&mut self, storage: &S) -> T {
+        let node = self.stack.pop().unwrap();
+        self.descend_left(storage, storage.get_right(node));
+        node
+    }
+
+    unsafe fn descend_left<S: IterativeSizeBalancedTree<T>>(&mut self, storage: &S, mut node: T) {
+        while node != T::funty(0) {
+            self.stack.push(node);
+            node = storage.get_left(node);
+        }
+    }
+}
@@ -1,16 +1,67 @@
-// fixme: #![no_std]
+#![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+mod allocators;
+#[cfg(feature = "serde")]
+mod dump;
+mod flow;
+mod hash;
+mod indexes;
 mod link_type;
 mod lists;
+#[cfg(feature = "std")]
+mod snapshot;
+mod storages;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod trees;
 
 #[cfg(test)]
 mod tests;
 
+pub use allocators::NodeAllocator;
+#[cfg(feature = "serde")]
+pub use dump::{DumpError, ListHeadDump, ListNodeDump, ListsDump, TreeDump, TreeNodeDump};
+pub use flow::Flow;
+#[cfg(feature = "std")]
+pub use indexes::ShardedIndex;
+pub use indexes::{DoubletsIndex, IndexNode, LinksTree};
 pub use link_type::LinkType;
 pub use lists::{
     AbsoluteCircularLinkedList, AbsoluteLinkedList, LinkedList, RelativeCircularLinkedList,
-    RelativeLinkedList,
+    RelativeLinkedList, SkipList,
+};
+#[cfg(feature = "derive")]
+pub use platform_trees_derive::{LinkedList, SizeBalancedTree};
+#[cfg(feature = "std")]
+pub use snapshot::{
+    SnapshotError, SnapshotHeader, SnapshotReader, SnapshotWriter, SNAPSHOT_MAGIC, SNAPSHOT_VERSION,
 };
+#[cfg(all(unix, feature = "mmap"))]
+pub use storages::{FileMappedStorage, MappedNode};
+#[cfg(feature = "alloc")]
+pub use storages::{PackedField, PackedOverflow, PackedTree, Savepoint, Transaction};
+#[cfg(feature = "alloc")]
+pub use trees::{FrozenTree, FrozenTreeIter};
+
+pub use trees::{
+    AvlTree, BPlusTree, CheckedTree, CheckedTreeIter, CritBitChild, CritBitTrie,
+    IterativeSizeBalancedTree, LeftRightTree, PersistentSizeBalancedTree,
+    RecursiveSizeBalancedTree, RedBlackTree, ScapegoatRecord, ScapegoatTree, Slot,
+    SlotSizeBalancedTree, SplayTree, Treap, TreeError,
+};
+
+#[doc(hidden)]
+pub mod __private {
+    use crate::LinkType;
 
-pub use trees::{IterativeSizeBalancedTree, RecursiveSizeBalancedTree};
+    /// Converts a link to a storage index in code generated by the derive macros.
+    #[inline]
+    pub fn index<T: LinkType>(link: T) -> usize {
+        link.as_usize()
+    }
+}
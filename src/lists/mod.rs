@@ -3,6 +3,7 @@ mod absolute_linked_list;
 mod linked_list;
 mod relative_circular_linked_list;
 mod relative_doubly_linked_list;
+mod skip_list;
 
 // TODO: use human names
 pub use absolute_circular_linked_list::AbsoluteCircularLinkedList;
@@ -10,3 +11,4 @@ pub use absolute_linked_list::AbsoluteLinkedList;
 pub use linked_list::LinkedList;
 pub use relative_circular_linked_list::RelativeCircularLinkedList;
 pub use relative_doubly_linked_list::RelativeLinkedList;
+pub use skip_list::SkipList;
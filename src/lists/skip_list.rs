@@ -0,0 +1,140 @@
+use crate::{hash, Flow, LinkType, RelativeCircularLinkedList};
+use core::cmp::Ordering;
+
+/// Largest supported number of levels.
+const MAX_LEVELS: usize = 64;
+
+/// Ordered index made of stacked [`RelativeCircularLinkedList`]s.
+///
+/// Level `0` holds every node in key order and each higher level holds a
+/// subset of the level below it. A node's level count is derived from a hash
+/// of its id (each extra level with probability `1/2`), so it needs no
+/// field and `delete` finds every entry of the node without searching.
+///
+/// A node appears in a level's list through an entry id given by
+/// `get_entry`, which must be distinct per `(node, level)` and map back with
+/// `get_entry_node`. Keys are never read directly: operations take
+/// comparison closures, so the same storage can be searched by any key.
+pub trait SkipList<T: LinkType>: RelativeCircularLinkedList<T> {
+    /// Number of levels, from `1` to `64`.
+    fn get_max_level(&self) -> usize;
+
+    fn get_level_head(&self, level: usize) -> T;
+
+    fn get_entry(&self, node: T, level: usize) -> T;
+
+    fn get_entry_node(&self, entry: T) -> T;
+
+    /// Number of levels `node` takes part in, from `1` to `get_max_level()`.
+    fn get_node_level(&self, node: T) -> usize {
+        let height = hash::splitmix64(node.as_u64()).trailing_zeros() as usize + 1;
+        height.min(self.get_max_level()).min(MAX_LEVELS)
+    }
+
+    fn get_count(&self) -> T {
+        self.get_size(self.get_level_head(0))
+    }
+
+    /// Returns the entry after `entry` in `level`, where `0` stands for the
+    /// position before the first entry and for the end of the level.
+    fn get_level_next(&self, level: usize, entry: T) -> T {
+        let first = self.get_first(self.get_level_head(level));
+        if entry == T::funty(0) {
+            return first;
+        }
+        let next = self.get_next(entry);
+        if next == first {
+            T::funty(0)
+        } else {
+            next
+        }
+    }
+
+    /// Fills `predecessors[level]` with the last entry of each level whose
+    /// node `target` reports as `Less`, or `0` when there is none.
+    fn find_predecessors<F: Fn(&Self, T) -> Ordering>(&self, target: F, predecessors: &mut [T]) {
+        let mut current = T::funty(0);
+        for level in (0..self.get_max_level()).rev() {
+            if current != T::funty(0) {
+                current = self.get_entry(self.get_entry_node(current), level);
+            }
+            loop {
+                let next = self.get_level_next(level, current);
+                if next == T::funty(0) || target(self, self.get_entry_node(next)) != Ordering::Less
+                {
+                    break;
+                }
+                current = next;
+            }
+            if level < predecessors.len() {
+                predecessors[level] = current;
+            }
+        }
+    }
+
+    /// Returns the first node `target` reports as `Equal`, or `0`.
+    ///
+    /// `target` tells how a node compares to the key being looked for.
+    fn search<F: Fn(&Self, T) -> Ordering>(&self, target: F) -> T {
+        let mut predecessor = [T::funty(0)];
+        self.find_predecessors(&target, &mut predecessor);
+        let next = self.get_level_next(0, predecessor[0]);
+        if next != T::funty(0) && target(self, self.get_entry_node(next)) == Ordering::Equal {
+            self.get_entry_node(next)
+        } else {
+            T::funty(0)
+        }
+    }
+
+    /// Inserts `node` before the first node that `compare(node, other)`
+    /// does not place it after.
+    fn insert<C: Fn(&Self, T, T) -> Ordering>(&mut self, node: T, compare: C) {
+        let height = self.get_node_level(node);
+        let mut predecessors = [T::funty(0); MAX_LEVELS];
+        self.find_predecessors(
+            |storage, other| compare(storage, node, other).reverse(),
+            &mut predecessors[..height],
+        );
+        for (level, &predecessor) in predecessors[..height].iter().enumerate() {
+            let head = self.get_level_head(level);
+            let entry = self.get_entry(node, level);
+            if predecessor == T::funty(0) {
+                self.attach_as_first(head, entry);
+            } else {
+                self.attach_after(head, predecessor, entry);
+            }
+        }
+    }
+
+    fn delete(&mut self, node: T) {
+        for level in 0..self.get_node_level(node) {
+            let head = self.get_level_head(level);
+            let entry = self.get_entry(node, level);
+            RelativeCircularLinkedList::detach(self, head, entry);
+        }
+    }
+
+    /// Calls `handler` with every node that `range` reports as `Equal`, in
+    /// order, where `range` reports nodes before the range as `Less` and
+    /// nodes after it as `Greater`.
+    fn each_in_range<F: Fn(&Self, T) -> Ordering, H: FnMut(T) -> Flow>(
+        &self,
+        range: F,
+        mut handler: H,
+    ) -> Flow {
+        let mut predecessor = [T::funty(0)];
+        self.find_predecessors(&range, &mut predecessor);
+        let mut entry = self.get_level_next(0, predecessor[0]);
+        while entry != T::funty(0) {
+            let node = self.get_entry_node(entry);
+            if range(self, node) == Ordering::Greater {
+                break;
+            }
+            if handler(node).is_break() {
+                return Flow::Break;
+            }
+            entry = self.get_level_next(0, entry);
+        }
+        Flow::Continue
+    }
+}
@@ -0,0 +1,527 @@
+use crate::{LinkType, RecursiveSizeBalancedTree};
+use core::convert::TryFrom;
+use core::fmt;
+use core::marker::PhantomData;
+use std::io::{self, Read, Write};
+use std::vec;
+use std::vec::Vec;
+
+/// First bytes of every snapshot.
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"LPTREES\0";
+
+/// Format version written by [`SnapshotWriter`].
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Node records per checksummed block.
+const BLOCK_NODES: u32 = 4096;
+
+/// Bytes in an encoded header, without its checksum.
+const HEADER_BYTES: usize = 28;
+
+/// Description of a snapshot, stored in its first block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    pub version: u16,
+    /// Bytes per link, the width of the link type that wrote the snapshot.
+    pub link_bytes: u8,
+    /// Links per node record.
+    pub fields: u8,
+    pub node_count: u64,
+    pub root_count: u32,
+    /// Node records per block; the last block may hold fewer.
+    pub block_nodes: u32,
+}
+
+impl SnapshotHeader {
+    fn encode(&self) -> [u8; HEADER_BYTES] {
+        let mut bytes = [0; HEADER_BYTES];
+        bytes[0..8].copy_from_slice(&SNAPSHOT_MAGIC);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10] = self.link_bytes;
+        bytes[11] = self.fields;
+        bytes[12..20].copy_from_slice(&self.node_count.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.root_count.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.block_nodes.to_le_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8; HEADER_BYTES]) -> Self {
+        let u32_at = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        let mut node_count = [0; 8];
+        node_count.copy_from_slice(&bytes[12..20]);
+        Self {
+            version: u16::from_le_bytes([bytes[8], bytes[9]]),
+            link_bytes: bytes[10],
+            fields: bytes[11],
+            node_count: u64::from_le_bytes(node_count),
+            root_count: u32_at(20),
+            block_nodes: u32_at(24),
+        }
+    }
+}
+
+/// Error returned while reading or writing a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The stream does not start with [`SNAPSHOT_MAGIC`].
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The header announces empty node records or blocks, or blocks too
+    /// large to address.
+    BadHeader,
+    /// The snapshot was written with a different link type width.
+    LinkWidth {
+        expected: u8,
+        found: u8,
+    },
+    /// A record slice does not hold as many links as each node of the
+    /// snapshot: `expected` is the count in the header and `found` is the
+    /// length of the slice, saturated at `255`.
+    FieldCount {
+        expected: u8,
+        found: u8,
+    },
+    /// The number of nodes or roots differs from the one announced.
+    Count {
+        expected: u64,
+        found: u64,
+    },
+    /// A stored link does not fit the link type.
+    LinkOverflow,
+    /// The snapshot holds more nodes than the storage it is restored into.
+    Capacity {
+        capacity: u64,
+        found: u64,
+    },
+    /// A stored link points past the last node of the snapshot.
+    DanglingLink {
+        link: u128,
+    },
+    /// The checksum of a block does not match; block `0` is the header and
+    /// the root table is the last block.
+    Checksum {
+        block: u64,
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "snapshot i/o failed: {}", error),
+            SnapshotError::BadMagic => write!(f, "stream is not a snapshot"),
+            SnapshotError::BadHeader => {
+                write!(
+                    f,
+                    "snapshot header has empty or oversized records or blocks"
+                )
+            }
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "snapshot version {} is not supported", version)
+            }
+            SnapshotError::LinkWidth { expected, found } => write!(
+                f,
+                "snapshot links are {} bytes wide, expected {}",
+                found, expected
+            ),
+            SnapshotError::FieldCount { expected, found } => write!(
+                f,
+                "snapshot nodes have {} links, {} were given",
+                expected, found
+            ),
+            SnapshotError::Count { expected, found } => {
+                write!(f, "expected {} records, found {}", expected, found)
+            }
+            SnapshotError::LinkOverflow => write!(f, "stored link does not fit the link type"),
+            SnapshotError::Capacity { capacity, found } => write!(
+                f,
+                "snapshot holds {} nodes, storage has room for {}",
+                found, capacity
+            ),
+            SnapshotError::DanglingLink { link } => {
+                write!(f, "stored link {} points past the last node", link)
+            }
+            SnapshotError::Checksum { block } => {
+                write!(f, "checksum mismatch in block {}", block)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnapshotError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(error: io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+/// CRC-32 (IEEE) lookup table.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE) of `bytes`.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Length of a record slice as reported by [`SnapshotError::FieldCount`].
+fn field_count(fields: usize) -> u8 {
+    u8::try_from(fields).unwrap_or(u8::MAX)
+}
+
+fn link_bytes<T: LinkType>() -> u8 {
+    (T::BITS / 8) as u8
+}
+
+fn encode_link<T: LinkType>(link: T, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&link.as_u128().to_le_bytes()[..link_bytes::<T>() as usize]);
+}
+
+fn decode_link<T: LinkType>(bytes: &[u8]) -> Result<T, SnapshotError> {
+    let mut wide = [0; 16];
+    wide[..bytes.len()].copy_from_slice(bytes);
+    T::try_from(u128::from_le_bytes(wide)).map_err(|_| SnapshotError::LinkOverflow)
+}
+
+/// Streaming writer of the binary snapshot format.
+///
+/// A snapshot is a header block, node blocks of up to `block_nodes` records
+/// and a root table block. Every block is followed by its CRC-32, and every
+/// number is little-endian, links taking the width of `T`, so snapshots
+/// read back byte-for-byte on any platform with the same link type.
+pub struct SnapshotWriter<W, T> {
+    writer: W,
+    header: SnapshotHeader,
+    written: u64,
+    block: Vec<u8>,
+    link: PhantomData<T>,
+}
+
+impl<W: Write, T: LinkType> SnapshotWriter<W, T> {
+    /// Writes the header of a snapshot of `node_count` records of `fields`
+    /// links each, followed by `root_count` roots.
+    pub fn new(mut writer: W, fields: u8, node_count: u64, root_count: u32) -> io::Result<Self> {
+        let header = SnapshotHeader {
+            version: SNAPSHOT_VERSION,
+            link_bytes: link_bytes::<T>(),
+            fields,
+            node_count,
+            root_count,
+            block_nodes: BLOCK_NODES,
+        };
+        let bytes = header.encode();
+        writer.write_all(&bytes)?;
+        writer.write_all(&crc32(&bytes).to_le_bytes())?;
+        Ok(Self {
+            writer,
+            header,
+            written: 0,
+            block: Vec::new(),
+            link: PhantomData,
+        })
+    }
+
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    /// Appends the record of the next node.
+    pub fn write_node(&mut self, fields: &[T]) -> Result<(), SnapshotError> {
+        if fields.len() != self.header.fields as usize {
+            return Err(SnapshotError::FieldCount {
+                expected: self.header.fields,
+                found: field_count(fields.len()),
+            });
+        }
+        if self.written == self.header.node_count {
+            return Err(SnapshotError::Count {
+                expected: self.header.node_count,
+                found: self.written + 1,
+            });
+        }
+        for &field in fields {
+            encode_link(field, &mut self.block);
+        }
+        self.written += 1;
+        if self.written % self.header.block_nodes as u64 == 0 {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.block)?;
+        self.writer.write_all(&crc32(&self.block).to_le_bytes())?;
+        self.block.clear();
+        Ok(())
+    }
+
+    /// Writes the root table after the last node and returns the writer.
+    pub fn finish(mut self, roots: &[T]) -> Result<W, SnapshotError> {
+        if self.written != self.header.node_count {
+            return Err(SnapshotError::Count {
+                expected: self.header.node_count,
+                found: self.written,
+            });
+        }
+        if roots.len() as u64 != self.header.root_count as u64 {
+            return Err(SnapshotError::Count {
+                expected: self.header.root_count as u64,
+                found: roots.len() as u64,
+            });
+        }
+        if !self.block.is_empty() {
+            self.flush_block()?;
+        }
+        for &root in roots {
+            encode_link(root, &mut self.block);
+        }
+        self.flush_block()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write, T: LinkType> SnapshotWriter<W, T> {
+    /// Writes the `left`, `right` and `size` of nodes `1..=node_count` of a
+    /// tree storage and its `roots`.
+    ///
+    /// # Safety
+    ///
+    /// Every node in `1..=node_count` must be a valid node of `storage`.
+    pub unsafe fn write_tree_storage<S: RecursiveSizeBalancedTree<T>>(
+        writer: W,
+        storage: &S,
+        node_count: T,
+        roots: &[T],
+    ) -> Result<W, SnapshotError> {
+        let mut snapshot = Self::new(writer, 3, node_count.as_u64(), roots.len() as u32)?;
+        let mut node = T::funty(1);
+        while node <= node_count {
+            snapshot.write_node(&[
+                storage.get_left(node),
+                storage.get_right(node),
+                storage.get_size(node),
+            ])?;
+            node += T::funty(1);
+        }
+        snapshot.finish(roots)
+    }
+}
+
+/// Streaming reader of snapshots written by [`SnapshotWriter`].
+///
+/// Each block is checked against its checksum before any of its records
+/// is returned.
+pub struct SnapshotReader<R, T> {
+    reader: R,
+    header: SnapshotHeader,
+    read: u64,
+    blocks: u64,
+    block: Vec<u8>,
+    offset: usize,
+    link: PhantomData<T>,
+}
+
+impl<R: Read, T: LinkType> SnapshotReader<R, T> {
+    /// Reads and checks the header of a snapshot.
+    pub fn new(mut reader: R) -> Result<Self, SnapshotError> {
+        let mut bytes = [0; HEADER_BYTES];
+        reader.read_exact(&mut bytes)?;
+        if bytes[0..8] != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let mut checksum = [0; 4];
+        reader.read_exact(&mut checksum)?;
+        if u32::from_le_bytes(checksum) != crc32(&bytes) {
+            return Err(SnapshotError::Checksum { block: 0 });
+        }
+        let header = SnapshotHeader::decode(&bytes);
+        if header.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(header.version));
+        }
+        if header.fields == 0 || header.block_nodes == 0 {
+            return Err(SnapshotError::BadHeader);
+        }
+        let record_bytes = header.fields as u64 * header.link_bytes as u64;
+        let block_bytes = (header.block_nodes as u64).checked_mul(record_bytes);
+        let root_bytes = (header.root_count as u64).checked_mul(header.link_bytes as u64);
+        if [block_bytes, root_bytes].iter().any(|bytes| {
+            bytes
+                .and_then(|bytes| usize::try_from(bytes).ok())
+                .is_none()
+        }) {
+            return Err(SnapshotError::BadHeader);
+        }
+        if header.link_bytes != link_bytes::<T>() {
+            return Err(SnapshotError::LinkWidth {
+                expected: link_bytes::<T>(),
+                found: header.link_bytes,
+            });
+        }
+        Ok(Self {
+            reader,
+            header,
+            read: 0,
+            blocks: 0,
+            block: Vec::new(),
+            offset: 0,
+            link: PhantomData,
+        })
+    }
+
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    /// Reads the next block of `records` records and checks its checksum.
+    fn load_block(&mut self, records: u64) -> Result<(), SnapshotError> {
+        // At most `block_nodes` records, whose length `new` checked.
+        self.read_block(records as usize * self.record_bytes())?;
+        self.blocks += 1;
+        self.offset = 0;
+        Ok(())
+    }
+
+    /// Reads `length` bytes into the block buffer and checks the checksum
+    /// that follows them, returning [`SnapshotError::Checksum`] for the
+    /// block after the last one read.
+    ///
+    /// The buffer grows with the bytes that actually arrive, so a header
+    /// announcing more than the stream holds fails at its end instead of
+    /// allocating the announced length up front.
+    fn read_block(&mut self, length: usize) -> Result<(), SnapshotError> {
+        self.block.clear();
+        (&mut self.reader)
+            .take(length as u64)
+            .read_to_end(&mut self.block)?;
+        if self.block.len() != length {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let mut checksum = [0; 4];
+        self.reader.read_exact(&mut checksum)?;
+        if u32::from_le_bytes(checksum) != crc32(&self.block) {
+            return Err(SnapshotError::Checksum {
+                block: self.blocks + 1,
+            });
+        }
+        Ok(())
+    }
+
+    fn record_bytes(&self) -> usize {
+        self.header.fields as usize * self.header.link_bytes as usize
+    }
+
+    /// Reads the next node record into `fields`, or returns `false` after
+    /// the last one.
+    pub fn read_node(&mut self, fields: &mut [T]) -> Result<bool, SnapshotError> {
+        if fields.len() != self.header.fields as usize {
+            return Err(SnapshotError::FieldCount {
+                expected: self.header.fields,
+                found: field_count(fields.len()),
+            });
+        }
+        if self.read == self.header.node_count {
+            return Ok(false);
+        }
+        if self.read % self.header.block_nodes as u64 == 0 {
+            let left = self.header.node_count - self.read;
+            self.load_block(left.min(self.header.block_nodes as u64))?;
+        }
+        let width = self.header.link_bytes as usize;
+        for field in fields.iter_mut() {
+            *field = decode_link(&self.block[self.offset..self.offset + width])?;
+            self.offset += width;
+        }
+        self.read += 1;
+        Ok(true)
+    }
+
+    /// Skips the remaining nodes and reads the root table.
+    pub fn finish(mut self) -> Result<Vec<T>, SnapshotError> {
+        let mut fields = vec![T::funty(0); self.header.fields as usize];
+        while self.read_node(&mut fields)? {}
+        let width = self.header.link_bytes as usize;
+        self.read_block(self.header.root_count as usize * width)?;
+        self.block.chunks(width).map(decode_link).collect()
+    }
+}
+
+impl<R: Read, T: LinkType> SnapshotReader<R, T> {
+    /// Restores a snapshot written by [`SnapshotWriter::write_tree_storage`]
+    /// into nodes `1..=node_count` of `storage` and returns its roots.
+    ///
+    /// The stream need not be trusted: a snapshot of more than `capacity`
+    /// nodes is rejected before anything is written, and a record or root
+    /// linking past its last node before that record is written.
+    ///
+    /// # Safety
+    ///
+    /// Every node in `1..=capacity` must be a valid node of `storage`.
+    pub unsafe fn read_tree_storage<S: RecursiveSizeBalancedTree<T>>(
+        reader: R,
+        storage: &mut S,
+        capacity: T,
+    ) -> Result<Vec<T>, SnapshotError> {
+        let mut snapshot = Self::new(reader)?;
+        let node_count = snapshot.header.node_count;
+        let last = match T::try_from(node_count) {
+            Ok(last) if last <= capacity => last,
+            _ => {
+                return Err(SnapshotError::Capacity {
+                    capacity: capacity.as_u64(),
+                    found: node_count,
+                })
+            }
+        };
+        let check = |link: T| {
+            if link > last {
+                Err(SnapshotError::DanglingLink {
+                    link: link.as_u128(),
+                })
+            } else {
+                Ok(link)
+            }
+        };
+        let mut fields = [T::funty(0); 3];
+        let mut node = T::funty(0);
+        while snapshot.read_node(&mut fields)? {
+            let left = check(fields[0])?;
+            let right = check(fields[1])?;
+            node += T::funty(1);
+            storage.set_left(node, left);
+            storage.set_right(node, right);
+            storage.set_size(node, fields[2]);
+        }
+        snapshot.finish()?.into_iter().map(check).collect()
+    }
+}
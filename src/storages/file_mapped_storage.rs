@@ -0,0 +1,382 @@
+use crate::{
+    AbsoluteCircularLinkedList, AbsoluteLinkedList, IterativeSizeBalancedTree, LinkType,
+    LinkedList, NodeAllocator, RecursiveSizeBalancedTree,
+};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"LPTREES\0");
+const ROOTS: usize = 8;
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Node record stored in a [`FileMappedStorage`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MappedNode<T> {
+    pub left: T,
+    pub right: T,
+    pub size: T,
+    pub previous: T,
+    pub next: T,
+}
+
+#[repr(C)]
+struct Header<T> {
+    magic: u64,
+    link_size: u64,
+    capacity: T,
+    allocated: T,
+    first_unused: T,
+    last_unused: T,
+    unused: T,
+    roots: [T; ROOTS],
+}
+
+/// Node storage over a memory-mapped file.
+///
+/// The file starts with a header holding the allocation state, the free list
+/// and a table of tree roots, followed by one [`MappedNode`] per node id
+/// (including the null node `0`). Nodes are ordered by id in the trees, the
+/// `previous`/`next` fields hold the free list of [`NodeAllocator`], and the
+/// file grows on demand when more nodes are allocated.
+///
+/// The list traits are implemented for the allocator: their first, last and
+/// size are the free list's, so they are not available for other lists.
+pub struct FileMappedStorage<T: LinkType> {
+    file: File,
+    map: *mut u8,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: LinkType> Send for FileMappedStorage<T> {}
+
+impl<T: LinkType> FileMappedStorage<T> {
+    /// Number of tree roots persisted in the header.
+    pub const ROOTS: usize = ROOTS;
+
+    /// Opens `path`, creating and initializing the file if it is empty.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let file_len = file.metadata()?.len() as usize;
+        if file_len == 0 {
+            let capacity = DEFAULT_CAPACITY.min(<T as funty::Integral>::MAX.as_usize());
+            let len = Self::file_len(capacity).expect("default capacity fits in memory");
+            file.set_len(len as u64)?;
+            let mut storage = Self::map(file, len)?;
+            let header = storage.header_mut();
+            header.magic = MAGIC;
+            header.link_size = mem::size_of::<T>() as u64;
+            header.capacity = T::try_from(capacity as u64).unwrap_or(<T as funty::Integral>::MAX);
+            Ok(storage)
+        } else if file_len < mem::size_of::<Header<T>>() {
+            Err(invalid_data("file is too small to hold a header"))
+        } else {
+            let storage = Self::map(file, file_len)?;
+            let header = storage.header();
+            if header.magic != MAGIC {
+                return Err(invalid_data("file is not a mapped tree storage"));
+            }
+            if header.link_size != mem::size_of::<T>() as u64 {
+                return Err(invalid_data("file was created with a different link type"));
+            }
+            match Self::file_len(header.capacity.as_usize()) {
+                Some(len) if len <= file_len => {}
+                _ => return Err(invalid_data("file is shorter than its declared capacity")),
+            }
+            if header.allocated > header.capacity {
+                return Err(invalid_data("file allocates more nodes than its capacity"));
+            }
+            Ok(storage)
+        }
+    }
+
+    pub fn get_capacity(&self) -> T {
+        self.header().capacity
+    }
+
+    /// Grows the file so it can hold at least `capacity` nodes.
+    pub fn reserve(&mut self, capacity: T) -> io::Result<()> {
+        if capacity <= self.get_capacity() {
+            return Ok(());
+        }
+        let len = Self::file_len(capacity.as_usize()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "capacity does not fit in memory",
+            )
+        })?;
+        self.file.set_len(len as u64)?;
+        let map = Self::map_file(&self.file, len)?;
+        unsafe {
+            libc::munmap(self.map as *mut libc::c_void, self.len);
+        }
+        self.map = map;
+        self.len = len;
+        self.header_mut().capacity = capacity;
+        Ok(())
+    }
+
+    /// Allocates a node like [`NodeAllocator::allocate`], but returns an
+    /// error instead of panicking when the file cannot grow or no ids are
+    /// left.
+    pub fn try_allocate(&mut self) -> io::Result<T> {
+        if self.get_first() == T::funty(0) {
+            let allocated = self
+                .get_allocated()
+                .checked_add(T::funty(1))
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::OutOfMemory, "no node ids are left")
+                })?;
+            self.grow_to(allocated)?;
+        }
+        Ok(self.allocate())
+    }
+
+    /// Reserves room for `allocated` nodes, doubling the capacity when it
+    /// has to grow.
+    fn grow_to(&mut self, allocated: T) -> io::Result<()> {
+        let capacity = self.get_capacity();
+        if allocated <= capacity {
+            return Ok(());
+        }
+        let capacity = match capacity.checked_add(capacity) {
+            Some(doubled) if doubled > allocated => doubled,
+            _ => allocated,
+        };
+        self.reserve(capacity)
+    }
+
+    pub fn get_root(&self, index: usize) -> T {
+        self.header().roots[index]
+    }
+
+    pub fn set_root(&mut self, index: usize, root: T) {
+        self.header_mut().roots[index] = root;
+    }
+
+    pub fn get_node(&self, node: T) -> &MappedNode<T> {
+        &self.nodes()[node.as_usize()]
+    }
+
+    pub fn get_mut_node(&mut self, node: T) -> &mut MappedNode<T> {
+        &mut self.nodes_mut()[node.as_usize()]
+    }
+
+    /// Synchronously writes the mapped pages back to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        let result = unsafe { libc::msync(self.map as *mut libc::c_void, self.len, libc::MS_SYNC) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Length of a file holding `capacity` nodes, or `None` when it does
+    /// not fit in `usize`.
+    fn file_len(capacity: usize) -> Option<usize> {
+        capacity
+            .checked_add(1)?
+            .checked_mul(mem::size_of::<MappedNode<T>>())?
+            .checked_add(mem::size_of::<Header<T>>())
+    }
+
+    fn map(file: File, len: usize) -> io::Result<Self> {
+        let map = Self::map_file(&file, len)?;
+        Ok(Self {
+            file,
+            map,
+            len,
+            _marker: PhantomData,
+        })
+    }
+
+    fn map_file(file: &File, len: usize) -> io::Result<*mut u8> {
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(map as *mut u8)
+        }
+    }
+
+    fn header(&self) -> &Header<T> {
+        unsafe { &*(self.map as *const Header<T>) }
+    }
+
+    fn header_mut(&mut self) -> &mut Header<T> {
+        unsafe { &mut *(self.map as *mut Header<T>) }
+    }
+
+    fn nodes(&self) -> &[MappedNode<T>] {
+        let count = self.header().capacity.as_usize() + 1;
+        unsafe {
+            let first = self.map.add(mem::size_of::<Header<T>>()) as *const MappedNode<T>;
+            slice::from_raw_parts(first, count)
+        }
+    }
+
+    fn nodes_mut(&mut self) -> &mut [MappedNode<T>] {
+        let count = self.header().capacity.as_usize() + 1;
+        unsafe {
+            let first = self.map.add(mem::size_of::<Header<T>>()) as *mut MappedNode<T>;
+            slice::from_raw_parts_mut(first, count)
+        }
+    }
+}
+
+impl<T: LinkType> Drop for FileMappedStorage<T> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.map as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<T: LinkType> RecursiveSizeBalancedTree<T> for FileMappedStorage<T> {
+    unsafe fn get_mut_left_reference(&mut self, node: T) -> *mut T {
+        &mut self.get_mut_node(node).left
+    }
+
+    unsafe fn get_mut_right_reference(&mut self, node: T) -> *mut T {
+        &mut self.get_mut_node(node).right
+    }
+
+    unsafe fn get_left_reference(&self, node: T) -> *const T {
+        &self.get_node(node).left
+    }
+
+    unsafe fn get_right_reference(&self, node: T) -> *const T {
+        &self.get_node(node).right
+    }
+
+    unsafe fn get_left(&self, node: T) -> T {
+        self.get_node(node).left
+    }
+
+    unsafe fn get_right(&self, node: T) -> T {
+        self.get_node(node).right
+    }
+
+    unsafe fn get_size(&self, node: T) -> T {
+        self.get_node(node).size
+    }
+
+    unsafe fn set_left(&mut self, node: T, left: T) {
+        self.get_mut_node(node).left = left;
+    }
+
+    unsafe fn set_right(&mut self, node: T, right: T) {
+        self.get_mut_node(node).right = right;
+    }
+
+    unsafe fn set_size(&mut self, node: T, size: T) {
+        self.get_mut_node(node).size = size;
+    }
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool {
+        first < second
+    }
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool {
+        first > second
+    }
+}
+
+impl<T: LinkType> IterativeSizeBalancedTree<T> for FileMappedStorage<T> {}
+
+impl<T: LinkType> LinkedList<T> for FileMappedStorage<T> {
+    fn get_previous(&self, element: T) -> T {
+        self.get_node(element).previous
+    }
+
+    fn get_next(&self, element: T) -> T {
+        self.get_node(element).next
+    }
+
+    fn set_previous(&mut self, element: T, previous: T) {
+        self.get_mut_node(element).previous = previous;
+    }
+
+    fn set_next(&mut self, element: T, next: T) {
+        self.get_mut_node(element).next = next;
+    }
+}
+
+impl<T: LinkType> AbsoluteLinkedList<T> for FileMappedStorage<T> {
+    fn get_first(&self) -> T {
+        self.header().first_unused
+    }
+
+    fn get_last(&self) -> T {
+        self.header().last_unused
+    }
+
+    fn get_size(&self) -> T {
+        self.header().unused
+    }
+
+    fn set_first(&mut self, element: T) {
+        self.header_mut().first_unused = element;
+    }
+
+    fn set_last(&mut self, element: T) {
+        self.header_mut().last_unused = element;
+    }
+
+    fn set_size(&mut self, size: T) {
+        self.header_mut().unused = size;
+    }
+}
+
+impl<T: LinkType> AbsoluteCircularLinkedList<T> for FileMappedStorage<T> {}
+
+impl<T: LinkType> NodeAllocator<T> for FileMappedStorage<T> {
+    fn get_allocated(&self) -> T {
+        self.header().allocated
+    }
+
+    /// # Panics
+    ///
+    /// Panics when the file has to grow and cannot, for example when the disk
+    /// is full; use [`FileMappedStorage::try_allocate`] to handle that.
+    fn set_allocated(&mut self, allocated: T) {
+        if let Err(error) = self.grow_to(allocated) {
+            panic!("failed to grow mapped storage: {}", error);
+        }
+        let previous = self.header().allocated;
+        if allocated > previous {
+            let start = previous.as_usize() + 1;
+            let end = allocated.as_usize() + 1;
+            for node in &mut self.nodes_mut()[start..end] {
+                *node = MappedNode::default();
+            }
+        }
+        self.header_mut().allocated = allocated;
+    }
+}
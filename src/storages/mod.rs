@@ -0,0 +1,13 @@
+#[cfg(all(unix, feature = "mmap"))]
+mod file_mapped_storage;
+#[cfg(feature = "alloc")]
+mod packed_tree;
+#[cfg(feature = "alloc")]
+mod transaction;
+
+#[cfg(all(unix, feature = "mmap"))]
+pub use file_mapped_storage::{FileMappedStorage, MappedNode};
+#[cfg(feature = "alloc")]
+pub use packed_tree::{PackedField, PackedOverflow, PackedTree};
+#[cfg(feature = "alloc")]
+pub use transaction::{Savepoint, Transaction};
@@ -0,0 +1,300 @@
+use crate::{LinkType, RecursiveSizeBalancedTree, SlotSizeBalancedTree};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Field of a packed node that could not hold a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackedField {
+    Left,
+    Right,
+    Size,
+}
+
+/// Error returned when a value does not fit the width of a packed field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedOverflow<T> {
+    pub field: PackedField,
+    pub value: T,
+    pub bits: u32,
+}
+
+impl<T: fmt::Display> fmt::Display for PackedOverflow<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} value {} does not fit in {} bits",
+            self.field, self.value, self.bits
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug + fmt::Display> std::error::Error for PackedOverflow<T> {}
+
+/// Size-balanced tree storage packing a node into two words.
+///
+/// The low `link_bits` of the first word hold the left child and those of the
+/// second word hold the right child; the spare high bits of both words hold
+/// the size (high half in the first word, low half in the second). With `u64`
+/// links and 40-bit ids a node takes 16 bytes instead of 24 and sizes get 48
+/// bits. Nodes are ordered by id.
+///
+/// Packed links cannot be borrowed, so the storage does not implement
+/// [`RecursiveSizeBalancedTree`], whose methods may ask for pointers to
+/// links. The tree is built with [`PackedTree::attach`] and
+/// [`PackedTree::detach`], which run the pointer-free
+/// [`SlotSizeBalancedTree`] algorithms over the getters and setters only.
+/// The setters panic when a value overflows its field (use the `try_set_*`
+/// methods to handle that instead).
+pub struct PackedTree<T> {
+    words: Vec<[T; 2]>,
+    link_bits: u32,
+}
+
+impl<T: LinkType> PackedTree<T> {
+    /// Creates storage for nodes `1..=capacity` with `link_bits`-wide child
+    /// links, capped at the width of `T`.
+    ///
+    /// Fails with the field that cannot hold `capacity`: the links when they
+    /// are too narrow (including `0` bits) and the size when the links leave
+    /// too few bits for it.
+    pub fn new(capacity: T, link_bits: u32) -> Result<Self, PackedOverflow<T>> {
+        let storage = Self {
+            words: Vec::new(),
+            link_bits: link_bits.min(T::BITS),
+        };
+        storage.check(PackedField::Left, capacity)?;
+        storage.check(PackedField::Size, capacity)?;
+        Ok(Self {
+            words: vec![[T::funty(0); 2]; capacity.as_usize() + 1],
+            ..storage
+        })
+    }
+
+    pub fn link_bits(&self) -> u32 {
+        self.link_bits
+    }
+
+    pub fn size_bits(&self) -> u32 {
+        (2 * self.spare_bits()).min(T::BITS)
+    }
+
+    pub fn get_capacity(&self) -> T {
+        T::try_from((self.words.len() - 1) as u64).unwrap_or(<T as funty::Integral>::MAX)
+    }
+
+    pub fn try_set_left(&mut self, node: T, left: T) -> Result<(), PackedOverflow<T>> {
+        self.check(PackedField::Left, left)?;
+        let link_mask = self.link_mask();
+        let word = &mut self.words[node.as_usize()][0];
+        *word = (*word & !link_mask) | left;
+        Ok(())
+    }
+
+    pub fn try_set_right(&mut self, node: T, right: T) -> Result<(), PackedOverflow<T>> {
+        self.check(PackedField::Right, right)?;
+        let link_mask = self.link_mask();
+        let word = &mut self.words[node.as_usize()][1];
+        *word = (*word & !link_mask) | right;
+        Ok(())
+    }
+
+    pub fn try_set_size(&mut self, node: T, size: T) -> Result<(), PackedOverflow<T>> {
+        self.check(PackedField::Size, size)?;
+        let link_bits = self.link_bits;
+        let spare_bits = self.spare_bits();
+        let link_mask = self.link_mask();
+        let low = size & Self::mask(spare_bits);
+        let high = size >> spare_bits;
+        let words = &mut self.words[node.as_usize()];
+        words[0] = (words[0] & link_mask) | (high << link_bits);
+        words[1] = (words[1] & link_mask) | (low << link_bits);
+        Ok(())
+    }
+
+    fn spare_bits(&self) -> u32 {
+        T::BITS - self.link_bits
+    }
+
+    fn mask(bits: u32) -> T {
+        if bits >= T::BITS {
+            <T as funty::Integral>::MAX
+        } else {
+            (T::funty(1) << bits) - T::funty(1)
+        }
+    }
+
+    fn link_mask(&self) -> T {
+        Self::mask(self.link_bits)
+    }
+
+    fn check(&self, field: PackedField, value: T) -> Result<(), PackedOverflow<T>> {
+        let bits = match field {
+            PackedField::Left | PackedField::Right => self.link_bits,
+            PackedField::Size => self.size_bits(),
+        };
+        if value > Self::mask(bits) {
+            Err(PackedOverflow { field, value, bits })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: LinkType> PackedTree<T> {
+    pub fn get_left(&self, node: T) -> T {
+        self.words[node.as_usize()][0] & self.link_mask()
+    }
+
+    pub fn get_right(&self, node: T) -> T {
+        self.words[node.as_usize()][1] & self.link_mask()
+    }
+
+    pub fn get_size(&self, node: T) -> T {
+        let words = self.words[node.as_usize()];
+        let low = words[1] >> self.link_bits;
+        let high = words[0] >> self.link_bits;
+        (high << self.spare_bits()) | low
+    }
+
+    /// # Panics
+    ///
+    /// Panics when `left` does not fit in `link_bits`.
+    pub fn set_left(&mut self, node: T, left: T) {
+        expect_fits(self.try_set_left(node, left));
+    }
+
+    /// # Panics
+    ///
+    /// Panics when `right` does not fit in `link_bits`.
+    pub fn set_right(&mut self, node: T, right: T) {
+        expect_fits(self.try_set_right(node, right));
+    }
+
+    /// # Panics
+    ///
+    /// Panics when `size` does not fit in `size_bits`.
+    pub fn set_size(&mut self, node: T, size: T) {
+        expect_fits(self.try_set_size(node, size));
+    }
+
+    /// Returns whether `node` is in the tree at `root`.
+    pub fn contains(&self, node: T, root: T) -> bool {
+        let mut current = root;
+        while current != T::funty(0) {
+            if node < current {
+                current = self.get_left(current);
+            } else if node > current {
+                current = self.get_right(current);
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the smallest node of the tree at `root`, which must not be
+    /// `0`.
+    pub fn get_leftest(&self, root: T) -> T {
+        let mut current = root;
+        while self.get_left(current) != T::funty(0) {
+            current = self.get_left(current);
+        }
+        current
+    }
+
+    /// Returns the largest node of the tree at `root`, which must not be
+    /// `0`.
+    pub fn get_rightest(&self, root: T) -> T {
+        let mut current = root;
+        while self.get_right(current) != T::funty(0) {
+            current = self.get_right(current);
+        }
+        current
+    }
+
+    /// Attaches `node` to the tree at `root` through the getters and setters
+    /// only, so no link is ever borrowed.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be a valid node that is not in any tree, and `root` must
+    /// be `0` or the root of a well-formed tree of this storage.
+    pub unsafe fn attach(&mut self, root: &mut T, node: T) {
+        PackedSlots(self).slot_attach(root, node);
+    }
+
+    /// Detaches `node` from the tree at `root` through the getters and
+    /// setters only.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be in the tree at `root`.
+    pub unsafe fn detach(&mut self, root: &mut T, node: T) {
+        PackedSlots(self).slot_detach(root, node);
+    }
+}
+
+fn expect_fits<T: fmt::Display>(result: Result<(), PackedOverflow<T>>) {
+    if let Err(overflow) = result {
+        panic!("{}", overflow);
+    }
+}
+
+/// View of a [`PackedTree`] for the [`SlotSizeBalancedTree`] algorithms,
+/// which only use the getters and setters. It stays private so the
+/// `get_*_reference` methods it cannot provide are never reachable.
+struct PackedSlots<'a, T>(&'a mut PackedTree<T>);
+
+impl<T: LinkType> RecursiveSizeBalancedTree<T> for PackedSlots<'_, T> {
+    unsafe fn get_mut_left_reference(&mut self, _node: T) -> *mut T {
+        unreachable!("slot algorithms never borrow links")
+    }
+
+    unsafe fn get_mut_right_reference(&mut self, _node: T) -> *mut T {
+        unreachable!("slot algorithms never borrow links")
+    }
+
+    unsafe fn get_left_reference(&self, _node: T) -> *const T {
+        unreachable!("slot algorithms never borrow links")
+    }
+
+    unsafe fn get_right_reference(&self, _node: T) -> *const T {
+        unreachable!("slot algorithms never borrow links")
+    }
+
+    unsafe fn get_left(&self, node: T) -> T {
+        self.0.get_left(node)
+    }
+
+    unsafe fn get_right(&self, node: T) -> T {
+        self.0.get_right(node)
+    }
+
+    unsafe fn get_size(&self, node: T) -> T {
+        self.0.get_size(node)
+    }
+
+    unsafe fn set_left(&mut self, node: T, left: T) {
+        self.0.set_left(node, left);
+    }
+
+    unsafe fn set_right(&mut self, node: T, right: T) {
+        self.0.set_right(node, right);
+    }
+
+    unsafe fn set_size(&mut self, node: T, size: T) {
+        self.0.set_size(node, size);
+    }
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool {
+        first < second
+    }
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool {
+        first > second
+    }
+}
+
+impl<T: LinkType> SlotSizeBalancedTree<T> for PackedSlots<'_, T> {}
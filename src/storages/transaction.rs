@@ -0,0 +1,371 @@
+use crate::{
+    AbsoluteCircularLinkedList, AbsoluteLinkedList, IterativeSizeBalancedTree, LinkType,
+    LinkedList, RecursiveSizeBalancedTree, RelativeCircularLinkedList, RelativeLinkedList,
+    SlotSizeBalancedTree, SplayTree, Treap,
+};
+use alloc::vec::Vec;
+
+/// Position in the undo log of a [`Transaction`] to roll back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Savepoint(usize);
+
+/// Old value of one field, with the setter that restores it, or of one
+/// root slot.
+enum UndoEntry<T, S> {
+    Field {
+        undo: unsafe fn(&mut S, T, T),
+        target: T,
+        value: T,
+    },
+    Root {
+        index: usize,
+        value: T,
+    },
+}
+
+/// Undo-logging wrapper over a tree or list storage.
+///
+/// Implements the tree and list traits of the borrowed storage, forwarding
+/// reads and recording the previous value of every field before a setter
+/// changes it. `commit` keeps the changes, while `rollback`, `rollback_to`
+/// a [`Savepoint`] and dropping the transaction restore the recorded values
+/// in reverse order. Savepoints nest: rolling back to one keeps the changes
+/// made before it, including those before older savepoints.
+///
+/// Tree roots live outside the storage, so a transaction started with
+/// [`Transaction::with_roots`] also logs the caller's root slots: updates
+/// go through [`Transaction::with_root`] or [`Transaction::set_root`] and
+/// every rollback restores the roots along with the nodes. Roots kept
+/// anywhere else must be saved and restored by the caller.
+///
+/// Writes through the pointers of `get_mut_left_reference` and
+/// `get_mut_right_reference` cannot be observed, so handing out a pointer
+/// records the current value of the link instead.
+pub struct Transaction<'a, T, S> {
+    storage: &'a mut S,
+    roots: &'a mut [T],
+    log: Vec<UndoEntry<T, S>>,
+}
+
+impl<'a, T: LinkType, S> Transaction<'a, T, S> {
+    /// Starts a transaction over `storage`.
+    pub fn new(storage: &'a mut S) -> Self {
+        Self::with_roots(storage, &mut [])
+    }
+
+    /// Starts a transaction over `storage` and the tree roots in `roots`.
+    pub fn with_roots(storage: &'a mut S, roots: &'a mut [T]) -> Self {
+        Self {
+            storage,
+            roots,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn storage(&self) -> &S {
+        self.storage
+    }
+
+    /// Returns root slot `index`.
+    pub fn root(&self, index: usize) -> T {
+        self.roots[index]
+    }
+
+    /// Sets root slot `index`, logging its old value.
+    pub fn set_root(&mut self, index: usize, root: T) {
+        let old = self.roots[index];
+        if old != root {
+            self.log.push(UndoEntry::Root { index, value: old });
+            self.roots[index] = root;
+        }
+    }
+
+    /// Runs `update` on a copy of root slot `index` and stores the result
+    /// with [`Transaction::set_root`], for the tree methods that take the
+    /// root by pointer.
+    pub fn with_root<R>(&mut self, index: usize, update: impl FnOnce(&mut Self, &mut T) -> R) -> R {
+        let mut root = self.roots[index];
+        let result = update(self, &mut root);
+        self.set_root(index, root);
+        result
+    }
+
+    /// Number of field changes that a rollback would undo.
+    pub fn logged_changes(&self) -> usize {
+        self.log.len()
+    }
+
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint(self.log.len())
+    }
+
+    /// Undoes the changes made after `savepoint`, which stays usable.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        while self.log.len() > savepoint.0 {
+            let entry = self.log.pop().unwrap();
+            undo(self.storage, self.roots, entry);
+        }
+    }
+
+    /// Undoes every change of the transaction and ends it.
+    pub fn rollback(mut self) {
+        self.rollback_to(Savepoint(0));
+    }
+
+    /// Keeps every change of the transaction and ends it.
+    pub fn commit(mut self) {
+        self.log.clear();
+    }
+
+    fn record(&mut self, undo: unsafe fn(&mut S, T, T), target: T, value: T) {
+        self.log.push(UndoEntry::Field {
+            undo,
+            target,
+            value,
+        });
+    }
+}
+
+/// Restores the value recorded by `entry`.
+fn undo<T, S>(storage: &mut S, roots: &mut [T], entry: UndoEntry<T, S>) {
+    match entry {
+        // SAFETY: the entry restores a value read from the same field
+        // through the storage's own accessors.
+        UndoEntry::Field {
+            undo,
+            target,
+            value,
+        } => unsafe { undo(storage, target, value) },
+        UndoEntry::Root { index, value } => roots[index] = value,
+    }
+}
+
+impl<T, S> Drop for Transaction<'_, T, S> {
+    /// Rolls back the changes that were not committed.
+    fn drop(&mut self) {
+        while let Some(entry) = self.log.pop() {
+            undo(self.storage, self.roots, entry);
+        }
+    }
+}
+
+impl<T: LinkType, S: RecursiveSizeBalancedTree<T>> RecursiveSizeBalancedTree<T>
+    for Transaction<'_, T, S>
+{
+    unsafe fn get_mut_left_reference(&mut self, node: T) -> *mut T {
+        let left = self.storage.get_left(node);
+        self.record(
+            |storage, node, left| storage.set_left(node, left),
+            node,
+            left,
+        );
+        self.storage.get_mut_left_reference(node)
+    }
+
+    unsafe fn get_mut_right_reference(&mut self, node: T) -> *mut T {
+        let right = self.storage.get_right(node);
+        self.record(
+            |storage, node, right| storage.set_right(node, right),
+            node,
+            right,
+        );
+        self.storage.get_mut_right_reference(node)
+    }
+
+    unsafe fn get_left_reference(&self, node: T) -> *const T {
+        self.storage.get_left_reference(node)
+    }
+
+    unsafe fn get_right_reference(&self, node: T) -> *const T {
+        self.storage.get_right_reference(node)
+    }
+
+    unsafe fn get_left(&self, node: T) -> T {
+        self.storage.get_left(node)
+    }
+
+    unsafe fn get_right(&self, node: T) -> T {
+        self.storage.get_right(node)
+    }
+
+    unsafe fn get_size(&self, node: T) -> T {
+        RecursiveSizeBalancedTree::get_size(self.storage, node)
+    }
+
+    unsafe fn set_left(&mut self, node: T, left: T) {
+        let old = self.storage.get_left(node);
+        self.record(
+            |storage, node, left| storage.set_left(node, left),
+            node,
+            old,
+        );
+        self.storage.set_left(node, left);
+    }
+
+    unsafe fn set_right(&mut self, node: T, right: T) {
+        let old = self.storage.get_right(node);
+        self.record(
+            |storage, node, right| storage.set_right(node, right),
+            node,
+            old,
+        );
+        self.storage.set_right(node, right);
+    }
+
+    unsafe fn set_size(&mut self, node: T, size: T) {
+        let old = RecursiveSizeBalancedTree::get_size(self.storage, node);
+        self.record(
+            |storage, node, size| RecursiveSizeBalancedTree::set_size(storage, node, size),
+            node,
+            old,
+        );
+        RecursiveSizeBalancedTree::set_size(self.storage, node, size);
+    }
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool {
+        self.storage.first_is_to_the_left_of_second(first, second)
+    }
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool {
+        self.storage.first_is_to_the_right_of_second(first, second)
+    }
+}
+
+impl<T: LinkType, S: IterativeSizeBalancedTree<T>> IterativeSizeBalancedTree<T>
+    for Transaction<'_, T, S>
+{
+}
+
+impl<T: LinkType, S: SlotSizeBalancedTree<T>> SlotSizeBalancedTree<T> for Transaction<'_, T, S> {}
+
+impl<T: LinkType, S: SplayTree<T>> SplayTree<T> for Transaction<'_, T, S> {}
+
+impl<T: LinkType, S: Treap<T>> Treap<T> for Transaction<'_, T, S> {}
+
+impl<T: LinkType, S: LinkedList<T>> LinkedList<T> for Transaction<'_, T, S> {
+    fn get_previous(&self, element: T) -> T {
+        LinkedList::get_previous(self.storage, element)
+    }
+
+    fn get_next(&self, element: T) -> T {
+        LinkedList::get_next(self.storage, element)
+    }
+
+    fn set_previous(&mut self, element: T, previous: T) {
+        let old = LinkedList::get_previous(self.storage, element);
+        self.record(
+            |storage, element, previous| storage.set_previous(element, previous),
+            element,
+            old,
+        );
+        self.storage.set_previous(element, previous);
+    }
+
+    fn set_next(&mut self, element: T, next: T) {
+        let old = LinkedList::get_next(self.storage, element);
+        self.record(
+            |storage, element, next| storage.set_next(element, next),
+            element,
+            old,
+        );
+        self.storage.set_next(element, next);
+    }
+}
+
+impl<T: LinkType, S: AbsoluteLinkedList<T>> AbsoluteLinkedList<T> for Transaction<'_, T, S> {
+    fn get_first(&self) -> T {
+        AbsoluteLinkedList::get_first(self.storage)
+    }
+
+    fn get_last(&self) -> T {
+        AbsoluteLinkedList::get_last(self.storage)
+    }
+
+    fn get_size(&self) -> T {
+        AbsoluteLinkedList::get_size(self.storage)
+    }
+
+    fn set_first(&mut self, element: T) {
+        let old = AbsoluteLinkedList::get_first(self.storage);
+        self.record(
+            |storage, _, element| AbsoluteLinkedList::set_first(storage, element),
+            T::funty(0),
+            old,
+        );
+        AbsoluteLinkedList::set_first(self.storage, element);
+    }
+
+    fn set_last(&mut self, element: T) {
+        let old = AbsoluteLinkedList::get_last(self.storage);
+        self.record(
+            |storage, _, element| AbsoluteLinkedList::set_last(storage, element),
+            T::funty(0),
+            old,
+        );
+        AbsoluteLinkedList::set_last(self.storage, element);
+    }
+
+    fn set_size(&mut self, size: T) {
+        let old = AbsoluteLinkedList::get_size(self.storage);
+        self.record(
+            |storage, _, size| AbsoluteLinkedList::set_size(storage, size),
+            T::funty(0),
+            old,
+        );
+        AbsoluteLinkedList::set_size(self.storage, size);
+    }
+}
+
+impl<T: LinkType, S: AbsoluteCircularLinkedList<T>> AbsoluteCircularLinkedList<T>
+    for Transaction<'_, T, S>
+{
+}
+
+impl<T: LinkType, S: RelativeLinkedList<T>> RelativeLinkedList<T> for Transaction<'_, T, S> {
+    fn get_first(&self, head: T) -> T {
+        RelativeLinkedList::get_first(self.storage, head)
+    }
+
+    fn get_last(&self, head: T) -> T {
+        RelativeLinkedList::get_last(self.storage, head)
+    }
+
+    fn get_size(&self, head: T) -> T {
+        RelativeLinkedList::get_size(self.storage, head)
+    }
+
+    fn set_first(&mut self, head: T, element: T) {
+        let old = RelativeLinkedList::get_first(self.storage, head);
+        self.record(
+            |storage, head, element| RelativeLinkedList::set_first(storage, head, element),
+            head,
+            old,
+        );
+        RelativeLinkedList::set_first(self.storage, head, element);
+    }
+
+    fn set_last(&mut self, head: T, element: T) {
+        let old = RelativeLinkedList::get_last(self.storage, head);
+        self.record(
+            |storage, head, element| RelativeLinkedList::set_last(storage, head, element),
+            head,
+            old,
+        );
+        RelativeLinkedList::set_last(self.storage, head, element);
+    }
+
+    fn set_size(&mut self, head: T, size: T) {
+        let old = RelativeLinkedList::get_size(self.storage, head);
+        self.record(
+            |storage, head, size| RelativeLinkedList::set_size(storage, head, size),
+            head,
+            old,
+        );
+        RelativeLinkedList::set_size(self.storage, head, size);
+    }
+}
+
+impl<T: LinkType, S: RelativeCircularLinkedList<T>> RelativeCircularLinkedList<T>
+    for Transaction<'_, T, S>
+{
+}
@@ -0,0 +1,544 @@
+//! Conformance suites for implementors of the tree and list traits.
+//!
+//! Each suite replays randomized operation sequences on storages built by a
+//! factory and on a reference model (a `BTreeSet` for trees, `VecDeque`s
+//! for lists), checking the structure after every step. A failing sequence
+//! is shrunk to a short one that still fails before it is reported.
+
+use crate::{
+    hash, AbsoluteCircularLinkedList, IterativeSizeBalancedTree, LinkType,
+    RelativeCircularLinkedList,
+};
+use core::fmt;
+use std::collections::{BTreeSet, VecDeque};
+use std::format;
+use std::panic::{self, AssertUnwindSafe};
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
+
+/// Parameters of a conformance run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conformance {
+    /// Seed of the first sequence; sequence `i` uses `seed + i`.
+    pub seed: u64,
+    /// Number of sequences to run.
+    pub cases: usize,
+    /// Operations per sequence.
+    pub steps: usize,
+    /// Nodes `1..=capacity` must be valid in every storage the factory
+    /// builds. With no nodes there is nothing to check and suites pass.
+    pub capacity: usize,
+}
+
+impl Default for Conformance {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            cases: 64,
+            steps: 200,
+            capacity: 32,
+        }
+    }
+}
+
+/// Shrunk failing sequence reported by a suite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure<O> {
+    /// Seed of the sequence that failed before shrinking.
+    pub seed: u64,
+    /// Shortest failing sequence found.
+    pub operations: Vec<O>,
+    /// Index of the operation after which the check failed.
+    pub step: usize,
+    pub message: String,
+}
+
+impl<O: fmt::Debug> fmt::Display for Failure<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {}: {} after step {} of {:?}",
+            self.seed, self.message, self.step, self.operations
+        )
+    }
+}
+
+impl<O: fmt::Debug> std::error::Error for Failure<O> {}
+
+/// Operation of the tree suite. Operations the model cannot apply, such as
+/// attaching an attached node, are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeOperation {
+    Attach(usize),
+    Detach(usize),
+}
+
+/// Operation of the list suites, on the list at `head` for relative lists.
+/// Operations the model cannot apply are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOperation {
+    AttachFirst {
+        head: usize,
+        element: usize,
+    },
+    AttachLast {
+        head: usize,
+        element: usize,
+    },
+    AttachBefore {
+        head: usize,
+        base: usize,
+        element: usize,
+    },
+    AttachAfter {
+        head: usize,
+        base: usize,
+        element: usize,
+    },
+    Detach {
+        head: usize,
+        element: usize,
+    },
+}
+
+/// Deterministic generator for operation sequences.
+struct Random(u64);
+
+impl Random {
+    /// Returns a value in `0..bound`; callers never pass an empty range,
+    /// which would yield `0`.
+    fn below(&mut self, bound: usize) -> usize {
+        debug_assert!(bound > 0, "empty range");
+        self.0 = self.0.wrapping_add(1);
+        (hash::splitmix64(self.0) % bound.max(1) as u64) as usize
+    }
+
+    fn node(&mut self, capacity: usize) -> usize {
+        self.below(capacity) + 1
+    }
+}
+
+fn link<T: LinkType>(index: usize) -> T {
+    T::try_from(index as u64)
+        .ok()
+        .expect("capacity does not fit the link type")
+}
+
+/// Runs every case of a suite, shrinking and returning the first failure.
+fn run_cases<O: Clone>(
+    config: Conformance,
+    generate: impl Fn(&mut Random) -> O,
+    replay: impl Fn(&[O]) -> Result<(), (usize, String)>,
+) -> Result<(), Failure<O>> {
+    if config.capacity == 0 {
+        return Ok(());
+    }
+    for case in 0..config.cases {
+        let seed = config.seed.wrapping_add(case as u64);
+        let mut random = Random(hash::splitmix64(seed));
+        let operations: Vec<O> = (0..config.steps).map(|_| generate(&mut random)).collect();
+        if let Err(failure) = replay(&operations) {
+            let (operations, (step, message)) = shrink(operations, failure, &replay);
+            return Err(Failure {
+                seed,
+                operations,
+                step,
+                message,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Removes chunks of halving size from `operations` while the rest still
+/// fails, and cuts the sequence after the failing step.
+fn shrink<O: Clone>(
+    mut operations: Vec<O>,
+    mut failure: (usize, String),
+    replay: &impl Fn(&[O]) -> Result<(), (usize, String)>,
+) -> (Vec<O>, (usize, String)) {
+    operations.truncate(failure.0 + 1);
+    let mut chunk = operations.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        while start < operations.len() {
+            let end = (start + chunk).min(operations.len());
+            let mut candidate = operations[..start].to_vec();
+            candidate.extend_from_slice(&operations[end..]);
+            match replay(&candidate) {
+                Err(found) => {
+                    candidate.truncate(found.0 + 1);
+                    operations = candidate;
+                    failure = found;
+                }
+                Ok(()) => start += chunk,
+            }
+        }
+        chunk /= 2;
+    }
+    (operations, failure)
+}
+
+/// Replays `operations` step by step, turning a failed check or a panic of
+/// the implementor into the failing step and a message.
+fn replay_steps<O>(
+    operations: &[O],
+    mut step: impl FnMut(&O) -> Result<(), String>,
+) -> Result<(), (usize, String)> {
+    for (index, operation) in operations.iter().enumerate() {
+        match panic::catch_unwind(AssertUnwindSafe(|| step(operation))) {
+            Ok(Ok(())) => {}
+            Ok(Err(message)) => return Err((index, message)),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| String::from(*message))
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                return Err((index, format!("panicked: {}", message)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks an [`IterativeSizeBalancedTree`] against a `BTreeSet` of attached
+/// nodes: after every attach or detach the sizes must add up, the in-order
+/// walk must be strictly increasing under the storage's comparisons, and
+/// `contains` must agree with the model for every node.
+pub fn check_size_balanced_tree<T, S, F>(
+    config: Conformance,
+    factory: F,
+) -> Result<(), Failure<TreeOperation>>
+where
+    T: LinkType,
+    S: IterativeSizeBalancedTree<T>,
+    F: Fn() -> S,
+{
+    let capacity = config.capacity;
+    run_cases(
+        config,
+        |random| {
+            let node = random.node(capacity);
+            if random.below(3) == 0 {
+                TreeOperation::Detach(node)
+            } else {
+                TreeOperation::Attach(node)
+            }
+        },
+        |operations| {
+            let mut storage = factory();
+            let mut root = T::funty(0);
+            let mut model = BTreeSet::new();
+            replay_steps(operations, |&operation| {
+                unsafe {
+                    match operation {
+                        TreeOperation::Attach(node) if model.insert(node) => {
+                            storage.attach(&mut root, link(node))
+                        }
+                        TreeOperation::Detach(node) if model.remove(&node) => {
+                            storage.detach(&mut root, link(node))
+                        }
+                        _ => {}
+                    }
+                }
+                check_tree(&storage, root, &model, capacity)
+            })
+        },
+    )
+}
+
+fn check_tree<T: LinkType, S: IterativeSizeBalancedTree<T>>(
+    storage: &S,
+    root: T,
+    model: &BTreeSet<usize>,
+    capacity: usize,
+) -> Result<(), String> {
+    let mut order = Vec::new();
+    unsafe { walk_tree(storage, root, &mut order, model.len())? };
+    if order.len() != model.len() {
+        return Err(format!(
+            "tree has {} nodes, model has {}",
+            order.len(),
+            model.len()
+        ));
+    }
+    for pair in order.windows(2) {
+        if !unsafe { storage.first_is_to_the_left_of_second(pair[0], pair[1]) } {
+            return Err(format!("{} is not to the left of {}", pair[0], pair[1]));
+        }
+    }
+    for node in 1..=capacity {
+        let found = unsafe { storage.contains(link(node), root) };
+        if found != model.contains(&node) {
+            return Err(format!("contains({}) is {}", node, found));
+        }
+        if found != order.contains(&link(node)) {
+            return Err(format!("{} is found but not attached", node));
+        }
+    }
+    Ok(())
+}
+
+/// Collects the tree at `root` in order and checks its sizes, giving up
+/// after `limit` nodes so that cycles are reported instead of followed.
+///
+/// The walk keeps its own stack and counts every descent, so a link back to
+/// an ancestor (or to the node itself) ends the walk instead of the thread.
+unsafe fn walk_tree<T: LinkType, S: IterativeSizeBalancedTree<T>>(
+    storage: &S,
+    root: T,
+    order: &mut Vec<T>,
+    limit: usize,
+) -> Result<(), String> {
+    let mut stack = Vec::new();
+    let mut visited = 0;
+    let mut current = root;
+    loop {
+        while current != T::funty(0) {
+            if visited >= limit {
+                return Err(format!(
+                    "more nodes are reachable than the {} attached",
+                    limit
+                ));
+            }
+            visited += 1;
+            stack.push(current);
+            current = storage.get_left(current);
+        }
+        let node = match stack.pop() {
+            Some(node) => node,
+            None => return Ok(()),
+        };
+        // Sizes that add up at every node of a finite tree are the real
+        // subtree sizes.
+        let left = storage.get_size_or_zero(storage.get_left(node)).as_usize();
+        let right = storage.get_size_or_zero(storage.get_right(node)).as_usize();
+        let size = storage.get_size(node).as_usize();
+        if size != left + right + 1 {
+            return Err(format!(
+                "size of {} is {}, subtrees hold {}",
+                node,
+                size,
+                left + right + 1
+            ));
+        }
+        order.push(node);
+        current = storage.get_right(node);
+    }
+}
+
+fn generate_list_operation(random: &mut Random, heads: usize, capacity: usize) -> ListOperation {
+    let head = random.node(heads);
+    let element = random.node(capacity);
+    let base = random.node(capacity);
+    match random.below(5) {
+        0 => ListOperation::AttachFirst { head, element },
+        1 => ListOperation::AttachLast { head, element },
+        2 => ListOperation::AttachBefore {
+            head,
+            base,
+            element,
+        },
+        3 => ListOperation::AttachAfter {
+            head,
+            base,
+            element,
+        },
+        _ => ListOperation::Detach { head, element },
+    }
+}
+
+/// Applies `operation` to `models` (one per head, first at index `1`) if
+/// it is valid there, returning whether it was applied.
+fn apply_list_model(models: &mut [VecDeque<usize>], operation: ListOperation) -> bool {
+    let attached = |element: usize| models.iter().any(|model| model.contains(&element));
+    match operation {
+        ListOperation::AttachFirst { head, element } if !attached(element) => {
+            models[head].push_front(element);
+        }
+        ListOperation::AttachLast { head, element } if !attached(element) => {
+            models[head].push_back(element);
+        }
+        ListOperation::AttachBefore {
+            head,
+            base,
+            element,
+        } if !attached(element) => match models[head].iter().position(|&e| e == base) {
+            Some(index) => models[head].insert(index, element),
+            None => return false,
+        },
+        ListOperation::AttachAfter {
+            head,
+            base,
+            element,
+        } if !attached(element) => match models[head].iter().position(|&e| e == base) {
+            Some(index) => models[head].insert(index + 1, element),
+            None => return false,
+        },
+        ListOperation::Detach { head, element } => {
+            match models[head].iter().position(|&e| e == element) {
+                Some(index) => {
+                    models[head].remove(index);
+                }
+                None => return false,
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Checks that a circular list starting at `first` with `size` elements,
+/// read through `next` and `previous`, holds exactly `model`.
+fn check_circular_list<T: LinkType>(
+    first: T,
+    last: T,
+    size: T,
+    next: impl Fn(T) -> T,
+    previous: impl Fn(T) -> T,
+    model: &VecDeque<usize>,
+) -> Result<(), String> {
+    if size.as_usize() != model.len() {
+        return Err(format!("size is {}, model has {}", size, model.len()));
+    }
+    if model.is_empty() {
+        if first != T::funty(0) || last != T::funty(0) {
+            return Err(format!("empty list has first {} and last {}", first, last));
+        }
+        return Ok(());
+    }
+    let mut element = first;
+    for &expected in model {
+        if element != link(expected) {
+            return Err(format!("found {} where {} was expected", element, expected));
+        }
+        let following = next(element);
+        if previous(following) != element {
+            return Err(format!("previous of {} is not {}", following, element));
+        }
+        element = following;
+    }
+    if element != first {
+        return Err(format!("list does not wrap around to {}", first));
+    }
+    if last != link(*model.back().unwrap()) {
+        return Err(format!("last is {}", last));
+    }
+    Ok(())
+}
+
+/// Checks an [`AbsoluteCircularLinkedList`] against a `VecDeque`: after
+/// every attach or detach the list must hold the model's elements in order
+/// with matching `first`, `last`, `size` and links in both directions.
+pub fn check_absolute_circular_list<T, S, F>(
+    config: Conformance,
+    factory: F,
+) -> Result<(), Failure<ListOperation>>
+where
+    T: LinkType,
+    S: AbsoluteCircularLinkedList<T>,
+    F: Fn() -> S,
+{
+    let capacity = config.capacity;
+    run_cases(
+        config,
+        |random| generate_list_operation(random, 1, capacity),
+        |operations| {
+            let mut list = factory();
+            let mut models = [VecDeque::new(), VecDeque::new()];
+            replay_steps(operations, |&operation| {
+                if apply_list_model(&mut models, operation) {
+                    match operation {
+                        ListOperation::AttachFirst { element, .. } => {
+                            list.attach_as_first(link(element))
+                        }
+                        ListOperation::AttachLast { element, .. } => {
+                            list.attach_as_last(link(element))
+                        }
+                        ListOperation::AttachBefore { base, element, .. } => {
+                            list.attach_before(link(base), link(element))
+                        }
+                        ListOperation::AttachAfter { base, element, .. } => {
+                            list.attach_after(link(base), link(element))
+                        }
+                        ListOperation::Detach { element, .. } => list.detach(link(element)),
+                    }
+                }
+                check_circular_list(
+                    list.get_first(),
+                    list.get_last(),
+                    list.get_size(),
+                    |element| list.get_next(element),
+                    |element| list.get_previous(element),
+                    &models[1],
+                )
+            })
+        },
+    )
+}
+
+/// Checks a [`RelativeCircularLinkedList`] with heads `1..=heads` against
+/// one `VecDeque` per head, moving elements between lists. Passes without
+/// running anything when there are no heads.
+pub fn check_relative_circular_list<T, S, F>(
+    config: Conformance,
+    heads: usize,
+    factory: F,
+) -> Result<(), Failure<ListOperation>>
+where
+    T: LinkType,
+    S: RelativeCircularLinkedList<T>,
+    F: Fn() -> S,
+{
+    if heads == 0 {
+        return Ok(());
+    }
+    let capacity = config.capacity;
+    run_cases(
+        config,
+        |random| generate_list_operation(random, heads, capacity),
+        |operations| {
+            let mut list = factory();
+            let mut models = vec![VecDeque::new(); heads + 1];
+            replay_steps(operations, |&operation| {
+                if apply_list_model(&mut models, operation) {
+                    match operation {
+                        ListOperation::AttachFirst { head, element } => {
+                            list.attach_as_first(link(head), link(element))
+                        }
+                        ListOperation::AttachLast { head, element } => {
+                            list.attach_as_last(link(head), link(element))
+                        }
+                        ListOperation::AttachBefore {
+                            head,
+                            base,
+                            element,
+                        } => list.attach_before(link(head), link(base), link(element)),
+                        ListOperation::AttachAfter {
+                            head,
+                            base,
+                            element,
+                        } => list.attach_after(link(head), link(base), link(element)),
+                        ListOperation::Detach { head, element } => {
+                            list.detach(link(head), link(element))
+                        }
+                    }
+                }
+                for (head, model) in models.iter().enumerate().skip(1) {
+                    let head = link(head);
+                    check_circular_list(
+                        list.get_first(head),
+                        list.get_last(head),
+                        list.get_size(head),
+                        |element| list.get_next(element),
+                        |element| list.get_previous(element),
+                        model,
+                    )?;
+                }
+                Ok(())
+            })
+        },
+    )
+}
@@ -1,8 +1,14 @@
 //! Comprehensive tests for 100% code coverage of platform-trees
 
+use std::{string::ToString, vec, vec::Vec};
+
 use crate::{
-    AbsoluteCircularLinkedList, AbsoluteLinkedList, IterativeSizeBalancedTree, LinkType,
-    LinkedList, RecursiveSizeBalancedTree, RelativeCircularLinkedList, RelativeLinkedList,
+    AbsoluteCircularLinkedList, AbsoluteLinkedList, AvlTree, BPlusTree, CheckedTree, CritBitChild,
+    CritBitTrie, DoubletsIndex, Flow, IndexNode, IterativeSizeBalancedTree, LeftRightTree,
+    LinkType, LinkedList, LinksTree, NodeAllocator, PersistentSizeBalancedTree,
+    RecursiveSizeBalancedTree, RedBlackTree, RelativeCircularLinkedList, RelativeLinkedList,
+    ScapegoatRecord, ScapegoatTree, SkipList, Slot, SlotSizeBalancedTree, SplayTree, Treap,
+    TreeError,
 };
 
 // =============================================================================
@@ -148,7 +154,7 @@ impl RelativeLinkedList<usize> for TestRelativeList {
 impl RelativeCircularLinkedList<usize> for TestRelativeList {}
 
 /// A tree node structure for testing SizeBalancedTree
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 struct TreeNode {
     left: usize,
     right: usize,
@@ -156,6 +162,7 @@ struct TreeNode {
 }
 
 /// A simple SizeBalancedTree implementation for testing
+#[derive(Clone)]
 struct TestTree {
     nodes: Vec<TreeNode>,
 }
@@ -220,1456 +227,5770 @@ impl RecursiveSizeBalancedTree<usize> for TestTree {
 
 impl IterativeSizeBalancedTree<usize> for TestTree {}
 
-// =============================================================================
-// LinkType trait tests
-// =============================================================================
+impl SlotSizeBalancedTree<usize> for TestTree {}
 
-#[cfg(test)]
-mod link_type_tests {
-    use super::*;
+impl SplayTree<usize> for TestTree {}
 
-    #[test]
-    fn test_funty_zero() {
-        assert_eq!(usize::funty(0), 0usize);
-        assert_eq!(u8::funty(0), 0u8);
-        assert_eq!(u16::funty(0), 0u16);
-        assert_eq!(u32::funty(0), 0u32);
-        assert_eq!(u64::funty(0), 0u64);
-    }
+impl Treap<usize> for TestTree {}
 
-    #[test]
-    fn test_funty_one() {
-        assert_eq!(usize::funty(1), 1usize);
-        assert_eq!(u8::funty(1), 1u8);
-        assert_eq!(u16::funty(1), 1u16);
-        assert_eq!(u32::funty(1), 1u32);
-        assert_eq!(u64::funty(1), 1u64);
+/// A keyed node with a reference count for testing PersistentSizeBalancedTree
+#[derive(Debug, Clone, Copy, Default)]
+struct PersistentNode {
+    key: usize,
+    left: usize,
+    right: usize,
+    size: usize,
+    references: usize,
+}
+
+/// A persistent tree storage for testing, allocating copies from a free list
+struct TestPersistentTree {
+    nodes: Vec<PersistentNode>,
+    free: Vec<usize>,
+}
+
+impl TestPersistentTree {
+    fn new() -> Self {
+        Self {
+            nodes: vec![PersistentNode::default()],
+            free: Vec::new(),
+        }
     }
 
-    #[test]
-    fn test_funty_various_values() {
-        for i in 0..=255u8 {
-            assert_eq!(u8::funty(i), i);
-            assert_eq!(u16::funty(i), i as u16);
-            assert_eq!(u32::funty(i), i as u32);
-            assert_eq!(u64::funty(i), i as u64);
-            assert_eq!(usize::funty(i), i as usize);
+    fn allocate(&mut self, key: usize) -> usize {
+        let node = PersistentNode {
+            key,
+            ..PersistentNode::default()
+        };
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
         }
     }
+
+    fn live_nodes(&self) -> usize {
+        self.nodes.len() - 1 - self.free.len()
+    }
 }
 
-// =============================================================================
-// LinkedList trait tests
-// =============================================================================
+impl RecursiveSizeBalancedTree<usize> for TestPersistentTree {
+    unsafe fn get_mut_left_reference(&mut self, node: usize) -> *mut usize {
+        &mut self.nodes[node].left
+    }
 
-#[cfg(test)]
-mod linked_list_tests {
-    use super::*;
+    unsafe fn get_mut_right_reference(&mut self, node: usize) -> *mut usize {
+        &mut self.nodes[node].right
+    }
 
-    #[test]
-    fn test_get_set_previous() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_previous(1, 5);
-        assert_eq!(list.get_previous(1), 5);
+    unsafe fn get_left_reference(&self, node: usize) -> *const usize {
+        &self.nodes[node].left
     }
 
-    #[test]
-    fn test_get_set_next() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_next(1, 3);
-        assert_eq!(list.get_next(1), 3);
+    unsafe fn get_right_reference(&self, node: usize) -> *const usize {
+        &self.nodes[node].right
     }
-}
 
-// =============================================================================
-// AbsoluteLinkedList trait tests
-// =============================================================================
+    unsafe fn get_left(&self, node: usize) -> usize {
+        self.nodes[node].left
+    }
 
-#[cfg(test)]
-mod absolute_linked_list_tests {
-    use super::*;
+    unsafe fn get_right(&self, node: usize) -> usize {
+        self.nodes[node].right
+    }
 
-    #[test]
-    fn test_get_set_first() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_first(5);
-        assert_eq!(list.get_first(), 5);
+    unsafe fn get_size(&self, node: usize) -> usize {
+        self.nodes[node].size
     }
 
-    #[test]
-    fn test_get_set_last() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_last(7);
-        assert_eq!(list.get_last(), 7);
+    unsafe fn set_left(&mut self, node: usize, left: usize) {
+        self.nodes[node].left = left;
     }
 
-    #[test]
-    fn test_get_set_size() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_size(3);
-        assert_eq!(list.get_size(), 3);
+    unsafe fn set_right(&mut self, node: usize, right: usize) {
+        self.nodes[node].right = right;
     }
 
-    #[test]
-    fn test_inc_size() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_size(5);
-        list.inc_size();
-        assert_eq!(list.get_size(), 6);
+    unsafe fn set_size(&mut self, node: usize, size: usize) {
+        self.nodes[node].size = size;
     }
 
-    #[test]
-    fn test_dec_size() {
-        let mut list = TestAbsoluteList::new(10);
-        list.set_size(5);
-        list.dec_size();
-        assert_eq!(list.get_size(), 4);
+    unsafe fn first_is_to_the_left_of_second(&self, first: usize, second: usize) -> bool {
+        self.nodes[first].key < self.nodes[second].key
     }
-}
 
-// =============================================================================
-// AbsoluteCircularLinkedList trait tests
-// =============================================================================
+    unsafe fn first_is_to_the_right_of_second(&self, first: usize, second: usize) -> bool {
+        self.nodes[first].key > self.nodes[second].key
+    }
+}
 
-#[cfg(test)]
-mod absolute_circular_linked_list_tests {
-    use super::*;
+impl PersistentSizeBalancedTree<usize> for TestPersistentTree {
+    unsafe fn get_references(&self, node: usize) -> usize {
+        self.nodes[node].references
+    }
 
-    #[test]
-    fn test_attach_as_first_empty_list() {
-        let mut list = TestAbsoluteList::new(10);
+    unsafe fn set_references(&mut self, node: usize, references: usize) {
+        self.nodes[node].references = references;
+    }
 
-        list.attach_as_first(1);
+    unsafe fn copy_node(&mut self, node: usize) -> usize {
+        self.allocate(self.nodes[node].key)
+    }
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 1);
-        assert_eq!(list.get_size(), 1);
-        assert_eq!(list.get_previous(1), 1); // Points to itself
-        assert_eq!(list.get_next(1), 1); // Points to itself
+    unsafe fn free_node(&mut self, node: usize) {
+        self.nodes[node] = PersistentNode::default();
+        self.free.push(node);
     }
+}
 
-    #[test]
-    fn test_attach_as_first_non_empty_list() {
-        let mut list = TestAbsoluteList::new(10);
+/// A tree node with a height field for testing AvlTree
+#[derive(Debug, Clone, Copy, Default)]
+struct AvlNode {
+    left: usize,
+    right: usize,
+    height: usize,
+}
 
-        list.attach_as_first(1);
-        list.attach_as_first(2);
+/// A simple AVL tree implementation for testing
+struct TestAvlTree {
+    nodes: Vec<AvlNode>,
+}
 
-        assert_eq!(list.get_first(), 2);
-        assert_eq!(list.get_last(), 1);
-        assert_eq!(list.get_size(), 2);
+impl TestAvlTree {
+    fn new(capacity: usize) -> Self {
+        Self {
+            nodes: vec![AvlNode::default(); capacity + 1],
+        }
     }
+}
 
-    #[test]
-    fn test_attach_as_last_empty_list() {
-        let mut list = TestAbsoluteList::new(10);
+impl AvlTree<usize> for TestAvlTree {
+    unsafe fn get_left(&self, node: usize) -> usize {
+        self.nodes[node].left
+    }
 
-        list.attach_as_last(1);
+    unsafe fn get_right(&self, node: usize) -> usize {
+        self.nodes[node].right
+    }
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 1);
-        assert_eq!(list.get_size(), 1);
+    unsafe fn get_height(&self, node: usize) -> usize {
+        self.nodes[node].height
     }
 
-    #[test]
-    fn test_attach_as_last_non_empty_list() {
-        let mut list = TestAbsoluteList::new(10);
+    unsafe fn set_left(&mut self, node: usize, left: usize) {
+        self.nodes[node].left = left;
+    }
 
-        list.attach_as_last(1);
-        list.attach_as_last(2);
+    unsafe fn set_right(&mut self, node: usize, right: usize) {
+        self.nodes[node].right = right;
+    }
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 2);
-        assert_eq!(list.get_size(), 2);
+    unsafe fn set_height(&mut self, node: usize, height: usize) {
+        self.nodes[node].height = height;
     }
 
-    #[test]
-    fn test_attach_before() {
-        let mut list = TestAbsoluteList::new(10);
+    unsafe fn first_is_to_the_left_of_second(&self, first: usize, second: usize) -> bool {
+        first < second
+    }
 
-        // Create list: 1 <-> 2
-        list.attach_as_first(1);
-        list.attach_as_last(2);
+    unsafe fn first_is_to_the_right_of_second(&self, first: usize, second: usize) -> bool {
+        first > second
+    }
+}
 
-        // Insert 3 before 2: 1 <-> 3 <-> 2
-        list.attach_before(2, 3);
+/// Color bit stolen from the size field of `TestRedBlackTree` nodes
+const RED_BIT: usize = 1 << (usize::BITS - 1);
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 2);
-        assert_eq!(list.get_size(), 3);
-        assert_eq!(list.get_next(1), 3);
-        assert_eq!(list.get_next(3), 2);
-        assert_eq!(list.get_previous(2), 3);
+/// A red-black tree implementation for testing, keeping the color in the
+/// high bit of the size
+struct TestRedBlackTree {
+    nodes: Vec<TreeNode>,
+}
+
+impl TestRedBlackTree {
+    fn new(capacity: usize) -> Self {
+        Self {
+            nodes: vec![TreeNode::default(); capacity + 1],
+        }
     }
+}
 
-    #[test]
-    fn test_attach_before_first() {
-        let mut list = TestAbsoluteList::new(10);
+impl RecursiveSizeBalancedTree<usize> for TestRedBlackTree {
+    unsafe fn get_mut_left_reference(&mut self, node: usize) -> *mut usize {
+        &mut self.nodes[node].left
+    }
 
-        list.attach_as_first(1);
-        list.attach_before(1, 2);
+    unsafe fn get_mut_right_reference(&mut self, node: usize) -> *mut usize {
+        &mut self.nodes[node].right
+    }
 
-        assert_eq!(list.get_first(), 2);
-        assert_eq!(list.get_last(), 1);
-        assert_eq!(list.get_size(), 2);
+    unsafe fn get_left_reference(&self, node: usize) -> *const usize {
+        &self.nodes[node].left
     }
 
-    #[test]
-    fn test_attach_after() {
-        let mut list = TestAbsoluteList::new(10);
+    unsafe fn get_right_reference(&self, node: usize) -> *const usize {
+        &self.nodes[node].right
+    }
 
-        // Create list: 1 <-> 2
-        list.attach_as_first(1);
-        list.attach_as_last(2);
+    unsafe fn get_left(&self, node: usize) -> usize {
+        self.nodes[node].left
+    }
 
-        // Insert 3 after 1: 1 <-> 3 <-> 2
-        list.attach_after(1, 3);
+    unsafe fn get_right(&self, node: usize) -> usize {
+        self.nodes[node].right
+    }
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 2);
-        assert_eq!(list.get_size(), 3);
-        assert_eq!(list.get_next(1), 3);
-        assert_eq!(list.get_next(3), 2);
+    unsafe fn get_size(&self, node: usize) -> usize {
+        self.nodes[node].size & !RED_BIT
     }
 
-    #[test]
-    fn test_attach_after_last() {
-        let mut list = TestAbsoluteList::new(10);
-
-        list.attach_as_first(1);
-        list.attach_after(1, 2);
-
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 2);
-        assert_eq!(list.get_size(), 2);
+    unsafe fn set_left(&mut self, node: usize, left: usize) {
+        self.nodes[node].left = left;
     }
 
-    #[test]
-    fn test_detach_single_element() {
-        let mut list = TestAbsoluteList::new(10);
+    unsafe fn set_right(&mut self, node: usize, right: usize) {
+        self.nodes[node].right = right;
+    }
 
-        list.attach_as_first(1);
-        list.detach(1);
+    unsafe fn set_size(&mut self, node: usize, size: usize) {
+        let red = self.nodes[node].size & RED_BIT;
+        self.nodes[node].size = size | red;
+    }
 
-        assert_eq!(list.get_first(), 0);
-        assert_eq!(list.get_last(), 0);
-        assert_eq!(list.get_size(), 0);
-        assert_eq!(list.get_previous(1), 0);
-        assert_eq!(list.get_next(1), 0);
+    unsafe fn first_is_to_the_left_of_second(&self, first: usize, second: usize) -> bool {
+        first < second
     }
 
-    #[test]
-    fn test_detach_first_element() {
-        let mut list = TestAbsoluteList::new(10);
+    unsafe fn first_is_to_the_right_of_second(&self, first: usize, second: usize) -> bool {
+        first > second
+    }
+}
 
-        list.attach_as_first(1);
-        list.attach_as_last(2);
-        list.attach_as_last(3);
+/// Number of levels of `TestSkipList`
+const SKIP_LEVELS: usize = 8;
 
-        list.detach(1);
+/// A skip list over keyed nodes for testing, with one list entry per
+/// `(node, level)` pair and one head per level
+struct TestSkipList {
+    keys: Vec<i64>,
+    entries: Vec<Node>,
+    heads: Vec<(usize, usize, usize)>, // (first, last, size)
+}
 
-        assert_eq!(list.get_first(), 2);
-        assert_eq!(list.get_last(), 3);
-        assert_eq!(list.get_size(), 2);
+impl TestSkipList {
+    fn new(keys: Vec<i64>) -> Self {
+        let count = keys.len();
+        Self {
+            keys,
+            entries: vec![Node::default(); count * SKIP_LEVELS],
+            heads: vec![(0, 0, 0); SKIP_LEVELS + 1],
+        }
     }
 
-    #[test]
-    fn test_detach_last_element() {
-        let mut list = TestAbsoluteList::new(10);
+    fn compare(&self, first: usize, second: usize) -> std::cmp::Ordering {
+        self.keys[first].cmp(&self.keys[second])
+    }
+}
 
-        list.attach_as_first(1);
-        list.attach_as_last(2);
-        list.attach_as_last(3);
+impl LinkedList<usize> for TestSkipList {
+    fn get_previous(&self, element: usize) -> usize {
+        self.entries[element].prev
+    }
 
-        list.detach(3);
+    fn get_next(&self, element: usize) -> usize {
+        self.entries[element].next
+    }
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 2);
-        assert_eq!(list.get_size(), 2);
+    fn set_previous(&mut self, element: usize, previous: usize) {
+        self.entries[element].prev = previous;
     }
 
-    #[test]
-    fn test_detach_middle_element() {
-        let mut list = TestAbsoluteList::new(10);
+    fn set_next(&mut self, element: usize, next: usize) {
+        self.entries[element].next = next;
+    }
+}
 
-        list.attach_as_first(1);
-        list.attach_as_last(2);
-        list.attach_as_last(3);
+impl RelativeLinkedList<usize> for TestSkipList {
+    fn get_first(&self, head: usize) -> usize {
+        self.heads[head].0
+    }
 
-        list.detach(2);
+    fn get_last(&self, head: usize) -> usize {
+        self.heads[head].1
+    }
 
-        assert_eq!(list.get_first(), 1);
-        assert_eq!(list.get_last(), 3);
-        assert_eq!(list.get_size(), 2);
-        assert_eq!(list.get_next(1), 3);
-        assert_eq!(list.get_previous(3), 1);
+    fn get_size(&self, head: usize) -> usize {
+        self.heads[head].2
     }
 
-    #[test]
-    fn test_circular_structure() {
-        let mut list = TestAbsoluteList::new(10);
+    fn set_first(&mut self, head: usize, element: usize) {
+        self.heads[head].0 = element;
+    }
 
-        list.attach_as_first(1);
-        list.attach_as_last(2);
-        list.attach_as_last(3);
+    fn set_last(&mut self, head: usize, element: usize) {
+        self.heads[head].1 = element;
+    }
 
-        // Check circular structure
-        assert_eq!(list.get_next(3), 1); // Last points to first
-        assert_eq!(list.get_previous(1), 3); // First points back to last
+    fn set_size(&mut self, head: usize, size: usize) {
+        self.heads[head].2 = size;
     }
 }
 
-// =============================================================================
-// RelativeLinkedList trait tests
-// =============================================================================
-
-#[cfg(test)]
-mod relative_linked_list_tests {
-    use super::*;
+impl RelativeCircularLinkedList<usize> for TestSkipList {}
 
-    #[test]
-    fn test_get_set_first() {
-        let mut list = TestRelativeList::new(10);
-        list.set_first(1, 5);
-        assert_eq!(list.get_first(1), 5);
+impl SkipList<usize> for TestSkipList {
+    fn get_max_level(&self) -> usize {
+        SKIP_LEVELS
     }
 
-    #[test]
-    fn test_get_set_last() {
-        let mut list = TestRelativeList::new(10);
-        list.set_last(1, 7);
-        assert_eq!(list.get_last(1), 7);
+    fn get_level_head(&self, level: usize) -> usize {
+        level + 1
     }
 
-    #[test]
-    fn test_get_set_size() {
-        let mut list = TestRelativeList::new(10);
-        list.set_size(1, 3);
-        assert_eq!(list.get_size(1), 3);
+    fn get_entry(&self, node: usize, level: usize) -> usize {
+        node * SKIP_LEVELS + level
     }
 
-    #[test]
-    fn test_inc_size() {
-        let mut list = TestRelativeList::new(10);
-        list.set_size(1, 5);
-        list.inc_size(1);
-        assert_eq!(list.get_size(1), 6);
+    fn get_entry_node(&self, entry: usize) -> usize {
+        entry / SKIP_LEVELS
     }
+}
 
-    #[test]
-    fn test_dec_size() {
-        let mut list = TestRelativeList::new(10);
-        list.set_size(1, 5);
-        list.dec_size(1);
-        assert_eq!(list.get_size(1), 4);
-    }
+/// A fixed-size page for testing BPlusTree
+#[derive(Debug, Clone, Copy, Default)]
+struct Page {
+    leaf: bool,
+    count: usize,
+    keys: [usize; 8],
+    children: [usize; 9],
+    next: usize,
+}
 
-    #[test]
-    fn test_multiple_heads() {
-        let mut list = TestRelativeList::new(10);
+/// An in-memory page store for testing BPlusTree, reusing freed pages
+struct TestPages {
+    pages: Vec<Page>,
+    free: Vec<usize>,
+    max_keys: usize,
+}
 
-        list.set_first(1, 2);
-        list.set_first(2, 3);
+impl TestPages {
+    fn new(max_keys: usize) -> Self {
+        Self {
+            pages: vec![Page::default()],
+            free: Vec::new(),
+            max_keys,
+        }
+    }
 
-        assert_eq!(list.get_first(1), 2);
-        assert_eq!(list.get_first(2), 3);
+    fn live_pages(&self) -> usize {
+        self.pages.len() - 1 - self.free.len()
     }
 }
 
-// =============================================================================
-// RelativeCircularLinkedList trait tests
-// =============================================================================
+impl BPlusTree<usize> for TestPages {
+    fn get_max_keys(&self) -> usize {
+        self.max_keys
+    }
 
-#[cfg(test)]
-mod relative_circular_linked_list_tests {
-    use super::*;
+    unsafe fn is_leaf(&self, page: usize) -> bool {
+        self.pages[page].leaf
+    }
 
-    const HEAD: usize = 1;
+    unsafe fn set_leaf(&mut self, page: usize, leaf: bool) {
+        self.pages[page].leaf = leaf;
+    }
 
-    #[test]
-    fn test_attach_as_first_empty_list() {
-        let mut list = TestRelativeList::new(10);
+    unsafe fn get_key_count(&self, page: usize) -> usize {
+        self.pages[page].count
+    }
 
-        list.attach_as_first(HEAD, 2);
+    unsafe fn set_key_count(&mut self, page: usize, count: usize) {
+        assert!(count <= self.max_keys, "page {} overflows", page);
+        self.pages[page].count = count;
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 2);
-        assert_eq!(list.get_size(HEAD), 1);
-        assert_eq!(list.get_previous(2), 2);
-        assert_eq!(list.get_next(2), 2);
+    unsafe fn get_key(&self, page: usize, index: usize) -> usize {
+        self.pages[page].keys[index]
     }
 
-    #[test]
-    fn test_attach_as_first_non_empty_list() {
-        let mut list = TestRelativeList::new(10);
+    unsafe fn set_key(&mut self, page: usize, index: usize, key: usize) {
+        self.pages[page].keys[index] = key;
+    }
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_as_first(HEAD, 3);
+    unsafe fn get_child(&self, page: usize, index: usize) -> usize {
+        self.pages[page].children[index]
+    }
 
-        assert_eq!(list.get_first(HEAD), 3);
-        assert_eq!(list.get_last(HEAD), 2);
-        assert_eq!(list.get_size(HEAD), 2);
+    unsafe fn set_child(&mut self, page: usize, index: usize, child: usize) {
+        self.pages[page].children[index] = child;
     }
 
-    #[test]
-    fn test_attach_as_last_empty_list() {
-        let mut list = TestRelativeList::new(10);
+    unsafe fn get_next_leaf(&self, page: usize) -> usize {
+        self.pages[page].next
+    }
 
-        list.attach_as_last(HEAD, 2);
+    unsafe fn set_next_leaf(&mut self, page: usize, next: usize) {
+        self.pages[page].next = next;
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 2);
-        assert_eq!(list.get_size(HEAD), 1);
+    unsafe fn allocate_page(&mut self) -> usize {
+        match self.free.pop() {
+            Some(page) => {
+                self.pages[page] = Page::default();
+                page
+            }
+            None => {
+                self.pages.push(Page::default());
+                self.pages.len() - 1
+            }
+        }
     }
 
-    #[test]
-    fn test_attach_as_last_non_empty_list() {
-        let mut list = TestRelativeList::new(10);
+    unsafe fn free_page(&mut self, page: usize) {
+        self.free.push(page);
+    }
 
-        list.attach_as_last(HEAD, 2);
-        list.attach_as_last(HEAD, 3);
+    unsafe fn first_is_to_the_left_of_second(&self, first: usize, second: usize) -> bool {
+        first < second
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 3);
-        assert_eq!(list.get_size(HEAD), 2);
+    unsafe fn first_is_to_the_right_of_second(&self, first: usize, second: usize) -> bool {
+        first > second
     }
+}
 
-    #[test]
-    fn test_attach_before() {
-        let mut list = TestRelativeList::new(10);
+/// A crit-bit trie node store for testing, reusing freed nodes
+struct TestCritBit {
+    nodes: Vec<(u32, [CritBitChild<usize>; 2])>,
+    free: Vec<usize>,
+}
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_as_last(HEAD, 3);
-        list.attach_before(HEAD, 3, 4);
+impl TestCritBit {
+    fn new() -> Self {
+        Self {
+            nodes: vec![(0, [CritBitChild::Empty; 2])],
+            free: Vec::new(),
+        }
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 3);
-        assert_eq!(list.get_size(HEAD), 3);
-        assert_eq!(list.get_next(2), 4);
-        assert_eq!(list.get_next(4), 3);
+    fn live_nodes(&self) -> usize {
+        self.nodes.len() - 1 - self.free.len()
     }
+}
 
-    #[test]
-    fn test_attach_before_first() {
-        let mut list = TestRelativeList::new(10);
+impl CritBitTrie<usize> for TestCritBit {
+    fn get_child(&self, node: usize, right: bool) -> CritBitChild<usize> {
+        self.nodes[node].1[right as usize]
+    }
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_before(HEAD, 2, 3);
+    fn set_child(&mut self, node: usize, right: bool, child: CritBitChild<usize>) {
+        self.nodes[node].1[right as usize] = child;
+    }
 
-        assert_eq!(list.get_first(HEAD), 3);
-        assert_eq!(list.get_last(HEAD), 2);
-        assert_eq!(list.get_size(HEAD), 2);
+    fn get_crit_bit(&self, node: usize) -> u32 {
+        self.nodes[node].0
     }
 
-    #[test]
-    fn test_attach_after() {
-        let mut list = TestRelativeList::new(10);
+    fn set_crit_bit(&mut self, node: usize, bit: u32) {
+        self.nodes[node].0 = bit;
+    }
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_as_last(HEAD, 3);
-        list.attach_after(HEAD, 2, 4);
+    fn allocate_node(&mut self) -> usize {
+        self.free.pop().unwrap_or_else(|| {
+            self.nodes.push((0, [CritBitChild::Empty; 2]));
+            self.nodes.len() - 1
+        })
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 3);
-        assert_eq!(list.get_size(HEAD), 3);
-        assert_eq!(list.get_next(2), 4);
-        assert_eq!(list.get_next(4), 3);
+    fn free_node(&mut self, node: usize) {
+        self.nodes[node] = (0, [CritBitChild::Empty; 2]);
+        self.free.push(node);
     }
+}
 
-    #[test]
-    fn test_attach_after_last() {
-        let mut list = TestRelativeList::new(10);
+/// A scapegoat tree implementation for testing, with links only
+struct TestScapegoatTree {
+    nodes: Vec<(usize, usize)>,
+}
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_after(HEAD, 2, 3);
+impl TestScapegoatTree {
+    fn new(capacity: usize) -> Self {
+        Self {
+            nodes: vec![(0, 0); capacity + 1],
+        }
+    }
+}
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 3);
-        assert_eq!(list.get_size(HEAD), 2);
+impl ScapegoatTree<usize> for TestScapegoatTree {
+    unsafe fn get_left(&self, node: usize) -> usize {
+        self.nodes[node].0
     }
 
-    #[test]
-    fn test_detach_single_element() {
-        let mut list = TestRelativeList::new(10);
+    unsafe fn get_right(&self, node: usize) -> usize {
+        self.nodes[node].1
+    }
 
-        list.attach_as_first(HEAD, 2);
-        list.detach(HEAD, 2);
+    unsafe fn set_left(&mut self, node: usize, left: usize) {
+        self.nodes[node].0 = left;
+    }
 
-        assert_eq!(list.get_first(HEAD), 0);
-        assert_eq!(list.get_last(HEAD), 0);
-        assert_eq!(list.get_size(HEAD), 0);
-        assert_eq!(list.get_previous(2), 0);
-        assert_eq!(list.get_next(2), 0);
+    unsafe fn set_right(&mut self, node: usize, right: usize) {
+        self.nodes[node].1 = right;
     }
 
-    #[test]
-    fn test_detach_first_element() {
-        let mut list = TestRelativeList::new(10);
+    unsafe fn first_is_to_the_left_of_second(&self, first: usize, second: usize) -> bool {
+        first < second
+    }
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_as_last(HEAD, 3);
-        list.attach_as_last(HEAD, 4);
+    unsafe fn first_is_to_the_right_of_second(&self, first: usize, second: usize) -> bool {
+        first > second
+    }
+}
 
-        list.detach(HEAD, 2);
+impl RedBlackTree<usize> for TestRedBlackTree {
+    unsafe fn is_red(&self, node: usize) -> bool {
+        self.nodes[node].size & RED_BIT != 0
+    }
 
-        assert_eq!(list.get_first(HEAD), 3);
-        assert_eq!(list.get_last(HEAD), 4);
-        assert_eq!(list.get_size(HEAD), 2);
+    unsafe fn set_red(&mut self, node: usize, red: bool) {
+        if red {
+            self.nodes[node].size |= RED_BIT;
+        } else {
+            self.nodes[node].size &= !RED_BIT;
+        }
     }
+}
 
-    #[test]
-    fn test_detach_last_element() {
-        let mut list = TestRelativeList::new(10);
+/// A node allocator growing its node storage on demand
+struct TestAllocator {
+    nodes: Vec<Node>,
+    first: usize,
+    last: usize,
+    unused: usize,
+}
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_as_last(HEAD, 3);
-        list.attach_as_last(HEAD, 4);
+impl TestAllocator {
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+            first: 0,
+            last: 0,
+            unused: 0,
+        }
+    }
+}
 
-        list.detach(HEAD, 4);
+impl LinkedList<usize> for TestAllocator {
+    fn get_previous(&self, element: usize) -> usize {
+        self.nodes[element].prev
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 3);
-        assert_eq!(list.get_size(HEAD), 2);
+    fn get_next(&self, element: usize) -> usize {
+        self.nodes[element].next
     }
 
-    #[test]
-    fn test_detach_middle_element() {
-        let mut list = TestRelativeList::new(10);
+    fn set_previous(&mut self, element: usize, previous: usize) {
+        self.nodes[element].prev = previous;
+    }
 
-        list.attach_as_first(HEAD, 2);
-        list.attach_as_last(HEAD, 3);
-        list.attach_as_last(HEAD, 4);
+    fn set_next(&mut self, element: usize, next: usize) {
+        self.nodes[element].next = next;
+    }
+}
 
-        list.detach(HEAD, 3);
+impl AbsoluteLinkedList<usize> for TestAllocator {
+    fn get_first(&self) -> usize {
+        self.first
+    }
 
-        assert_eq!(list.get_first(HEAD), 2);
-        assert_eq!(list.get_last(HEAD), 4);
-        assert_eq!(list.get_size(HEAD), 2);
-        assert_eq!(list.get_next(2), 4);
-        assert_eq!(list.get_previous(4), 2);
+    fn get_last(&self) -> usize {
+        self.last
     }
 
-    #[test]
-    fn test_multiple_lists() {
-        let mut list = TestRelativeList::new(20);
+    fn get_size(&self) -> usize {
+        self.unused
+    }
 
-        const HEAD1: usize = 1;
-        const HEAD2: usize = 10;
+    fn set_first(&mut self, element: usize) {
+        self.first = element;
+    }
 
-        list.attach_as_first(HEAD1, 2);
-        list.attach_as_last(HEAD1, 3);
+    fn set_last(&mut self, element: usize) {
+        self.last = element;
+    }
 
-        list.attach_as_first(HEAD2, 11);
-        list.attach_as_last(HEAD2, 12);
+    fn set_size(&mut self, size: usize) {
+        self.unused = size;
+    }
+}
 
-        assert_eq!(list.get_first(HEAD1), 2);
-        assert_eq!(list.get_last(HEAD1), 3);
-        assert_eq!(list.get_size(HEAD1), 2);
+impl AbsoluteCircularLinkedList<usize> for TestAllocator {}
 
-        assert_eq!(list.get_first(HEAD2), 11);
-        assert_eq!(list.get_last(HEAD2), 12);
-        assert_eq!(list.get_size(HEAD2), 2);
+impl NodeAllocator<usize> for TestAllocator {
+    fn get_allocated(&self) -> usize {
+        self.nodes.len() - 1
     }
-}
 
-// =============================================================================
-// SizeBalancedTree trait tests
-// =============================================================================
+    fn set_allocated(&mut self, allocated: usize) {
+        self.nodes.resize(allocated + 1, Node::default());
+    }
+}
 
-#[cfg(test)]
-mod size_balanced_tree_tests {
-    use super::*;
+/// A doublets links storage with sources and targets index trees
+struct TestLinks {
+    links: Vec<(usize, usize)>,
+    sources: Vec<IndexNode<usize>>,
+    targets: Vec<IndexNode<usize>>,
+    sources_root: usize,
+    targets_root: usize,
+}
 
-    #[test]
-    fn test_get_set_left() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_left(1, 2);
-            assert_eq!(tree.get_left(1), 2);
+impl TestLinks {
+    fn new() -> Self {
+        Self {
+            links: vec![(0, 0)],
+            sources: vec![IndexNode::default()],
+            targets: vec![IndexNode::default()],
+            sources_root: 0,
+            targets_root: 0,
         }
     }
 
-    #[test]
-    fn test_get_set_right() {
-        let mut tree = TestTree::new(10);
+    fn create(&mut self, source: usize, target: usize) -> usize {
+        self.links.push((source, target));
+        self.sources.push(IndexNode::default());
+        self.targets.push(IndexNode::default());
+        let link = self.links.len() - 1;
         unsafe {
-            tree.set_right(1, 3);
-            assert_eq!(tree.get_right(1), 3);
+            self.attach_link(link);
         }
+        link
     }
+}
 
-    #[test]
-    fn test_get_set_size() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_size(1, 5);
-            assert_eq!(tree.get_size(1), 5);
-        }
+impl DoubletsIndex<usize> for TestLinks {
+    fn get_source(&self, link: usize) -> usize {
+        self.links[link].0
     }
 
-    #[test]
-    fn test_get_left_or_default_with_zero() {
-        let tree = TestTree::new(10);
-        unsafe {
-            assert_eq!(tree.get_left_or_default(0), 0);
-        }
+    fn get_target(&self, link: usize) -> usize {
+        self.links[link].1
     }
 
-    #[test]
-    fn test_get_left_or_default_with_node() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_left(1, 2);
-            assert_eq!(tree.get_left_or_default(1), 2);
+    fn get_index_node(&self, tree: LinksTree, link: usize) -> &IndexNode<usize> {
+        match tree {
+            LinksTree::Sources => &self.sources[link],
+            LinksTree::Targets => &self.targets[link],
         }
     }
 
-    #[test]
-    fn test_get_right_or_default_with_zero() {
-        let tree = TestTree::new(10);
-        unsafe {
-            assert_eq!(tree.get_right_or_default(0), 0);
+    fn get_mut_index_node(&mut self, tree: LinksTree, link: usize) -> &mut IndexNode<usize> {
+        match tree {
+            LinksTree::Sources => &mut self.sources[link],
+            LinksTree::Targets => &mut self.targets[link],
         }
     }
 
-    #[test]
-    fn test_get_right_or_default_with_node() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_right(1, 3);
-            assert_eq!(tree.get_right_or_default(1), 3);
+    fn get_index_root(&self, tree: LinksTree) -> usize {
+        match tree {
+            LinksTree::Sources => self.sources_root,
+            LinksTree::Targets => self.targets_root,
         }
     }
 
-    #[test]
-    fn test_get_size_or_zero_with_zero() {
-        let tree = TestTree::new(10);
-        unsafe {
-            assert_eq!(tree.get_size_or_zero(0), 0);
+    fn set_index_root(&mut self, tree: LinksTree, root: usize) {
+        match tree {
+            LinksTree::Sources => self.sources_root = root,
+            LinksTree::Targets => self.targets_root = root,
         }
     }
+}
+
+// =============================================================================
+// LinkType trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod link_type_tests {
+    use super::*;
 
     #[test]
-    fn test_get_size_or_zero_with_node() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_size(1, 5);
-            assert_eq!(tree.get_size_or_zero(1), 5);
-        }
+    fn test_funty_zero() {
+        assert_eq!(usize::funty(0), 0usize);
+        assert_eq!(u8::funty(0), 0u8);
+        assert_eq!(u16::funty(0), 0u16);
+        assert_eq!(u32::funty(0), 0u32);
+        assert_eq!(u64::funty(0), 0u64);
     }
 
     #[test]
-    fn test_inc_size() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_size(1, 5);
-            tree.inc_size(1);
-            assert_eq!(tree.get_size(1), 6);
-        }
+    fn test_funty_one() {
+        assert_eq!(usize::funty(1), 1usize);
+        assert_eq!(u8::funty(1), 1u8);
+        assert_eq!(u16::funty(1), 1u16);
+        assert_eq!(u32::funty(1), 1u32);
+        assert_eq!(u64::funty(1), 1u64);
     }
 
     #[test]
-    fn test_dec_size() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_size(1, 5);
-            tree.dec_size(1);
-            assert_eq!(tree.get_size(1), 4);
+    fn test_funty_various_values() {
+        for i in 0..=255u8 {
+            assert_eq!(u8::funty(i), i);
+            assert_eq!(u16::funty(i), i as u16);
+            assert_eq!(u32::funty(i), i as u32);
+            assert_eq!(u64::funty(i), i as u64);
+            assert_eq!(usize::funty(i), i as usize);
         }
     }
 
     #[test]
-    fn test_get_left_size() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_left(1, 2);
-            tree.set_size(2, 3);
-            assert_eq!(tree.get_left_size(1), 3);
+    fn test_link_type_generic_arithmetic() {
+        fn sum_to<T: LinkType>(n: u8) -> T {
+            (1..=n).fold(T::funty(0), |acc, i| acc + T::funty(i))
         }
+
+        assert_eq!(sum_to::<u8>(10), 55u8);
+        assert_eq!(sum_to::<u16>(10), 55u16);
+        assert_eq!(sum_to::<u32>(10), 55u32);
+        assert_eq!(sum_to::<u64>(10), 55u64);
+        assert_eq!(sum_to::<usize>(10), 55usize);
     }
+}
+
+// =============================================================================
+// LinkedList trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod linked_list_tests {
+    use super::*;
 
     #[test]
-    fn test_get_right_size() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_right(1, 3);
-            tree.set_size(3, 5);
-            assert_eq!(tree.get_right_size(1), 5);
-        }
+    fn test_get_set_previous() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_previous(1, 5);
+        assert_eq!(list.get_previous(1), 5);
     }
 
     #[test]
-    fn test_fix_size() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Create tree: 1 -> (2, 3)
-            tree.set_left(1, 2);
+    fn test_get_set_next() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_next(1, 3);
+        assert_eq!(list.get_next(1), 3);
+    }
+}
+
+// =============================================================================
+// AbsoluteLinkedList trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod absolute_linked_list_tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set_first() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_first(5);
+        assert_eq!(list.get_first(), 5);
+    }
+
+    #[test]
+    fn test_get_set_last() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_last(7);
+        assert_eq!(list.get_last(), 7);
+    }
+
+    #[test]
+    fn test_get_set_size() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_size(3);
+        assert_eq!(list.get_size(), 3);
+    }
+
+    #[test]
+    fn test_inc_size() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_size(5);
+        list.inc_size();
+        assert_eq!(list.get_size(), 6);
+    }
+
+    #[test]
+    fn test_dec_size() {
+        let mut list = TestAbsoluteList::new(10);
+        list.set_size(5);
+        list.dec_size();
+        assert_eq!(list.get_size(), 4);
+    }
+}
+
+// =============================================================================
+// AbsoluteCircularLinkedList trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod absolute_circular_linked_list_tests {
+    use super::*;
+
+    #[test]
+    fn test_attach_as_first_empty_list() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 1);
+        assert_eq!(list.get_size(), 1);
+        assert_eq!(list.get_previous(1), 1); // Points to itself
+        assert_eq!(list.get_next(1), 1); // Points to itself
+    }
+
+    #[test]
+    fn test_attach_as_first_non_empty_list() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_as_first(2);
+
+        assert_eq!(list.get_first(), 2);
+        assert_eq!(list.get_last(), 1);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_attach_as_last_empty_list() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_last(1);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 1);
+        assert_eq!(list.get_size(), 1);
+    }
+
+    #[test]
+    fn test_attach_as_last_non_empty_list() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_last(1);
+        list.attach_as_last(2);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 2);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_attach_before() {
+        let mut list = TestAbsoluteList::new(10);
+
+        // Create list: 1 <-> 2
+        list.attach_as_first(1);
+        list.attach_as_last(2);
+
+        // Insert 3 before 2: 1 <-> 3 <-> 2
+        list.attach_before(2, 3);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 2);
+        assert_eq!(list.get_size(), 3);
+        assert_eq!(list.get_next(1), 3);
+        assert_eq!(list.get_next(3), 2);
+        assert_eq!(list.get_previous(2), 3);
+    }
+
+    #[test]
+    fn test_attach_before_first() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_before(1, 2);
+
+        assert_eq!(list.get_first(), 2);
+        assert_eq!(list.get_last(), 1);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_attach_after() {
+        let mut list = TestAbsoluteList::new(10);
+
+        // Create list: 1 <-> 2
+        list.attach_as_first(1);
+        list.attach_as_last(2);
+
+        // Insert 3 after 1: 1 <-> 3 <-> 2
+        list.attach_after(1, 3);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 2);
+        assert_eq!(list.get_size(), 3);
+        assert_eq!(list.get_next(1), 3);
+        assert_eq!(list.get_next(3), 2);
+    }
+
+    #[test]
+    fn test_attach_after_last() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_after(1, 2);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 2);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_detach_single_element() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.detach(1);
+
+        assert_eq!(list.get_first(), 0);
+        assert_eq!(list.get_last(), 0);
+        assert_eq!(list.get_size(), 0);
+        assert_eq!(list.get_previous(1), 0);
+        assert_eq!(list.get_next(1), 0);
+    }
+
+    #[test]
+    fn test_detach_first_element() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_as_last(2);
+        list.attach_as_last(3);
+
+        list.detach(1);
+
+        assert_eq!(list.get_first(), 2);
+        assert_eq!(list.get_last(), 3);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_detach_last_element() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_as_last(2);
+        list.attach_as_last(3);
+
+        list.detach(3);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 2);
+        assert_eq!(list.get_size(), 2);
+    }
+
+    #[test]
+    fn test_detach_middle_element() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_as_last(2);
+        list.attach_as_last(3);
+
+        list.detach(2);
+
+        assert_eq!(list.get_first(), 1);
+        assert_eq!(list.get_last(), 3);
+        assert_eq!(list.get_size(), 2);
+        assert_eq!(list.get_next(1), 3);
+        assert_eq!(list.get_previous(3), 1);
+    }
+
+    #[test]
+    fn test_circular_structure() {
+        let mut list = TestAbsoluteList::new(10);
+
+        list.attach_as_first(1);
+        list.attach_as_last(2);
+        list.attach_as_last(3);
+
+        // Check circular structure
+        assert_eq!(list.get_next(3), 1); // Last points to first
+        assert_eq!(list.get_previous(1), 3); // First points back to last
+    }
+}
+
+// =============================================================================
+// RelativeLinkedList trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod relative_linked_list_tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set_first() {
+        let mut list = TestRelativeList::new(10);
+        list.set_first(1, 5);
+        assert_eq!(list.get_first(1), 5);
+    }
+
+    #[test]
+    fn test_get_set_last() {
+        let mut list = TestRelativeList::new(10);
+        list.set_last(1, 7);
+        assert_eq!(list.get_last(1), 7);
+    }
+
+    #[test]
+    fn test_get_set_size() {
+        let mut list = TestRelativeList::new(10);
+        list.set_size(1, 3);
+        assert_eq!(list.get_size(1), 3);
+    }
+
+    #[test]
+    fn test_inc_size() {
+        let mut list = TestRelativeList::new(10);
+        list.set_size(1, 5);
+        list.inc_size(1);
+        assert_eq!(list.get_size(1), 6);
+    }
+
+    #[test]
+    fn test_dec_size() {
+        let mut list = TestRelativeList::new(10);
+        list.set_size(1, 5);
+        list.dec_size(1);
+        assert_eq!(list.get_size(1), 4);
+    }
+
+    #[test]
+    fn test_multiple_heads() {
+        let mut list = TestRelativeList::new(10);
+
+        list.set_first(1, 2);
+        list.set_first(2, 3);
+
+        assert_eq!(list.get_first(1), 2);
+        assert_eq!(list.get_first(2), 3);
+    }
+}
+
+// =============================================================================
+// RelativeCircularLinkedList trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod relative_circular_linked_list_tests {
+    use super::*;
+
+    const HEAD: usize = 1;
+
+    #[test]
+    fn test_attach_as_first_empty_list() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 2);
+        assert_eq!(list.get_size(HEAD), 1);
+        assert_eq!(list.get_previous(2), 2);
+        assert_eq!(list.get_next(2), 2);
+    }
+
+    #[test]
+    fn test_attach_as_first_non_empty_list() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_as_first(HEAD, 3);
+
+        assert_eq!(list.get_first(HEAD), 3);
+        assert_eq!(list.get_last(HEAD), 2);
+        assert_eq!(list.get_size(HEAD), 2);
+    }
+
+    #[test]
+    fn test_attach_as_last_empty_list() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_last(HEAD, 2);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 2);
+        assert_eq!(list.get_size(HEAD), 1);
+    }
+
+    #[test]
+    fn test_attach_as_last_non_empty_list() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_last(HEAD, 2);
+        list.attach_as_last(HEAD, 3);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 3);
+        assert_eq!(list.get_size(HEAD), 2);
+    }
+
+    #[test]
+    fn test_attach_before() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_as_last(HEAD, 3);
+        list.attach_before(HEAD, 3, 4);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 3);
+        assert_eq!(list.get_size(HEAD), 3);
+        assert_eq!(list.get_next(2), 4);
+        assert_eq!(list.get_next(4), 3);
+    }
+
+    #[test]
+    fn test_attach_before_first() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_before(HEAD, 2, 3);
+
+        assert_eq!(list.get_first(HEAD), 3);
+        assert_eq!(list.get_last(HEAD), 2);
+        assert_eq!(list.get_size(HEAD), 2);
+    }
+
+    #[test]
+    fn test_attach_after() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_as_last(HEAD, 3);
+        list.attach_after(HEAD, 2, 4);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 3);
+        assert_eq!(list.get_size(HEAD), 3);
+        assert_eq!(list.get_next(2), 4);
+        assert_eq!(list.get_next(4), 3);
+    }
+
+    #[test]
+    fn test_attach_after_last() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_after(HEAD, 2, 3);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 3);
+        assert_eq!(list.get_size(HEAD), 2);
+    }
+
+    #[test]
+    fn test_detach_single_element() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.detach(HEAD, 2);
+
+        assert_eq!(list.get_first(HEAD), 0);
+        assert_eq!(list.get_last(HEAD), 0);
+        assert_eq!(list.get_size(HEAD), 0);
+        assert_eq!(list.get_previous(2), 0);
+        assert_eq!(list.get_next(2), 0);
+    }
+
+    #[test]
+    fn test_detach_first_element() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_as_last(HEAD, 3);
+        list.attach_as_last(HEAD, 4);
+
+        list.detach(HEAD, 2);
+
+        assert_eq!(list.get_first(HEAD), 3);
+        assert_eq!(list.get_last(HEAD), 4);
+        assert_eq!(list.get_size(HEAD), 2);
+    }
+
+    #[test]
+    fn test_detach_last_element() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_as_last(HEAD, 3);
+        list.attach_as_last(HEAD, 4);
+
+        list.detach(HEAD, 4);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 3);
+        assert_eq!(list.get_size(HEAD), 2);
+    }
+
+    #[test]
+    fn test_detach_middle_element() {
+        let mut list = TestRelativeList::new(10);
+
+        list.attach_as_first(HEAD, 2);
+        list.attach_as_last(HEAD, 3);
+        list.attach_as_last(HEAD, 4);
+
+        list.detach(HEAD, 3);
+
+        assert_eq!(list.get_first(HEAD), 2);
+        assert_eq!(list.get_last(HEAD), 4);
+        assert_eq!(list.get_size(HEAD), 2);
+        assert_eq!(list.get_next(2), 4);
+        assert_eq!(list.get_previous(4), 2);
+    }
+
+    #[test]
+    fn test_multiple_lists() {
+        let mut list = TestRelativeList::new(20);
+
+        const HEAD1: usize = 1;
+        const HEAD2: usize = 10;
+
+        list.attach_as_first(HEAD1, 2);
+        list.attach_as_last(HEAD1, 3);
+
+        list.attach_as_first(HEAD2, 11);
+        list.attach_as_last(HEAD2, 12);
+
+        assert_eq!(list.get_first(HEAD1), 2);
+        assert_eq!(list.get_last(HEAD1), 3);
+        assert_eq!(list.get_size(HEAD1), 2);
+
+        assert_eq!(list.get_first(HEAD2), 11);
+        assert_eq!(list.get_last(HEAD2), 12);
+        assert_eq!(list.get_size(HEAD2), 2);
+    }
+}
+
+// =============================================================================
+// SkipList trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod skip_list_tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    /// Keys of nodes `1..=count` (node 0 is unused), scrambled
+    fn scrambled_keys(count: usize) -> Vec<i64> {
+        (0..=count)
+            .map(|node| ((node * 37) % 101) as i64 * 10)
+            .collect()
+    }
+
+    fn build(count: usize) -> TestSkipList {
+        let mut list = TestSkipList::new(scrambled_keys(count));
+        for node in 1..=count {
+            list.insert(node, |list, first, second| list.compare(first, second));
+        }
+        list
+    }
+
+    /// Collects the nodes of a level in list order
+    fn level_nodes(list: &TestSkipList, level: usize) -> Vec<usize> {
+        let mut nodes = Vec::new();
+        let mut entry = list.get_level_next(level, 0);
+        while entry != 0 {
+            nodes.push(list.get_entry_node(entry));
+            entry = list.get_level_next(level, entry);
+        }
+        nodes
+    }
+
+    /// Checks every level is sorted and a subset of the level below it
+    fn check(list: &TestSkipList) {
+        for level in 0..SKIP_LEVELS {
+            let nodes = level_nodes(list, level);
+            assert_eq!(nodes.len(), list.get_size(list.get_level_head(level)));
+            for pair in nodes.windows(2) {
+                assert!(list.keys[pair[0]] <= list.keys[pair[1]], "level {}", level);
+            }
+            for &node in &nodes {
+                assert!(list.get_node_level(node) > level, "node {}", node);
+            }
+        }
+    }
+
+    #[test]
+    fn test_node_levels_are_geometric() {
+        let list = TestSkipList::new(Vec::new());
+        let levels: Vec<usize> = (1..=4096).map(|node| list.get_node_level(node)).collect();
+        let promoted = levels.iter().filter(|&&level| level > 1).count();
+        assert!((1800..2300).contains(&promoted), "{} promoted", promoted);
+        assert!(levels
+            .iter()
+            .all(|&level| (1..=SKIP_LEVELS).contains(&level)));
+        assert_eq!(list.get_node_level(17), list.get_node_level(17));
+    }
+
+    #[test]
+    fn test_insert_keeps_levels_ordered() {
+        let list = build(100);
+
+        check(&list);
+        assert_eq!(list.get_count(), 100);
+        let bottom = level_nodes(&list, 0);
+        let mut expected: Vec<usize> = (1..=100).collect();
+        expected.sort_by_key(|&node| list.keys[node]);
+        assert_eq!(bottom, expected);
+    }
+
+    #[test]
+    fn test_search_by_key() {
+        let list = build(100);
+
+        for node in 1..=100 {
+            let key = list.keys[node];
+            assert_eq!(list.search(|list, other| list.keys[other].cmp(&key)), node);
+        }
+        assert_eq!(list.search(|list, other| list.keys[other].cmp(&15)), 0);
+        assert_eq!(list.search(|list, other| list.keys[other].cmp(&-1)), 0);
+        assert_eq!(list.search(|list, other| list.keys[other].cmp(&5000)), 0);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut list = build(100);
+
+        for node in (1..=100).step_by(2) {
+            list.delete(node);
+            let key = list.keys[node];
+            assert_eq!(list.search(|list, other| list.keys[other].cmp(&key)), 0);
+        }
+        check(&list);
+        assert_eq!(list.get_count(), 50);
+        for node in (2..=100).step_by(2) {
+            let key = list.keys[node];
+            assert_eq!(list.search(|list, other| list.keys[other].cmp(&key)), node);
+        }
+        for node in (2..=100).step_by(2) {
+            list.delete(node);
+        }
+        for level in 0..SKIP_LEVELS {
+            assert_eq!(list.get_first(list.get_level_head(level)), 0);
+        }
+    }
+
+    #[test]
+    fn test_range_iteration() {
+        let list = build(100);
+        let mut keys = Vec::new();
+
+        let flow = list.each_in_range(
+            |list, node| {
+                let key = list.keys[node];
+                if key < 95 {
+                    Ordering::Less
+                } else if key > 150 {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            },
+            |node| {
+                keys.push(list.keys[node]);
+                Flow::Continue
+            },
+        );
+        assert_eq!(flow, Flow::Continue);
+        assert_eq!(keys, vec![100, 110, 120, 130, 140, 150]);
+
+        let mut visited = 0;
+        let flow = list.each_in_range(
+            |_, _| Ordering::Equal,
+            |_| {
+                visited += 1;
+                if visited == 3 {
+                    Flow::Break
+                } else {
+                    Flow::Continue
+                }
+            },
+        );
+        assert_eq!(flow, Flow::Break);
+        assert_eq!(visited, 3);
+    }
+}
+
+// =============================================================================
+// SizeBalancedTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod size_balanced_tree_tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set_left() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_left(1, 2);
+            assert_eq!(tree.get_left(1), 2);
+        }
+    }
+
+    #[test]
+    fn test_get_set_right() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_right(1, 3);
+            assert_eq!(tree.get_right(1), 3);
+        }
+    }
+
+    #[test]
+    fn test_get_set_size() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_size(1, 5);
+            assert_eq!(tree.get_size(1), 5);
+        }
+    }
+
+    #[test]
+    fn test_get_left_or_default_with_zero() {
+        let tree = TestTree::new(10);
+        unsafe {
+            assert_eq!(tree.get_left_or_default(0), 0);
+        }
+    }
+
+    #[test]
+    fn test_get_left_or_default_with_node() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_left(1, 2);
+            assert_eq!(tree.get_left_or_default(1), 2);
+        }
+    }
+
+    #[test]
+    fn test_get_right_or_default_with_zero() {
+        let tree = TestTree::new(10);
+        unsafe {
+            assert_eq!(tree.get_right_or_default(0), 0);
+        }
+    }
+
+    #[test]
+    fn test_get_right_or_default_with_node() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_right(1, 3);
+            assert_eq!(tree.get_right_or_default(1), 3);
+        }
+    }
+
+    #[test]
+    fn test_get_size_or_zero_with_zero() {
+        let tree = TestTree::new(10);
+        unsafe {
+            assert_eq!(tree.get_size_or_zero(0), 0);
+        }
+    }
+
+    #[test]
+    fn test_get_size_or_zero_with_node() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_size(1, 5);
+            assert_eq!(tree.get_size_or_zero(1), 5);
+        }
+    }
+
+    #[test]
+    fn test_inc_size() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_size(1, 5);
+            tree.inc_size(1);
+            assert_eq!(tree.get_size(1), 6);
+        }
+    }
+
+    #[test]
+    fn test_dec_size() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_size(1, 5);
+            tree.dec_size(1);
+            assert_eq!(tree.get_size(1), 4);
+        }
+    }
+
+    #[test]
+    fn test_get_left_size() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_left(1, 2);
+            tree.set_size(2, 3);
+            assert_eq!(tree.get_left_size(1), 3);
+        }
+    }
+
+    #[test]
+    fn test_get_right_size() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_right(1, 3);
+            tree.set_size(3, 5);
+            assert_eq!(tree.get_right_size(1), 5);
+        }
+    }
+
+    #[test]
+    fn test_fix_size() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Create tree: 1 -> (2, 3)
+            tree.set_left(1, 2);
+            tree.set_right(1, 3);
+            tree.set_size(2, 1);
+            tree.set_size(3, 2);
+
+            tree.fix_size(1);
+            assert_eq!(tree.get_size(1), 4); // 1 + 2 + 1
+        }
+    }
+
+    #[test]
+    fn test_left_rotate_core() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Initial: 1 -> (_, 2) where 2 -> (3, _)
+            tree.set_right(1, 2);
+            tree.set_left(2, 3);
+            tree.set_size(1, 3);
+            tree.set_size(2, 2);
+            tree.set_size(3, 1);
+
+            let new_root = tree.left_rotate_core(1);
+
+            assert_eq!(new_root, 2);
+            assert_eq!(tree.get_left(2), 1);
+            assert_eq!(tree.get_right(1), 3);
+            assert_eq!(tree.get_size(2), 3);
+        }
+    }
+
+    #[test]
+    fn test_left_rotate() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_right(1, 2);
+            tree.set_left(2, 3);
+            tree.set_size(1, 3);
+            tree.set_size(2, 2);
+            tree.set_size(3, 1);
+
+            let mut root: usize = 1;
+            tree.left_rotate(&mut root);
+
+            assert_eq!(root, 2);
+        }
+    }
+
+    #[test]
+    fn test_right_rotate_core() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Initial: 1 -> (2, _) where 2 -> (_, 3)
+            tree.set_left(1, 2);
+            tree.set_right(2, 3);
+            tree.set_size(1, 3);
+            tree.set_size(2, 2);
+            tree.set_size(3, 1);
+
+            let new_root = tree.right_rotate_core(1);
+
+            assert_eq!(new_root, 2);
+            assert_eq!(tree.get_right(2), 1);
+            assert_eq!(tree.get_left(1), 3);
+            assert_eq!(tree.get_size(2), 3);
+        }
+    }
+
+    #[test]
+    fn test_right_rotate() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_left(1, 2);
+            tree.set_right(2, 3);
+            tree.set_size(1, 3);
+            tree.set_size(2, 2);
+            tree.set_size(3, 1);
+
+            let mut root: usize = 1;
+            tree.right_rotate(&mut root);
+
+            assert_eq!(root, 2);
+        }
+    }
+
+    #[test]
+    fn test_get_rightest() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Create tree: 1 -> (_, 2) where 2 -> (_, 3)
+            tree.set_right(1, 2);
+            tree.set_right(2, 3);
+
+            assert_eq!(tree.get_rightest(1), 3);
+        }
+    }
+
+    #[test]
+    fn test_get_leftest() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Create tree: 3 -> (2, _) where 2 -> (1, _)
+            tree.set_left(3, 2);
+            tree.set_left(2, 1);
+
+            assert_eq!(tree.get_leftest(3), 1);
+        }
+    }
+
+    #[test]
+    fn test_get_next() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Node 1 has right child 2 which has left child 3
+            tree.set_right(1, 2);
+            tree.set_left(2, 3);
+
+            assert_eq!(tree.get_next(1), 3);
+        }
+    }
+
+    #[test]
+    fn test_get_previous() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Node 3 has left child 2 which has right child 1
+            tree.set_left(3, 2);
+            tree.set_right(2, 1);
+
+            assert_eq!(tree.get_previous(3), 1);
+        }
+    }
+
+    #[test]
+    fn test_contains_found() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Create BST: 5 -> (3, 7)
+            tree.set_left(5, 3);
+            tree.set_right(5, 7);
+
+            assert!(tree.contains(5, 5));
+            assert!(tree.contains(3, 5));
+            assert!(tree.contains(7, 5));
+        }
+    }
+
+    #[test]
+    fn test_contains_not_found() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            // Create BST: 5 -> (3, 7)
+            tree.set_left(5, 3);
+            tree.set_right(5, 7);
+
+            assert!(!tree.contains(2, 5));
+            assert!(!tree.contains(4, 5));
+            assert!(!tree.contains(8, 5));
+        }
+    }
+
+    #[test]
+    fn test_contains_empty_tree() {
+        let tree = TestTree::new(10);
+        unsafe {
+            assert!(!tree.contains(1, 0));
+        }
+    }
+
+    #[test]
+    fn test_clear_node() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_left(1, 2);
             tree.set_right(1, 3);
-            tree.set_size(2, 1);
-            tree.set_size(3, 2);
+            tree.set_size(1, 5);
+
+            tree.clear_node(1);
+
+            assert_eq!(tree.get_left(1), 0);
+            assert_eq!(tree.get_right(1), 0);
+            assert_eq!(tree.get_size(1), 0);
+        }
+    }
+
+    #[test]
+    fn test_first_is_to_the_left_of_second() {
+        let tree = TestTree::new(10);
+        unsafe {
+            assert!(tree.first_is_to_the_left_of_second(1, 2));
+            assert!(!tree.first_is_to_the_left_of_second(2, 1));
+            assert!(!tree.first_is_to_the_left_of_second(1, 1));
+        }
+    }
+
+    #[test]
+    fn test_first_is_to_the_right_of_second() {
+        let tree = TestTree::new(10);
+        unsafe {
+            assert!(tree.first_is_to_the_right_of_second(2, 1));
+            assert!(!tree.first_is_to_the_right_of_second(1, 2));
+            assert!(!tree.first_is_to_the_right_of_second(1, 1));
+        }
+    }
+
+    #[test]
+    fn test_get_mut_left_reference() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            let ptr = tree.get_mut_left_reference(1);
+            *ptr = 5;
+            assert_eq!(tree.get_left(1), 5);
+        }
+    }
+
+    #[test]
+    fn test_get_mut_right_reference() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            let ptr = tree.get_mut_right_reference(1);
+            *ptr = 7;
+            assert_eq!(tree.get_right(1), 7);
+        }
+    }
+
+    #[test]
+    fn test_get_left_reference() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_left(1, 3);
+            let ptr = tree.get_left_reference(1);
+            assert_eq!(*ptr, 3);
+        }
+    }
+
+    #[test]
+    fn test_get_right_reference() {
+        let mut tree = TestTree::new(10);
+        unsafe {
+            tree.set_right(1, 4);
+            let ptr = tree.get_right_reference(1);
+            assert_eq!(*ptr, 4);
+        }
+    }
+}
+
+// =============================================================================
+// IterativeSizeBalancedTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod iterative_size_balanced_tree_tests {
+    use super::*;
+
+    #[test]
+    fn test_attach_to_empty_tree() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 5);
+        }
+
+        assert_eq!(root, 5);
+        unsafe {
+            assert_eq!(tree.get_size(5), 1);
+        }
+    }
+
+    #[test]
+    fn test_attach_single_node_to_left() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 3);
+        }
+
+        unsafe {
+            assert_eq!(tree.get_left(5), 3);
+            assert_eq!(tree.get_size(5), 2);
+            assert_eq!(tree.get_size(3), 1);
+        }
+    }
+
+    #[test]
+    fn test_attach_single_node_to_right() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 7);
+        }
+
+        unsafe {
+            assert_eq!(tree.get_right(5), 7);
+            assert_eq!(tree.get_size(5), 2);
+            assert_eq!(tree.get_size(7), 1);
+        }
+    }
+
+    #[test]
+    fn test_attach_multiple_nodes() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+            tree.attach(&mut root, 3);
+            tree.attach(&mut root, 7);
+        }
+
+        unsafe {
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(5, root));
+            assert!(tree.contains(15, root));
+            assert!(tree.contains(3, root));
+            assert!(tree.contains(7, root));
+            assert_eq!(tree.get_size(root), 5);
+        }
+    }
+
+    #[test]
+    fn test_detach_from_single_node_tree() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 5);
+            tree.detach(&mut root, 5);
+        }
+
+        assert_eq!(root, 0);
+    }
+
+    #[test]
+    fn test_detach_leaf_node() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+
+            tree.detach(&mut root, 5);
+
+            assert!(!tree.contains(5, root));
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(15, root));
+        }
+    }
+
+    #[test]
+    fn test_detach_node_with_one_child() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 3);
+
+            tree.detach(&mut root, 5);
+
+            assert!(!tree.contains(5, root));
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(3, root));
+        }
+    }
+
+    #[test]
+    fn test_detach_node_with_two_children() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+            tree.attach(&mut root, 3);
+            tree.attach(&mut root, 7);
+
+            tree.detach(&mut root, 5);
+
+            assert!(!tree.contains(5, root));
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(15, root));
+            assert!(tree.contains(3, root));
+            assert!(tree.contains(7, root));
+        }
+    }
+
+    #[test]
+    fn test_detach_root() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+
+            tree.detach(&mut root, 10);
+
+            assert!(!tree.contains(10, root));
+            assert!(tree.contains(5, root));
+            assert!(tree.contains(15, root));
+            assert_ne!(root, 0);
+        }
+    }
+
+    #[test]
+    fn test_attach_and_detach_sequence() {
+        let mut tree = TestTree::new(30);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build tree
+            for i in [10, 5, 15, 3, 7, 12, 17] {
+                tree.attach(&mut root, i);
+            }
+
+            // Verify all nodes present
+            for i in [10, 5, 15, 3, 7, 12, 17] {
+                assert!(tree.contains(i, root), "Node {} should be present", i);
+            }
+
+            // Remove some nodes
+            tree.detach(&mut root, 3);
+            tree.detach(&mut root, 17);
+            tree.detach(&mut root, 10);
+
+            // Verify correct nodes present
+            assert!(!tree.contains(3, root));
+            assert!(!tree.contains(17, root));
+            assert!(!tree.contains(10, root));
+            assert!(tree.contains(5, root));
+            assert!(tree.contains(15, root));
+            assert!(tree.contains(7, root));
+            assert!(tree.contains(12, root));
+        }
+    }
+
+    #[test]
+    fn test_tree_balancing() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Insert in order that would cause imbalance without rotations
+            for i in 1..=10 {
+                tree.attach(&mut root, i);
+            }
+
+            // All nodes should be reachable
+            for i in 1..=10 {
+                assert!(tree.contains(i, root), "Node {} should be present", i);
+            }
+
+            assert_eq!(tree.get_size(root), 10);
+        }
+    }
+
+    #[test]
+    fn test_detach_all_nodes() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            for i in 1..=5 {
+                tree.attach(&mut root, i);
+            }
+
+            for i in 1..=5 {
+                tree.detach(&mut root, i);
+            }
+
+            assert_eq!(root, 0);
+        }
+    }
+
+    // Additional edge case tests for full coverage
+
+    #[test]
+    fn test_attach_left_right_rotation_edge_case() {
+        // Tests the path where we need a left-right double rotation on left side
+        let mut tree = TestTree::new(30);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build a tree that will trigger left-right rotation
+            // Start with 10, then 5, then insert 8 (between 5 and 10)
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 8); // This goes right of 5, should trigger LR rotation
+
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(5, root));
+            assert!(tree.contains(8, root));
+            assert_eq!(tree.get_size(root), 3);
+        }
+    }
+
+    #[test]
+    fn test_attach_descend_left_without_rotation() {
+        // Tests the case where we descend left without rotation
+        let mut tree = TestTree::new(40);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build a more balanced tree first
+            tree.attach(&mut root, 20);
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 30);
+            tree.attach(&mut root, 25);
+            tree.attach(&mut root, 35);
+            // Now insert something that needs to go left but doesn't trigger rotation
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 2);
+
+            assert!(tree.contains(2, root));
+            assert_eq!(tree.get_size(root), 7);
+        }
+    }
+
+    #[test]
+    fn test_attach_special_case_left_right_empty() {
+        // Tests the special case in attach_core where left_right_size == 0 and right_size == 0
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Create specific tree structure
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            // Now insert 7 which is right of 5 but left of 10
+            // With just 2 nodes, this triggers the special case
+            tree.attach(&mut root, 7);
+
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(5, root));
+            assert!(tree.contains(7, root));
+        }
+    }
+
+    #[test]
+    fn test_attach_right_left_rotation_edge_case() {
+        // Tests the path where we need a right-left double rotation on right side
+        let mut tree = TestTree::new(30);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build a tree that will trigger right-left rotation
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 20);
+            tree.attach(&mut root, 15); // Between 10 and 20, should trigger RL rotation
+
+            assert!(tree.contains(10, root));
+            assert!(tree.contains(15, root));
+            assert!(tree.contains(20, root));
+            assert_eq!(tree.get_size(root), 3);
+        }
+    }
+
+    #[test]
+    fn test_attach_descend_right_without_rotation() {
+        // Tests the case where we descend right without rotation
+        let mut tree = TestTree::new(40);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build a balanced tree
+            tree.attach(&mut root, 20);
+            tree.attach(&mut root, 30);
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+            // Insert on the right side, descending
+            tree.attach(&mut root, 35);
+            tree.attach(&mut root, 40);
+
+            assert!(tree.contains(40, root));
+            assert_eq!(tree.get_size(root), 7);
+        }
+    }
+
+    #[test]
+    fn test_detach_with_double_rotation_left() {
+        // Tests detach causing double rotation on left side
+        let mut tree = TestTree::new(40);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build larger tree
+            for i in [20, 10, 30, 5, 15, 25, 35, 3, 8, 13, 17] {
+                tree.attach(&mut root, i);
+            }
+
+            // Detach to trigger specific paths
+            tree.detach(&mut root, 3);
+            tree.detach(&mut root, 5);
+            tree.detach(&mut root, 8);
+
+            assert!(!tree.contains(3, root));
+            assert!(!tree.contains(5, root));
+            assert!(!tree.contains(8, root));
+        }
+    }
+
+    #[test]
+    fn test_detach_with_double_rotation_right() {
+        // Tests detach causing double rotation on right side
+        let mut tree = TestTree::new(50);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build larger tree
+            for i in [20, 10, 30, 5, 15, 25, 35, 32, 38, 28, 27] {
+                tree.attach(&mut root, i);
+            }
+
+            // Detach to trigger specific paths
+            tree.detach(&mut root, 35);
+            tree.detach(&mut root, 38);
+            tree.detach(&mut root, 32);
+
+            assert!(!tree.contains(35, root));
+            assert!(!tree.contains(38, root));
+            assert!(!tree.contains(32, root));
+        }
+    }
+
+    #[test]
+    fn test_detach_with_left_only_child() {
+        // Tests detach where replacement comes from left subtree
+        let mut tree = TestTree::new(30);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Create tree where left subtree is larger
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+            tree.attach(&mut root, 3);
+            tree.attach(&mut root, 7);
+            tree.attach(&mut root, 2);
+            tree.attach(&mut root, 4);
+
+            // Detach a node that will use replacement from left (larger) subtree
+            tree.detach(&mut root, 5);
+
+            assert!(!tree.contains(5, root));
+            assert!(tree.contains(3, root));
+            assert!(tree.contains(7, root));
+        }
+    }
+
+    #[test]
+    fn test_detach_node_with_left_child_only() {
+        // Tests the path where we set *root = *left
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 10);
+            tree.attach(&mut root, 5);
+            tree.attach(&mut root, 15);
+            tree.attach(&mut root, 3);
+
+            // Remove 5, which has only left child (3)
+            tree.detach(&mut root, 5);
+
+            assert!(!tree.contains(5, root));
+            assert!(tree.contains(3, root));
+        }
+    }
+
+    #[test]
+    fn test_large_sequential_insert() {
+        // Tests inserting many nodes in ascending order
+        let mut tree = TestTree::new(50);
+        let mut root: usize = 0;
+
+        unsafe {
+            for i in 1..=20 {
+                tree.attach(&mut root, i);
+            }
+
+            for i in 1..=20 {
+                assert!(tree.contains(i, root), "Node {} missing", i);
+            }
+            assert_eq!(tree.get_size(root), 20);
+        }
+    }
+
+    #[test]
+    fn test_large_reverse_insert() {
+        // Tests inserting many nodes in descending order
+        let mut tree = TestTree::new(50);
+        let mut root: usize = 0;
+
+        unsafe {
+            for i in (1..=20).rev() {
+                tree.attach(&mut root, i);
+            }
+
+            for i in 1..=20 {
+                assert!(tree.contains(i, root), "Node {} missing", i);
+            }
+            assert_eq!(tree.get_size(root), 20);
+        }
+    }
+
+    #[test]
+    fn test_alternating_insert() {
+        // Tests inserting nodes in alternating pattern
+        let mut tree = TestTree::new(50);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Insert 10, 1, 20, 2, 19, 3, 18, etc.
+            let mut low = 1;
+            let mut high = 20;
+            for i in 0..20 {
+                if i % 2 == 0 {
+                    tree.attach(&mut root, high);
+                    high -= 1;
+                } else {
+                    tree.attach(&mut root, low);
+                    low += 1;
+                }
+            }
+
+            for i in 1..=20 {
+                assert!(tree.contains(i, root), "Node {} missing", i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detach_from_large_tree() {
+        // Tests various detach patterns on large tree
+        let mut tree = TestTree::new(100);
+        let mut root: usize = 0;
+
+        unsafe {
+            // Build tree with 50 nodes
+            for i in 1..=50 {
+                tree.attach(&mut root, i);
+            }
+
+            // Detach every other node
+            for i in (1..=50).step_by(2) {
+                tree.detach(&mut root, i);
+            }
+
+            // Check remaining nodes
+            for i in (2..=50).step_by(2) {
+                assert!(tree.contains(i, root), "Node {} should be present", i);
+            }
+            for i in (1..=49).step_by(2) {
+                assert!(!tree.contains(i, root), "Node {} should be absent", i);
+            }
+        }
+    }
+}
+
+// =============================================================================
+// SlotSizeBalancedTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod slot_size_balanced_tree_tests {
+    use super::*;
+
+    /// Visits nodes in a scrambled but deterministic order
+    fn scrambled(count: usize) -> Vec<usize> {
+        (1..=count).map(|i| (i * 37) % count + 1).collect()
+    }
+
+    #[test]
+    fn test_get_and_set_slot() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 5;
+
+        unsafe {
+            tree.set_slot(&mut root, Slot::LeftOf(5), 3);
+            tree.set_slot(&mut root, Slot::RightOf(5), 7);
+            assert_eq!(tree.get_slot(root, Slot::Root), 5);
+            assert_eq!(tree.get_slot(root, Slot::LeftOf(5)), 3);
+            assert_eq!(tree.get_slot(root, Slot::RightOf(5)), 7);
+
+            tree.set_slot(&mut root, Slot::Root, 3);
+            assert_eq!(root, 3);
+            assert_eq!(tree.get_left(5), 3);
+        }
+    }
+
+    #[test]
+    fn test_rotate_at_slot() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 4;
+
+        unsafe {
+            tree.set_left(4, 2);
+            tree.set_right(4, 6);
+            tree.set_left(6, 5);
+            tree.set_right(6, 7);
+            for node in [2, 5, 7] {
+                tree.set_size(node, 1);
+            }
+            tree.set_size(6, 3);
+            tree.set_size(4, 5);
+
+            tree.slot_left_rotate(&mut root, Slot::Root);
+            assert_eq!(root, 6);
+            assert_eq!(tree.get_left(6), 4);
+            assert_eq!(tree.get_right(4), 5);
+            assert_eq!(tree.get_size(6), 5);
+
+            tree.slot_right_rotate(&mut root, Slot::LeftOf(6));
+            assert_eq!(tree.get_left(6), 2);
+            assert_eq!(tree.get_right(2), 4);
+            assert_eq!(tree.get_size(2), 3);
+        }
+    }
+
+    #[test]
+    fn test_attach_matches_iterative() {
+        let mut by_slot = TestTree::new(100);
+        let mut by_pointer = TestTree::new(100);
+        let mut slot_root: usize = 0;
+        let mut pointer_root: usize = 0;
+
+        for node in scrambled(100) {
+            unsafe {
+                by_slot.slot_attach(&mut slot_root, node);
+                by_pointer.attach(&mut pointer_root, node);
+            }
+            assert_eq!(slot_root, pointer_root);
+            assert_eq!(by_slot.nodes, by_pointer.nodes);
+        }
+        unsafe {
+            assert_eq!(by_slot.get_size(slot_root), 100);
+        }
+    }
+
+    #[test]
+    fn test_detach_matches_iterative() {
+        let mut by_slot = TestTree::new(100);
+        let mut by_pointer = TestTree::new(100);
+        let mut slot_root: usize = 0;
+        let mut pointer_root: usize = 0;
+
+        for node in 1..=100 {
+            unsafe {
+                by_slot.slot_attach(&mut slot_root, node);
+                by_pointer.attach(&mut pointer_root, node);
+            }
+        }
+        for node in scrambled(100) {
+            unsafe {
+                by_slot.slot_detach(&mut slot_root, node);
+                by_pointer.detach(&mut pointer_root, node);
+            }
+            assert_eq!(slot_root, pointer_root);
+            assert_eq!(by_slot.nodes, by_pointer.nodes);
+        }
+        assert_eq!(slot_root, 0);
+    }
+
+    #[test]
+    fn test_detach_root_with_two_children() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            for node in [2, 1, 3] {
+                tree.slot_attach(&mut root, node);
+            }
+            tree.slot_detach(&mut root, 2);
+
+            assert_eq!(tree.get_size(root), 2);
+            assert!(!tree.contains(2, root));
+            assert!(tree.contains(1, root));
+            assert!(tree.contains(3, root));
+            assert_eq!(tree.get_size(2), 0);
+        }
+    }
+}
+
+// =============================================================================
+// AvlTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod avl_tree_tests {
+    use super::*;
+
+    /// Checks order, stored heights and balance; returns the subtree height
+    fn check_subtree(tree: &TestAvlTree, node: usize, low: usize, high: usize) -> usize {
+        if node == 0 {
+            return 0;
+        }
+        assert!(low < node && node < high, "node {} is out of order", node);
+        let n = tree.nodes[node];
+        let left_height = check_subtree(tree, n.left, low, node);
+        let right_height = check_subtree(tree, n.right, node, high);
+        assert!(
+            left_height.abs_diff(right_height) <= 1,
+            "node {} is unbalanced",
+            node
+        );
+        assert_eq!(n.height, left_height.max(right_height) + 1, "node {}", node);
+        n.height
+    }
+
+    fn check(tree: &TestAvlTree, root: usize) -> usize {
+        check_subtree(tree, root, 0, usize::MAX)
+    }
+
+    #[test]
+    fn test_attach_to_empty_tree() {
+        let mut tree = TestAvlTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.attach(&mut root, 5);
+            assert_eq!(root, 5);
+            assert_eq!(tree.get_height(5), 1);
+            assert!(tree.contains(5, root));
+            assert!(!tree.contains(4, root));
+        }
+    }
+
+    #[test]
+    fn test_single_and_double_rotations() {
+        let mut tree = TestAvlTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            for node in [1, 2, 3] {
+                tree.attach(&mut root, node);
+            }
+            assert_eq!(root, 2);
+            assert_eq!(tree.get_left(2), 1);
+            assert_eq!(tree.get_right(2), 3);
+
+            let mut root: usize = 0;
+            for node in [7, 5, 6] {
+                tree.attach(&mut root, node);
+            }
+            assert_eq!(root, 6);
+            assert_eq!(tree.get_left(6), 5);
+            assert_eq!(tree.get_right(6), 7);
+            assert_eq!(tree.get_height(6), 2);
+        }
+    }
+
+    #[test]
+    fn test_ascending_attach_stays_logarithmic() {
+        let mut tree = TestAvlTree::new(1023);
+        let mut root: usize = 0;
+
+        for node in 1..=1023 {
+            unsafe {
+                tree.attach(&mut root, node);
+            }
+        }
+        assert_eq!(check(&tree, root), 10);
+    }
+
+    #[test]
+    fn test_detach_every_shape() {
+        let count = 200;
+        let mut tree = TestAvlTree::new(count);
+        let mut root: usize = 0;
+
+        for i in 1..=count {
+            unsafe {
+                tree.attach(&mut root, (i * 37) % count + 1);
+            }
+        }
+        check(&tree, root);
+
+        for i in 1..=count {
+            let node = (i * 91) % count + 1;
+            unsafe {
+                assert!(tree.contains(node, root));
+                tree.detach(&mut root, node);
+                assert!(!tree.contains(node, root));
+                assert_eq!(tree.get_height(node), 0);
+            }
+            check(&tree, root);
+        }
+        assert_eq!(root, 0);
+    }
+
+    #[test]
+    fn test_detach_root_with_two_children() {
+        let mut tree = TestAvlTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            for node in [4, 2, 6, 5] {
+                tree.attach(&mut root, node);
+            }
+            tree.detach(&mut root, 4);
+
+            assert_eq!(root, 5);
+            assert_eq!(tree.get_left(5), 2);
+            assert_eq!(tree.get_right(5), 6);
+        }
+        check(&tree, root);
+    }
+
+    #[test]
+    fn test_reattach_after_detach() {
+        let mut tree = TestAvlTree::new(50);
+        let mut root: usize = 0;
+
+        unsafe {
+            for node in 1..=50 {
+                tree.attach(&mut root, node);
+            }
+            for node in (1..=50).step_by(3) {
+                tree.detach(&mut root, node);
+            }
+            for node in (1..=50).step_by(3) {
+                tree.attach(&mut root, node);
+            }
+            for node in 1..=50 {
+                assert!(tree.contains(node, root));
+            }
+        }
+        check(&tree, root);
+    }
+}
+
+// =============================================================================
+// RedBlackTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod red_black_tree_tests {
+    use super::*;
+
+    /// Checks order, sizes and colors; returns the subtree black height
+    fn check_subtree(tree: &TestRedBlackTree, node: usize, low: usize, high: usize) -> usize {
+        if node == 0 {
+            return 1;
+        }
+        assert!(low < node && node < high, "node {} is out of order", node);
+        unsafe {
+            let left = tree.get_left(node);
+            let right = tree.get_right(node);
+            if tree.is_red(node) {
+                assert!(
+                    !tree.is_red_or_default(left) && !tree.is_red_or_default(right),
+                    "red node {} has a red child",
+                    node
+                );
+            }
+            let left_black_height = check_subtree(tree, left, low, node);
+            let right_black_height = check_subtree(tree, right, node, high);
+            assert_eq!(left_black_height, right_black_height, "node {}", node);
+            assert_eq!(
+                tree.get_size(node),
+                tree.get_left_size(node) + tree.get_right_size(node) + 1,
+                "node {}",
+                node
+            );
+            left_black_height + usize::from(!tree.is_red(node))
+        }
+    }
+
+    fn check(tree: &TestRedBlackTree, root: usize) {
+        unsafe {
+            assert!(!tree.is_red_or_default(root), "root must be black");
+        }
+        check_subtree(tree, root, 0, usize::MAX);
+    }
+
+    #[test]
+    fn test_insert_into_empty_tree() {
+        let mut tree = TestRedBlackTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.insert(&mut root, 5);
+            assert_eq!(root, 5);
+            assert_eq!(tree.get_size(5), 1);
+            assert!(!tree.is_red(5));
+        }
+    }
+
+    #[test]
+    fn test_color_bit_does_not_leak_into_size() {
+        let mut tree = TestRedBlackTree::new(10);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.insert(&mut root, 5);
+            tree.insert(&mut root, 3);
+            assert!(tree.is_red(3));
+            assert_eq!(tree.get_size(3), 1);
+            assert_eq!(tree.get_size(5), 2);
+            assert!(!tree.is_red_or_default(0));
+        }
+    }
+
+    #[test]
+    fn test_insert_ascending() {
+        let mut tree = TestRedBlackTree::new(1000);
+        let mut root: usize = 0;
+
+        for node in 1..=1000 {
+            unsafe {
+                tree.insert(&mut root, node);
+            }
+            check(&tree, root);
+        }
+        unsafe {
+            assert_eq!(tree.get_size(root), 1000);
+            assert_eq!(tree.get_leftest(root), 1);
+            assert_eq!(tree.get_rightest(root), 1000);
+            assert_eq!(tree.get_next(500), 501);
+        }
+    }
+
+    #[test]
+    fn test_delete_in_scrambled_order() {
+        let count = 300;
+        let mut tree = TestRedBlackTree::new(count);
+        let mut root: usize = 0;
+
+        for i in 1..=count {
+            unsafe {
+                tree.insert(&mut root, (i * 37) % count + 1);
+            }
+        }
+        check(&tree, root);
+
+        for i in 1..=count {
+            let node = (i * 91) % count + 1;
+            unsafe {
+                assert!(tree.contains(node, root));
+                tree.delete(&mut root, node);
+                assert!(!tree.contains(node, root));
+                assert_eq!(tree.get_size(node), 0);
+                assert_eq!(tree.get_size_or_zero(root), count - i);
+            }
+            check(&tree, root);
+        }
+        assert_eq!(root, 0);
+    }
+
+    #[test]
+    fn test_delete_descending_and_reinsert() {
+        let mut tree = TestRedBlackTree::new(64);
+        let mut root: usize = 0;
+
+        unsafe {
+            for node in 1..=64 {
+                tree.insert(&mut root, node);
+            }
+            for node in (33..=64).rev() {
+                tree.delete(&mut root, node);
+                check(&tree, root);
+            }
+            for node in 33..=64 {
+                tree.insert(&mut root, node);
+            }
+            check(&tree, root);
+            for node in 1..=64 {
+                assert!(tree.contains(node, root));
+            }
+        }
+    }
+}
+
+// =============================================================================
+// ScapegoatTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod scapegoat_tree_tests {
+    use super::*;
+
+    /// Checks order; returns the subtree height
+    fn check_subtree(tree: &TestScapegoatTree, node: usize, low: usize, high: usize) -> usize {
+        if node == 0 {
+            return 0;
+        }
+        assert!(low < node && node < high, "node {} is out of order", node);
+        let (left, right) = tree.nodes[node];
+        let left_height = check_subtree(tree, left, low, node);
+        let right_height = check_subtree(tree, right, node, high);
+        left_height.max(right_height) + 1
+    }
+
+    /// Checks order and the record; returns the tree height
+    fn check(tree: &TestScapegoatTree, record: &ScapegoatRecord<usize>) -> usize {
+        unsafe {
+            assert_eq!(tree.count_nodes(record.root), record.size);
+        }
+        assert!(record.size <= record.max_size);
+        check_subtree(tree, record.root, 0, usize::MAX)
+    }
+
+    #[test]
+    fn test_rebuild_makes_complete_tree() {
+        let mut tree = TestScapegoatTree::new(15);
+        let mut root: usize = 1;
+
+        for node in 1..15 {
+            tree.nodes[node].1 = node + 1;
+        }
+        unsafe {
+            root = tree.rebuild(root);
+        }
+        assert_eq!(root, 8);
+        assert_eq!(
+            check_subtree(&tree, root, 0, usize::MAX),
+            4,
+            "15 nodes fit in 4 levels"
+        );
+        assert_eq!(tree.nodes[8], (4, 12));
+    }
+
+    #[test]
+    fn test_rebuild_with_partial_last_level() {
+        let mut tree = TestScapegoatTree::new(10);
+        let mut root: usize = 10;
+
+        for node in 2..=10 {
+            tree.nodes[node].0 = node - 1;
+        }
+        unsafe {
+            root = tree.rebuild(root);
+            assert_eq!(tree.count_nodes(root), 10);
+            for node in 1..=10 {
+                assert!(tree.contains(node, root));
+            }
+        }
+        assert_eq!(check_subtree(&tree, root, 0, usize::MAX), 4);
+    }
+
+    #[test]
+    fn test_attach_ascending_stays_shallow() {
+        let mut tree = TestScapegoatTree::new(1000);
+        let mut record = ScapegoatRecord::default();
+
+        for node in 1..=1000 {
+            unsafe {
+                tree.attach(&mut record, node);
+            }
+        }
+        assert_eq!(record.size, 1000);
+        assert_eq!(record.max_size, 1000);
+        let height = check(&tree, &record);
+        assert!(height <= 18, "height {} exceeds log_1.5(1000) + 1", height);
+    }
+
+    #[test]
+    fn test_detach_in_scrambled_order() {
+        let count = 300;
+        let mut tree = TestScapegoatTree::new(count);
+        let mut record = ScapegoatRecord::default();
+
+        for i in 1..=count {
+            unsafe {
+                tree.attach(&mut record, (i * 37) % count + 1);
+            }
+        }
+        check(&tree, &record);
+
+        for i in 1..=count {
+            let node = (i * 91) % count + 1;
+            unsafe {
+                assert!(tree.contains(node, record.root));
+                tree.detach(&mut record, node);
+                assert!(!tree.contains(node, record.root));
+            }
+            assert_eq!(tree.nodes[node], (0, 0));
+            assert_eq!(record.size, count - i);
+            check(&tree, &record);
+        }
+        assert_eq!(record, ScapegoatRecord::default());
+    }
+
+    #[test]
+    fn test_shrinking_rebuilds_whole_tree() {
+        let mut tree = TestScapegoatTree::new(30);
+        let mut record = ScapegoatRecord::default();
+
+        unsafe {
+            for node in 1..=30 {
+                tree.attach(&mut record, node);
+            }
+            for node in 1..=10 {
+                tree.detach(&mut record, node);
+            }
+            assert_eq!(record.max_size, 30);
+            tree.detach(&mut record, 11);
+        }
+        assert_eq!(record.size, 19);
+        assert_eq!(record.max_size, 19);
+        assert_eq!(check(&tree, &record), 5);
+    }
+}
+
+// =============================================================================
+// SplayTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod splay_tree_tests {
+    use super::*;
+
+    /// Checks order and sizes; returns the subtree size
+    fn check_subtree(tree: &TestTree, node: usize, low: usize, high: usize) -> usize {
+        if node == 0 {
+            return 0;
+        }
+        assert!(low < node && node < high, "node {} is out of order", node);
+        let n = tree.nodes[node];
+        let size =
+            check_subtree(tree, n.left, low, node) + check_subtree(tree, n.right, node, high) + 1;
+        assert_eq!(n.size, size, "node {}", node);
+        size
+    }
+
+    fn check(tree: &TestTree, root: usize) -> usize {
+        check_subtree(tree, root, 0, usize::MAX)
+    }
+
+    fn build(count: usize) -> (TestTree, usize) {
+        let mut tree = TestTree::new(count);
+        let mut root: usize = 0;
+        for i in 1..=count {
+            unsafe {
+                tree.splay_attach(&mut root, (i * 37) % count + 1);
+            }
+        }
+        (tree, root)
+    }
+
+    #[test]
+    fn test_attach_moves_node_to_root() {
+        let mut tree = TestTree::new(10);
+        let mut root: usize = 0;
+
+        for node in [5, 3, 8, 4] {
+            unsafe {
+                tree.splay_attach(&mut root, node);
+            }
+            assert_eq!(root, node);
+            check(&tree, root);
+        }
+        assert_eq!(check(&tree, root), 4);
+    }
+
+    #[test]
+    fn test_search_splays_hot_nodes() {
+        let (mut tree, mut root) = build(500);
+
+        unsafe {
+            assert!(tree.splay_search(&mut root, 123));
+            assert_eq!(root, 123);
+            check(&tree, root);
+
+            assert!(tree.splay_search(&mut root, 7));
+            assert_eq!(root, 7);
+            assert_eq!(tree.get_right(7), 123);
+            check(&tree, root);
+        }
+    }
+
+    #[test]
+    fn test_search_for_absent_node() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            assert!(!tree.splay_search(&mut root, 3));
+            for node in (2..=20).step_by(2) {
+                tree.splay_attach(&mut root, node);
+            }
+            assert!(!tree.splay_search(&mut root, 7));
+            assert!(root == 6 || root == 8);
+        }
+        assert_eq!(check(&tree, root), 10);
+    }
+
+    #[test]
+    fn test_rank_and_select() {
+        let (mut tree, mut root) = build(200);
+
+        unsafe {
+            for node in [1, 50, 137, 200] {
+                assert_eq!(tree.splay_rank(&mut root, node), node - 1);
+                check(&tree, root);
+            }
+            for index in [0, 99, 199] {
+                assert_eq!(tree.splay_select(&mut root, index), index + 1);
+                assert_eq!(root, index + 1);
+                check(&tree, root);
+            }
+            assert_eq!(tree.splay_select(&mut root, 200), 0);
+        }
+    }
+
+    #[test]
+    fn test_detach_in_scrambled_order() {
+        let count = 300;
+        let (mut tree, mut root) = build(count);
+
+        for i in 1..=count {
+            let node = (i * 91) % count + 1;
+            unsafe {
+                tree.splay_detach(&mut root, node);
+                assert!(!tree.contains(node, root));
+                assert_eq!(tree.get_size(node), 0);
+            }
+            assert_eq!(check(&tree, root), count - i);
+        }
+        assert_eq!(root, 0);
+    }
+
+    #[test]
+    fn test_detach_absent_node_keeps_tree() {
+        let mut tree = TestTree::new(20);
+        let mut root: usize = 0;
+
+        unsafe {
+            tree.splay_detach(&mut root, 3);
+            assert_eq!(root, 0);
+            for node in (2..=20).step_by(2) {
+                tree.splay_attach(&mut root, node);
+            }
+            tree.splay_detach(&mut root, 7);
+            assert!(root == 6 || root == 8);
+            for node in (2..=20).step_by(2) {
+                assert!(tree.contains(node, root), "node {} was lost", node);
+            }
+        }
+        assert_eq!(check(&tree, root), 10);
+    }
+}
+
+// =============================================================================
+// Treap trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod treap_tests {
+    use super::*;
+
+    /// Checks order, sizes and the heap property; returns the subtree depth
+    fn check_subtree(tree: &TestTree, node: usize, low: usize, high: usize) -> usize {
+        if node == 0 {
+            return 0;
+        }
+        assert!(low < node && node < high, "node {} is out of order", node);
+        unsafe {
+            let left = tree.get_left(node);
+            let right = tree.get_right(node);
+            for child in [left, right] {
+                if child != 0 {
+                    assert!(tree.get_priority(child) < tree.get_priority(node));
+                }
+            }
+            assert_eq!(
+                tree.get_size(node),
+                tree.get_left_size(node) + tree.get_right_size(node) + 1,
+                "node {}",
+                node
+            );
+            let left_depth = check_subtree(tree, left, low, node);
+            let right_depth = check_subtree(tree, right, node, high);
+            left_depth.max(right_depth) + 1
+        }
+    }
+
+    fn check(tree: &TestTree, root: usize) -> usize {
+        check_subtree(tree, root, 0, usize::MAX)
+    }
+
+    #[test]
+    fn test_priorities_are_distinct_and_deterministic() {
+        let tree = TestTree::new(0);
+        let mut priorities: Vec<u64> = (1..=1000).map(|node| tree.get_priority(node)).collect();
+        assert_eq!(tree.get_priority(7), tree.get_priority(7));
+        assert_eq!(tree.get_priority(7), crate::hash::link(7usize));
+        priorities.sort_unstable();
+        priorities.dedup();
+        assert_eq!(priorities.len(), 1000);
+    }
+
+    #[test]
+    fn test_priorities_use_the_high_half_of_wide_ids() {
+        let low = 7u128;
+        let high = 1u128 << 64 | 7;
+        assert_ne!(crate::hash::link(low), crate::hash::link(high));
+        assert_ne!(
+            crate::hash::link(1u128 << 64),
+            crate::hash::link(2u128 << 64)
+        );
+    }
+
+    #[test]
+    fn test_attach_ascending_keeps_depth_low() {
+        let mut tree = TestTree::new(1000);
+        let mut root: usize = 0;
+
+        for node in 1..=1000 {
+            unsafe {
+                tree.treap_attach(&mut root, node);
+            }
+        }
+        let depth = check(&tree, root);
+        assert!(depth < 40, "depth {} is too large", depth);
+        unsafe {
+            assert_eq!(tree.get_size(root), 1000);
+            assert_eq!(tree.get_leftest(root), 1);
+            assert_eq!(tree.get_rightest(root), 1000);
+        }
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let mut tree = TestTree::new(100);
+        let mut root: usize = 0;
+
+        unsafe {
+            for node in 1..=100 {
+                tree.treap_attach(&mut root, node);
+            }
+            let (left, right) = tree.split(root, 41);
+            assert_eq!(tree.get_size(left), 40);
+            assert_eq!(tree.get_size(right), 60);
+            assert_eq!(tree.get_rightest(left), 40);
+            assert_eq!(tree.get_leftest(right), 41);
+            check(&tree, left);
+            check(&tree, right);
+
+            root = tree.merge(left, right);
+            assert_eq!(tree.get_size(root), 100);
+        }
+        check(&tree, root);
+    }
+
+    #[test]
+    fn test_detach_in_scrambled_order() {
+        let count = 300;
+        let mut tree = TestTree::new(count);
+        let mut root: usize = 0;
+
+        for i in 1..=count {
+            unsafe {
+                tree.treap_attach(&mut root, (i * 37) % count + 1);
+            }
+        }
+        check(&tree, root);
+
+        for i in 1..=count {
+            let node = (i * 91) % count + 1;
+            unsafe {
+                assert!(tree.contains(node, root));
+                tree.treap_detach(&mut root, node);
+                assert!(!tree.contains(node, root));
+                assert_eq!(tree.get_size(node), 0);
+                assert_eq!(tree.get_size_or_zero(root), count - i);
+            }
+            check(&tree, root);
+        }
+        assert_eq!(root, 0);
+    }
+
+    #[test]
+    fn test_shape_does_not_depend_on_insertion_order() {
+        let mut ascending = TestTree::new(64);
+        let mut scrambled = TestTree::new(64);
+        let mut ascending_root: usize = 0;
+        let mut scrambled_root: usize = 0;
+
+        unsafe {
+            for node in 1..=64 {
+                ascending.treap_attach(&mut ascending_root, node);
+                scrambled.treap_attach(&mut scrambled_root, (node * 37) % 64 + 1);
+            }
+        }
+        assert_eq!(ascending_root, scrambled_root);
+        assert_eq!(ascending.nodes, scrambled.nodes);
+    }
+}
+
+// =============================================================================
+// BPlusTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod b_plus_tree_tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Checks page fill, key order and leaf depth; collects the leaves in order
+    fn check_page(
+        pages: &TestPages,
+        page: usize,
+        is_root: bool,
+        low: Option<usize>,
+        high: Option<usize>,
+        leaves: &mut Vec<usize>,
+    ) -> usize {
+        let p = pages.pages[page];
+        assert!(p.count <= pages.max_keys, "page {} overflows", page);
+        if !is_root {
+            assert!(
+                p.count >= (pages.max_keys - 1) / 2,
+                "page {} underflows",
+                page
+            );
+        }
+        for i in 0..p.count {
+            let key = p.keys[i];
+            assert!(low.map_or(true, |low| key >= low), "page {}", page);
+            assert!(high.map_or(true, |high| key < high), "page {}", page);
+            if i > 0 {
+                assert!(p.keys[i - 1] < key, "page {} is out of order", page);
+            }
+        }
+        if p.leaf {
+            leaves.push(page);
+            return 1;
+        }
+        let mut depth = None;
+        for i in 0..=p.count {
+            let child_low = if i == 0 { low } else { Some(p.keys[i - 1]) };
+            let child_high = if i == p.count { high } else { Some(p.keys[i]) };
+            let child_depth =
+                check_page(pages, p.children[i], false, child_low, child_high, leaves);
+            assert_eq!(
+                *depth.get_or_insert(child_depth),
+                child_depth,
+                "page {}",
+                page
+            );
+        }
+        depth.unwrap() + 1
+    }
+
+    /// Checks the whole tree against the model, including the leaf chain
+    fn check(pages: &TestPages, root: usize, model: &BTreeSet<usize>) {
+        let mut leaves = Vec::new();
+        if root != 0 {
+            check_page(pages, root, true, None, None, &mut leaves);
+        }
+        let mut keys = Vec::new();
+        for (i, &leaf) in leaves.iter().enumerate() {
+            let next = leaves.get(i + 1).copied().unwrap_or(0);
+            assert_eq!(pages.pages[leaf].next, next, "leaf {}", leaf);
+            let p = pages.pages[leaf];
+            keys.extend_from_slice(&p.keys[..p.count]);
+        }
+        assert_eq!(keys, model.iter().copied().collect::<Vec<_>>());
+    }
+
+    fn collect_range(pages: &TestPages, root: usize, from: usize, to: usize) -> Vec<usize> {
+        let mut keys = Vec::new();
+        unsafe {
+            pages.each_in_range(root, from, to, |key| {
+                keys.push(key);
+                Flow::Continue
+            });
+        }
+        keys
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let mut pages = TestPages::new(4);
+        let mut root: usize = 0;
+
+        unsafe {
+            assert!(!pages.search(root, 1));
+            assert!(!pages.delete(&mut root, 1));
+        }
+        assert!(collect_range(&pages, root, 0, 100).is_empty());
+    }
+
+    #[test]
+    fn test_insert_splits_root() {
+        let mut pages = TestPages::new(3);
+        let mut root: usize = 0;
+
+        unsafe {
+            for key in [10, 20, 30] {
+                assert!(pages.insert(&mut root, key));
+            }
+            assert!(pages.is_leaf(root));
+            assert!(!pages.insert(&mut root, 20));
+
+            assert!(pages.insert(&mut root, 40));
+            assert!(!pages.is_leaf(root));
+            assert_eq!(pages.get_key_count(root), 1);
+            assert!(pages.search(root, 40));
+            assert!(!pages.search(root, 25));
+        }
+        check(&pages, root, &BTreeSet::from([10, 20, 30, 40]));
+    }
+
+    #[test]
+    fn test_matches_model() {
+        for max_keys in [3, 4, 5, 8] {
+            let mut pages = TestPages::new(max_keys);
+            let mut root: usize = 0;
+            let mut model = BTreeSet::new();
+
+            for i in 1..=400 {
+                let key = (i * 37) % 211;
+                unsafe {
+                    assert_eq!(pages.insert(&mut root, key), model.insert(key));
+                }
+                check(&pages, root, &model);
+            }
+            for i in 1..=400 {
+                let key = (i * 53) % 223;
+                unsafe {
+                    assert_eq!(pages.delete(&mut root, key), model.remove(&key));
+                    assert!(!pages.search(root, key));
+                }
+                check(&pages, root, &model);
+            }
+            assert_eq!(root, 0);
+            assert_eq!(pages.live_pages(), 0, "max_keys {}", max_keys);
+        }
+    }
+
+    #[test]
+    fn test_range_scan_follows_leaf_chain() {
+        let mut pages = TestPages::new(4);
+        let mut root: usize = 0;
+
+        unsafe {
+            for key in (0..200).step_by(2) {
+                pages.insert(&mut root, key);
+            }
+        }
+        assert_eq!(collect_range(&pages, root, 9, 17), vec![10, 12, 14, 16]);
+        assert_eq!(
+            collect_range(&pages, root, 190, 500),
+            vec![190, 192, 194, 196, 198]
+        );
+        assert!(collect_range(&pages, root, 41, 41).is_empty());
+        assert_eq!(collect_range(&pages, root, 0, 199).len(), 100);
+    }
+
+    #[test]
+    fn test_range_scan_stops_on_break() {
+        let mut pages = TestPages::new(4);
+        let mut root: usize = 0;
+        let mut seen = Vec::new();
+
+        unsafe {
+            for key in 1..=50 {
+                pages.insert(&mut root, key);
+            }
+            let flow = pages.each_in_range(root, 5, 50, |key| {
+                seen.push(key);
+                if key == 8 {
+                    Flow::Break
+                } else {
+                    Flow::Continue
+                }
+            });
+            assert_eq!(flow, Flow::Break);
+        }
+        assert_eq!(seen, vec![5, 6, 7, 8]);
+    }
+}
+
+// =============================================================================
+// CritBitTrie trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod crit_bit_trie_tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn collect(trie: &TestCritBit, root: CritBitChild<usize>) -> Vec<usize> {
+        let mut keys = Vec::new();
+        trie.each(root, &mut |key| {
+            keys.push(key);
+            Flow::Continue
+        });
+        keys
+    }
+
+    fn scrambled_keys() -> Vec<usize> {
+        (0..300usize)
+            .map(|i| i.wrapping_mul(0x9e37_79b9) % 5000)
+            .chain([0, 1, usize::MAX, usize::MAX - 1, 1 << 40])
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_trie() {
+        let mut trie = TestCritBit::new();
+        let mut root = CritBitChild::Empty;
+
+        assert!(!trie.lookup(root, 0));
+        assert!(!trie.remove(&mut root, 0));
+        assert_eq!(trie.get_successor(root, 5), None);
+        assert_eq!(trie.get_predecessor(root, 5), None);
+        assert!(collect(&trie, root).is_empty());
+    }
+
+    #[test]
+    fn test_single_key_is_a_leaf_root() {
+        let mut trie = TestCritBit::new();
+        let mut root = CritBitChild::Empty;
+
+        assert!(trie.insert(&mut root, 0));
+        assert_eq!(root, CritBitChild::Leaf(0));
+        assert!(!trie.insert(&mut root, 0));
+        assert!(trie.lookup(root, 0));
+        assert_eq!(trie.live_nodes(), 0);
+
+        assert!(trie.insert(&mut root, 6));
+        assert_eq!(root, CritBitChild::Node(1));
+        assert_eq!(trie.get_crit_bit(1), 2);
+        assert_eq!(collect(&trie, root), vec![0, 6]);
+    }
+
+    #[test]
+    fn test_matches_model() {
+        let mut trie = TestCritBit::new();
+        let mut root = CritBitChild::Empty;
+        let mut model = BTreeSet::new();
+
+        for key in scrambled_keys() {
+            assert_eq!(trie.insert(&mut root, key), model.insert(key));
+        }
+        assert_eq!(
+            collect(&trie, root),
+            model.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(trie.live_nodes(), model.len() - 1);
+
+        for probe in (0..5200).step_by(7).chain([usize::MAX, usize::MAX - 2]) {
+            assert_eq!(trie.lookup(root, probe), model.contains(&probe));
+            assert_eq!(
+                trie.get_successor(root, probe),
+                probe
+                    .checked_add(1)
+                    .and_then(|next| model.range(next..).next().copied()),
+                "successor of {}",
+                probe
+            );
+            assert_eq!(
+                trie.get_predecessor(root, probe),
+                model.range(..probe).next_back().copied(),
+                "predecessor of {}",
+                probe
+            );
+        }
+
+        for (i, key) in scrambled_keys().into_iter().enumerate() {
+            if i % 3 == 0 {
+                assert_eq!(trie.remove(&mut root, key), model.remove(&key));
+            }
+        }
+        assert!(!trie.remove(&mut root, 4_999_999));
+        assert_eq!(
+            collect(&trie, root),
+            model.iter().copied().collect::<Vec<_>>()
+        );
+
+        for key in model.clone() {
+            assert!(trie.remove(&mut root, key));
+        }
+        assert_eq!(root, CritBitChild::Empty);
+        assert_eq!(trie.live_nodes(), 0);
+    }
+
+    #[test]
+    fn test_each_with_prefix() {
+        let mut trie = TestCritBit::new();
+        let mut root = CritBitChild::Empty;
+
+        for key in [0x1200, 0x12ff, 0x1300, 0x12a0, 0x0012, usize::MAX] {
+            trie.insert(&mut root, key);
+        }
+        let length = usize::BITS - 8;
+        let mut keys = Vec::new();
+        trie.each_with_prefix(root, 0x1234, length, |key| {
+            keys.push(key);
+            Flow::Continue
+        });
+        assert_eq!(keys, vec![0x1200, 0x12a0, 0x12ff]);
+
+        keys.clear();
+        trie.each_with_prefix(root, 0x5500, length, |key| {
+            keys.push(key);
+            Flow::Continue
+        });
+        assert!(keys.is_empty());
+
+        keys.clear();
+        trie.each_with_prefix(root, usize::MAX, usize::BITS, |key| {
+            keys.push(key);
+            Flow::Continue
+        });
+        assert_eq!(keys, vec![usize::MAX]);
+
+        let mut count = 0;
+        let flow = trie.each_with_prefix(root, 0, 0, |_| {
+            count += 1;
+            if count == 2 {
+                Flow::Break
+            } else {
+                Flow::Continue
+            }
+        });
+        assert_eq!(flow, Flow::Break);
+        assert_eq!(count, 2);
+    }
+}
+
+// =============================================================================
+// PersistentSizeBalancedTree trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod persistent_size_balanced_tree_tests {
+    use super::*;
+
+    /// Collects the keys of a version in order, checking sizes and the
+    /// size-balanced invariant on the way.
+    fn collect(tree: &TestPersistentTree, root: usize, keys: &mut Vec<usize>) -> usize {
+        if root == 0 {
+            return 0;
+        }
+        let node = tree.nodes[root];
+        assert!(node.references > 0);
+        let left = collect(tree, node.left, keys);
+        keys.push(node.key);
+        let right = collect(tree, node.right, keys);
+        assert_eq!(node.size, left + right + 1);
+        unsafe {
+            for (child, other) in [(node.left, right), (node.right, left)] {
+                assert!(tree.get_left_size(child) <= other);
+                assert!(tree.get_right_size(child) <= other);
+            }
+        }
+        node.size
+    }
+
+    fn keys_of(tree: &TestPersistentTree, root: usize) -> Vec<usize> {
+        let mut keys = Vec::new();
+        collect(tree, root, &mut keys);
+        keys
+    }
+
+    fn build(tree: &mut TestPersistentTree, count: usize) -> Vec<usize> {
+        let mut roots = vec![0];
+        for i in 1..=count {
+            let node = tree.allocate((i * 37) % count + 1);
+            let root = unsafe { tree.persistent_attach(*roots.last().unwrap(), node) };
+            roots.push(root);
+        }
+        roots
+    }
+
+    #[test]
+    fn test_attach_keeps_every_version() {
+        let mut tree = TestPersistentTree::new();
+        let count = 100;
+        let roots = build(&mut tree, count);
+
+        for (version, &root) in roots.iter().enumerate() {
+            let mut expected: Vec<usize> = (1..=version).map(|i| (i * 37) % count + 1).collect();
+            expected.sort_unstable();
+            assert_eq!(keys_of(&tree, root), expected);
+        }
+        // Path copying keeps the total well below a full copy per version.
+        assert!(tree.live_nodes() < count * 12);
+    }
+
+    #[test]
+    fn test_detach_keeps_every_version() {
+        let mut tree = TestPersistentTree::new();
+        let count = 64;
+        let mut roots = build(&mut tree, count);
+        let full = *roots.last().unwrap();
+
+        let mut remaining: Vec<usize> = (1..=count).collect();
+        for i in 1..=count {
+            let key = (i * 91) % count + 1;
+            let probe = tree.allocate(key);
+            let root = unsafe { tree.persistent_detach(*roots.last().unwrap(), probe) };
+            unsafe { tree.free_node(probe) };
+            remaining.retain(|&k| k != key);
+            assert_eq!(keys_of(&tree, root), remaining);
+            roots.push(root);
+        }
+        assert_eq!(*roots.last().unwrap(), 0);
+        assert_eq!(keys_of(&tree, full), (1..=count).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_detach_of_absent_node_shares_root() {
+        let mut tree = TestPersistentTree::new();
+        let roots = build(&mut tree, 10);
+        let root = roots[10];
+        let probe = tree.allocate(1000);
+
+        let same = unsafe { tree.persistent_detach(root, probe) };
+        assert_eq!(same, root);
+        assert_eq!(tree.nodes[root].references, 2);
+
+        unsafe { tree.release(same) };
+        assert_eq!(keys_of(&tree, root), (1..=10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_release_collects_unreferenced_versions() {
+        let mut tree = TestPersistentTree::new();
+        let count = 50;
+        let roots = build(&mut tree, count);
+        let newest = roots[count];
+
+        for &root in &roots[..count] {
+            unsafe { tree.release(root) };
+        }
+        assert_eq!(tree.live_nodes(), count);
+        assert_eq!(keys_of(&tree, newest), (1..=count).collect::<Vec<_>>());
+
+        unsafe { tree.release(newest) };
+        assert_eq!(tree.live_nodes(), 0);
+    }
+
+    #[test]
+    fn test_uniquely_held_version_changes_in_place() {
+        let mut tree = TestPersistentTree::new();
+        let mut root = 0;
+        for key in 1..=32 {
+            let node = tree.allocate((key * 73) % 32 + 1);
+            root = unsafe { tree.persistent_attach_core(root, node) };
+        }
+        assert_eq!(tree.nodes.len(), 33);
+
+        let probe = tree.allocate(7);
+        root = unsafe { tree.persistent_detach_core(root, probe) };
+        unsafe { tree.free_node(probe) };
+        assert_eq!(tree.nodes.len(), 34);
+        assert_eq!(tree.live_nodes(), 31);
+        let keys = keys_of(&tree, root);
+        assert_eq!(keys.len(), 31);
+        assert!(!keys.contains(&7));
+    }
+}
+
+// =============================================================================
+// LeftRightTree tests
+// =============================================================================
+
+#[cfg(test)]
+mod left_right_tree_tests {
+    use super::*;
+    use core::cell::Cell;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    /// Walks the tree in order, checking every size field on the way.
+    fn walk(tree: &TestTree, node: usize, keys: &mut Vec<usize>) -> usize {
+        if node == 0 {
+            return 0;
+        }
+        let TreeNode { left, right, size } = tree.nodes[node];
+        let counted = walk(tree, left, keys);
+        keys.push(node);
+        let counted = counted + walk(tree, right, keys) + 1;
+        assert_eq!(size, counted, "torn size at node {}", node);
+        counted
+    }
+
+    #[test]
+    fn test_writes_are_visible_to_reads() {
+        let shared = LeftRightTree::new(TestTree::new(20), 0);
+        unsafe {
+            for node in 1..=20 {
+                shared.attach(node);
+            }
+            shared.detach(10);
+        }
+        let keys = shared.read(|tree, root| {
+            let mut keys = Vec::new();
+            walk(tree, root, &mut keys);
+            keys
+        });
+        assert_eq!(
+            keys,
+            (1..=20).filter(|&node| node != 10).collect::<Vec<_>>()
+        );
+
+        let root = shared.root();
+        let (tree, into_root) = shared.into_inner();
+        assert_eq!(into_root, root);
+        unsafe {
+            assert_eq!(tree.get_size(root), 19);
+        }
+    }
+
+    #[test]
+    fn test_both_copies_stay_identical() {
+        let shared = LeftRightTree::new(TestTree::new(50), 0);
+        unsafe {
+            for i in 1..=50 {
+                shared.attach((i * 37) % 50 + 1);
+            }
+        }
+        let first = shared.read(|tree, root| (tree.nodes.clone(), root));
+        shared.write(|_, _| {});
+        let second = shared.read(|tree, root| (tree.nodes.clone(), root));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_panicking_writer_releases_the_lock() {
+        let shared = LeftRightTree::new(TestTree::new(10), 0);
+        unsafe {
+            for node in 1..=5 {
+                shared.attach(node);
+            }
+        }
+        let before = shared.read(|tree, root| (tree.nodes.clone(), root));
+
+        // Panicking on the first copy undoes the write.
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            shared.write(|storage, root| {
+                unsafe { storage.attach(root, 6) };
+                panic!("first copy");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(shared.read(|tree, root| (tree.nodes.clone(), root)), before);
+        shared.write(|_, _| {});
+        assert_eq!(shared.read(|tree, root| (tree.nodes.clone(), root)), before);
+
+        // Panicking on the second copy completes it from the first.
+        let calls = Cell::new(0);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            shared.write(|storage, root| {
+                unsafe { storage.attach(root, 7) };
+                calls.set(calls.get() + 1);
+                assert_eq!(calls.get(), 1, "second copy");
+            })
+        }));
+        assert!(result.is_err());
+        let after = shared.read(|tree, root| (tree.nodes.clone(), root));
+        shared.write(|_, _| {});
+        assert_eq!(shared.read(|tree, root| (tree.nodes.clone(), root)), after);
+
+        unsafe { shared.attach(8) };
+        let keys = shared.read(|tree, root| {
+            let mut keys = Vec::new();
+            walk(tree, root, &mut keys);
+            keys
+        });
+        assert_eq!(keys, vec![1, 2, 3, 4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn test_readers_never_observe_torn_structure() {
+        let count = 64;
+        let shared = LeftRightTree::new(TestTree::new(count), 0);
+        let done = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    let mut reads = 0;
+                    while !done.load(Ordering::Acquire) || reads == 0 {
+                        shared.read(|tree, root| {
+                            let mut keys = Vec::new();
+                            let size = walk(tree, root, &mut keys);
+                            // The writer keeps the tree at exactly `1..=size`.
+                            assert_eq!(keys, (1..=size).collect::<Vec<_>>());
+                        });
+                        reads += 1;
+                        thread::yield_now();
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for _ in 0..5 {
+                    unsafe {
+                        for node in 1..=count {
+                            shared.attach(node);
+                        }
+                        for node in (1..=count).rev() {
+                            shared.detach(node);
+                        }
+                    }
+                }
+                done.store(true, Ordering::Release);
+            });
+        });
+
+        assert_eq!(shared.root(), 0);
+    }
+}
+
+// =============================================================================
+// CheckedTree tests
+// =============================================================================
+
+#[cfg(test)]
+mod checked_tree_tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_contains() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.insert(5), Ok(true));
+        assert_eq!(tree.insert(3), Ok(true));
+        assert_eq!(tree.insert(8), Ok(true));
+
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.contains(3), Ok(true));
+        assert_eq!(tree.contains(4), Ok(false));
+    }
+
+    #[test]
+    fn test_insert_duplicate() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        assert_eq!(tree.insert(5), Ok(true));
+        assert_eq!(tree.insert(5), Ok(false));
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        for node in 1..=10 {
+            tree.insert(node).unwrap();
+        }
+        assert_eq!(tree.remove(4), Ok(true));
+        assert_eq!(tree.remove(4), Ok(false));
+        assert_eq!(tree.contains(4), Ok(false));
+        assert_eq!(tree.len(), 9);
+    }
+
+    #[test]
+    fn test_remove_from_empty_tree() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        assert_eq!(tree.remove(1), Ok(false));
+    }
+
+    #[test]
+    fn test_null_node_is_rejected() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        assert_eq!(tree.insert(0), Err(TreeError::NullNode));
+        assert_eq!(tree.remove(0), Err(TreeError::NullNode));
+        assert_eq!(tree.contains(0), Err(TreeError::NullNode));
+    }
+
+    #[test]
+    fn test_out_of_bounds_is_rejected() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        let error = TreeError::OutOfBounds {
+            node: 11,
+            capacity: 10,
+        };
+        assert_eq!(tree.insert(11), Err(error));
+        assert_eq!(tree.remove(11), Err(error));
+        assert_eq!(tree.contains(11), Err(error));
+        assert_eq!(
+            error.to_string(),
+            "node 11 is out of bounds (capacity is 10)"
+        );
+    }
+
+    #[test]
+    fn test_iter_in_order() {
+        let mut storage = TestTree::new(20);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 20) };
+
+        for node in [10, 4, 17, 1, 20, 7, 13] {
+            tree.insert(node).unwrap();
+        }
+
+        assert_eq!(tree.iter().len(), 7);
+        assert_eq!(
+            tree.iter().collect::<Vec<_>>(),
+            vec![1, 4, 7, 10, 13, 17, 20]
+        );
+        assert_eq!(tree.first(), Some(1));
+        assert_eq!(tree.last(), Some(20));
+    }
+
+    #[test]
+    fn test_iter_empty_tree() {
+        let mut storage = TestTree::new(10);
+        let tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        assert_eq!(tree.iter().next(), None);
+        assert_eq!(tree.first(), None);
+        assert_eq!(tree.last(), None);
+    }
+
+    #[test]
+    fn test_insert_clears_stale_node() {
+        let mut storage = TestTree::new(10);
+        unsafe {
+            storage.set_left(3, 7);
+            storage.set_right(3, 9);
+            storage.set_size(3, 5);
+        }
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+
+        tree.insert(3).unwrap();
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![3]);
+    }
+
+    #[test]
+    fn test_resume_with_root() {
+        let mut storage = TestTree::new(10);
+        let mut tree = unsafe { CheckedTree::new(&mut storage, 10) };
+        for node in [2, 6, 9] {
+            tree.insert(node).unwrap();
+        }
+        let root = tree.into_root();
+
+        let tree = unsafe { CheckedTree::with_root(&mut storage, 10, root) };
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.capacity(), 10);
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![2, 6, 9]);
+    }
+}
+
+// =============================================================================
+// NodeAllocator trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod node_allocator_tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_grows_high_water_mark() {
+        let mut allocator = TestAllocator::new();
+
+        assert_eq!(allocator.allocate(), 1);
+        assert_eq!(allocator.allocate(), 2);
+        assert_eq!(allocator.allocate(), 3);
+        assert_eq!(allocator.get_allocated(), 3);
+        assert_eq!(allocator.get_used_count(), 3);
+        assert_eq!(allocator.get_unused_count(), 0);
+    }
+
+    #[test]
+    fn test_free_and_reuse() {
+        let mut allocator = TestAllocator::new();
+        for _ in 0..5 {
+            allocator.allocate();
+        }
+
+        allocator.free(2);
+        allocator.free(4);
+        assert!(allocator.is_unused(2));
+        assert!(allocator.is_unused(4));
+        assert!(!allocator.is_unused(3));
+        assert_eq!(allocator.get_unused_count(), 2);
+        assert_eq!(allocator.get_used_count(), 3);
+
+        assert_eq!(allocator.allocate(), 4);
+        assert_eq!(allocator.allocate(), 2);
+        assert_eq!(allocator.allocate(), 6);
+        assert!(!allocator.is_unused(2));
+        assert!(!allocator.is_unused(4));
+    }
+
+    #[test]
+    fn test_free_null_and_unallocated_ids() {
+        let mut allocator = TestAllocator::new();
+        allocator.allocate();
+        allocator.allocate();
+
+        allocator.free(0);
+        allocator.free(5);
+        assert_eq!(allocator.get_allocated(), 2);
+        assert_eq!(allocator.get_unused_count(), 0);
+        assert_eq!(allocator.allocate(), 3);
+    }
+
+    #[test]
+    fn test_double_free_is_ignored() {
+        let mut allocator = TestAllocator::new();
+        for _ in 0..4 {
+            allocator.allocate();
+        }
+
+        allocator.free(2);
+        allocator.free(2);
+        assert_eq!(allocator.get_unused_count(), 1);
+        assert_eq!(allocator.get_used_count(), 3);
+        assert_eq!(allocator.get_next(2), 2);
+        assert_eq!(allocator.get_previous(2), 2);
+
+        assert_eq!(allocator.allocate(), 2);
+        assert_eq!(allocator.allocate(), 5);
+    }
+
+    #[test]
+    fn test_free_last_shrinks_high_water_mark() {
+        let mut allocator = TestAllocator::new();
+        for _ in 0..3 {
+            allocator.allocate();
+        }
+
+        allocator.free(3);
+        assert_eq!(allocator.get_allocated(), 2);
+        assert_eq!(allocator.get_unused_count(), 0);
+    }
+
+    #[test]
+    fn test_free_last_compacts_trailing_unused() {
+        let mut allocator = TestAllocator::new();
+        for _ in 0..5 {
+            allocator.allocate();
+        }
+
+        allocator.free(3);
+        allocator.free(4);
+        allocator.free(1);
+        assert_eq!(allocator.get_unused_count(), 3);
+
+        allocator.free(5);
+        assert_eq!(allocator.get_allocated(), 2);
+        assert_eq!(allocator.get_unused_count(), 1);
+        assert!(allocator.is_unused(1));
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut allocator = TestAllocator::new();
+        for _ in 0..4 {
+            allocator.allocate();
+        }
+        allocator.attach_as_first(4);
+        allocator.attach_as_first(3);
+        allocator.attach_as_first(1);
+
+        assert_eq!(allocator.compact(), 2);
+        assert_eq!(allocator.get_allocated(), 2);
+        assert_eq!(allocator.get_unused_count(), 1);
+        assert_eq!(allocator.compact(), 0);
+    }
+
+    #[test]
+    fn test_free_everything() {
+        let mut allocator = TestAllocator::new();
+        for _ in 0..4 {
+            allocator.allocate();
+        }
+        for node in 1..=4 {
+            allocator.free(node);
+        }
+
+        assert_eq!(allocator.get_allocated(), 0);
+        assert_eq!(allocator.get_unused_count(), 0);
+        assert_eq!(allocator.get_first(), 0);
+        assert_eq!(allocator.allocate(), 1);
+    }
+
+    #[test]
+    fn test_null_is_never_unused() {
+        let allocator = TestAllocator::new();
+        assert!(!allocator.is_unused(0));
+    }
+}
+
+// =============================================================================
+// FileMappedStorage tests
+// =============================================================================
+
+#[cfg(all(test, unix, feature = "mmap"))]
+mod file_mapped_storage_tests {
+    use super::*;
+    use crate::FileMappedStorage;
+    use std::format;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    /// A temporary file path removed when dropped
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new() -> Self {
+            let name = format!(
+                "platform-trees-{}-{}.bin",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            );
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_open_new_file() {
+        let path = TempPath::new();
+        let storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+
+        assert!(storage.get_capacity() > 0);
+        assert_eq!(storage.get_allocated(), 0);
+        assert_eq!(storage.get_unused_count(), 0);
+        for index in 0..FileMappedStorage::<u64>::ROOTS {
+            assert_eq!(storage.get_root(index), 0);
+        }
+    }
+
+    #[test]
+    fn test_tree_survives_reopen() {
+        let path = TempPath::new();
+        {
+            let mut storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+            let mut root = 0;
+            for _ in 0..100 {
+                let node = storage.allocate();
+                unsafe {
+                    storage.attach(&mut root, node);
+                }
+            }
+            storage.set_root(0, root);
+            storage.flush().unwrap();
+        }
+
+        let storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+        let root = storage.get_root(0);
+        assert_eq!(storage.get_allocated(), 100);
+        unsafe {
+            assert_eq!(RecursiveSizeBalancedTree::get_size(&storage, root), 100);
+            for node in 1..=100 {
+                assert!(storage.contains(node, root), "Node {} missing", node);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grows_on_demand() {
+        let path = TempPath::new();
+        let mut storage = FileMappedStorage::<u32>::open(&path.0).unwrap();
+        let initial_capacity = storage.get_capacity();
+        let mut root = 0;
+
+        for _ in 0..initial_capacity * 3 {
+            let node = storage.allocate();
+            unsafe {
+                storage.attach(&mut root, node);
+            }
+        }
 
-            tree.fix_size(1);
-            assert_eq!(tree.get_size(1), 4); // 1 + 2 + 1
+        assert!(storage.get_capacity() >= initial_capacity * 3);
+        unsafe {
+            assert_eq!(
+                RecursiveSizeBalancedTree::get_size(&storage, root),
+                initial_capacity * 3
+            );
+            assert!(storage.contains(initial_capacity * 3, root));
         }
     }
 
     #[test]
-    fn test_left_rotate_core() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Initial: 1 -> (_, 2) where 2 -> (3, _)
-            tree.set_right(1, 2);
-            tree.set_left(2, 3);
-            tree.set_size(1, 3);
-            tree.set_size(2, 2);
-            tree.set_size(3, 1);
+    fn test_free_list_survives_reopen() {
+        let path = TempPath::new();
+        {
+            let mut storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+            for _ in 0..10 {
+                storage.allocate();
+            }
+            storage.free(3);
+            storage.free(7);
+        }
 
-            let new_root = tree.left_rotate_core(1);
+        let mut storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+        assert_eq!(storage.get_unused_count(), 2);
+        assert_eq!(storage.allocate(), 7);
+        assert_eq!(storage.allocate(), 3);
+        assert_eq!(storage.allocate(), 11);
+    }
 
-            assert_eq!(new_root, 2);
-            assert_eq!(tree.get_left(2), 1);
-            assert_eq!(tree.get_right(1), 3);
-            assert_eq!(tree.get_size(2), 3);
+    #[test]
+    fn test_allocated_nodes_are_cleared() {
+        let path = TempPath::new();
+        let mut storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+        let node = storage.allocate();
+        unsafe {
+            RecursiveSizeBalancedTree::set_size(&mut storage, node, 42);
         }
+        storage.free(node);
+
+        let node = storage.allocate();
+        assert_eq!(*storage.get_node(node), crate::MappedNode::default());
     }
 
     #[test]
-    fn test_left_rotate() {
-        let mut tree = TestTree::new(10);
+    fn test_multiple_roots() {
+        let path = TempPath::new();
+        let mut storage = FileMappedStorage::<u64>::open(&path.0).unwrap();
+        let mut even = 0;
+        let mut odd = 0;
+        for _ in 0..20 {
+            let node = storage.allocate();
+            unsafe {
+                if node % 2 == 0 {
+                    storage.attach(&mut even, node);
+                } else {
+                    storage.attach(&mut odd, node);
+                }
+            }
+        }
+        storage.set_root(0, even);
+        storage.set_root(1, odd);
+
         unsafe {
-            tree.set_right(1, 2);
-            tree.set_left(2, 3);
-            tree.set_size(1, 3);
-            tree.set_size(2, 2);
-            tree.set_size(3, 1);
+            assert!(storage.contains(4, storage.get_root(0)));
+            assert!(!storage.contains(4, storage.get_root(1)));
+            assert!(storage.contains(5, storage.get_root(1)));
+        }
+    }
 
-            let mut root: usize = 1;
-            tree.left_rotate(&mut root);
+    #[test]
+    fn test_rejects_foreign_file() {
+        let path = TempPath::new();
+        fs::write(&path.0, vec![0xAB; 4096]).unwrap();
 
-            assert_eq!(root, 2);
-        }
+        let error = FileMappedStorage::<u64>::open(&path.0).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
     }
 
     #[test]
-    fn test_right_rotate_core() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Initial: 1 -> (2, _) where 2 -> (_, 3)
-            tree.set_left(1, 2);
-            tree.set_right(2, 3);
-            tree.set_size(1, 3);
-            tree.set_size(2, 2);
-            tree.set_size(3, 1);
+    fn test_rejects_other_link_width() {
+        let path = TempPath::new();
+        drop(FileMappedStorage::<u64>::open(&path.0).unwrap());
 
-            let new_root = tree.right_rotate_core(1);
+        let error = FileMappedStorage::<u32>::open(&path.0).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
 
-            assert_eq!(new_root, 2);
-            assert_eq!(tree.get_right(2), 1);
-            assert_eq!(tree.get_left(1), 3);
-            assert_eq!(tree.get_size(2), 3);
-        }
+    #[test]
+    fn test_rejects_overflowing_capacity() {
+        let path = TempPath::new();
+        drop(FileMappedStorage::<u64>::open(&path.0).unwrap());
+        let mut bytes = fs::read(&path.0).unwrap();
+        bytes[16..24].copy_from_slice(&(u64::MAX / 8).to_ne_bytes());
+        fs::write(&path.0, &bytes).unwrap();
+
+        let error = FileMappedStorage::<u64>::open(&path.0).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
     }
 
     #[test]
-    fn test_right_rotate() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_left(1, 2);
-            tree.set_right(2, 3);
-            tree.set_size(1, 3);
-            tree.set_size(2, 2);
-            tree.set_size(3, 1);
+    fn test_rejects_allocation_past_capacity() {
+        let path = TempPath::new();
+        drop(FileMappedStorage::<u64>::open(&path.0).unwrap());
+        let mut bytes = fs::read(&path.0).unwrap();
+        bytes[24..32].copy_from_slice(&u64::MAX.to_ne_bytes());
+        fs::write(&path.0, &bytes).unwrap();
 
-            let mut root: usize = 1;
-            tree.right_rotate(&mut root);
+        let error = FileMappedStorage::<u64>::open(&path.0).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
 
-            assert_eq!(root, 2);
+    #[test]
+    fn test_try_allocate_reports_exhaustion() {
+        let path = TempPath::new();
+        let mut storage = FileMappedStorage::<u8>::open(&path.0).unwrap();
+        for expected in 1..=u8::MAX {
+            assert_eq!(storage.try_allocate().unwrap(), expected);
         }
+
+        let error = storage.try_allocate().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::OutOfMemory);
+        storage.free(9);
+        assert_eq!(storage.try_allocate().unwrap(), 9);
     }
 
     #[test]
-    fn test_get_rightest() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Create tree: 1 -> (_, 2) where 2 -> (_, 3)
-            tree.set_right(1, 2);
-            tree.set_right(2, 3);
+    fn test_small_link_type_capacity() {
+        let path = TempPath::new();
+        let mut storage = FileMappedStorage::<u8>::open(&path.0).unwrap();
 
-            assert_eq!(tree.get_rightest(1), 3);
-        }
+        assert_eq!(storage.get_capacity(), u8::MAX);
+        assert_eq!(storage.allocate(), 1);
+    }
+}
+
+// =============================================================================
+// DoubletsIndex trait tests
+// =============================================================================
+
+#[cfg(test)]
+mod doublets_index_tests {
+    use super::*;
+
+    fn collect_usages(links: &TestLinks, link: usize) -> Vec<usize> {
+        let mut usages = Vec::new();
+        links.each_usage(link, |usage| {
+            usages.push(usage);
+            Flow::Continue
+        });
+        usages
     }
 
     #[test]
-    fn test_get_leftest() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Create tree: 3 -> (2, _) where 2 -> (1, _)
-            tree.set_left(3, 2);
-            tree.set_left(2, 1);
+    fn test_flow() {
+        assert!(Flow::Continue.is_continue());
+        assert!(!Flow::Continue.is_break());
+        assert!(Flow::Break.is_break());
+        assert!(!Flow::Break.is_continue());
+    }
 
-            assert_eq!(tree.get_leftest(3), 1);
-        }
+    #[test]
+    fn test_search() {
+        let mut links = TestLinks::new();
+        let point = links.create(1, 1);
+        let a = links.create(1, 2);
+        let b = links.create(2, 1);
+
+        assert_eq!(links.search(1, 1), point);
+        assert_eq!(links.search(1, 2), a);
+        assert_eq!(links.search(2, 1), b);
+        assert_eq!(links.search(2, 2), 0);
     }
 
     #[test]
-    fn test_get_next() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Node 1 has right child 2 which has left child 3
-            tree.set_right(1, 2);
-            tree.set_left(2, 3);
+    fn test_search_empty() {
+        let links = TestLinks::new();
+        assert_eq!(links.search(1, 1), 0);
+        assert_eq!(links.count_by_source(1), 0);
+        assert_eq!(links.each_by_source(1, |_| Flow::Break), Flow::Continue);
+    }
 
-            assert_eq!(tree.get_next(1), 3);
+    #[test]
+    fn test_count_by_source_and_target() {
+        let mut links = TestLinks::new();
+        for source in 1..=5 {
+            for target in 1..=5 {
+                links.create(source, target);
+            }
         }
+        links.create(3, 3);
+
+        assert_eq!(links.count_by_source(3), 6);
+        assert_eq!(links.count_by_target(3), 6);
+        assert_eq!(links.count_by_source(6), 0);
+        assert_eq!(links.count(3, 3), 2);
+        assert_eq!(links.count(3, 4), 1);
+        assert_eq!(links.count(6, 1), 0);
     }
 
     #[test]
-    fn test_get_previous() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Node 3 has left child 2 which has right child 1
-            tree.set_left(3, 2);
-            tree.set_right(2, 1);
+    fn test_each_by_source_in_order() {
+        let mut links = TestLinks::new();
+        let c = links.create(7, 9);
+        let a = links.create(7, 2);
+        links.create(8, 1);
+        let b = links.create(7, 5);
 
-            assert_eq!(tree.get_previous(3), 1);
+        let mut visited = Vec::new();
+        let flow = links.each_by_source(7, |link| {
+            visited.push(link);
+            Flow::Continue
+        });
+
+        assert_eq!(flow, Flow::Continue);
+        assert_eq!(visited, vec![a, b, c]);
+    }
+
+    #[test]
+    fn test_each_by_target_in_order() {
+        let mut links = TestLinks::new();
+        let b = links.create(4, 7);
+        links.create(7, 4);
+        let a = links.create(2, 7);
+
+        let mut visited = Vec::new();
+        links.each_by_target(7, |link| {
+            visited.push(link);
+            Flow::Continue
+        });
+
+        assert_eq!(visited, vec![a, b]);
+    }
+
+    #[test]
+    fn test_each_with_break() {
+        let mut links = TestLinks::new();
+        for target in 1..=10 {
+            links.create(1, target);
         }
+
+        let mut visited = 0;
+        let flow = links.each_by_source(1, |_| {
+            visited += 1;
+            if visited == 3 {
+                Flow::Break
+            } else {
+                Flow::Continue
+            }
+        });
+
+        assert_eq!(flow, Flow::Break);
+        assert_eq!(visited, 3);
     }
 
     #[test]
-    fn test_contains_found() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            // Create BST: 5 -> (3, 7)
-            tree.set_left(5, 3);
-            tree.set_right(5, 7);
+    fn test_usages() {
+        let mut links = TestLinks::new();
+        let point = links.create(1, 1);
+        let a = links.create(point, 5);
+        let b = links.create(6, point);
+        let c = links.create(point, point);
+        links.create(5, 6);
 
-            assert!(tree.contains(5, 5));
-            assert!(tree.contains(3, 5));
-            assert!(tree.contains(7, 5));
+        assert_eq!(links.count_usages(point), 3);
+        let mut usages = collect_usages(&links, point);
+        usages.sort_unstable();
+        assert_eq!(usages, vec![a, b, c]);
+    }
+
+    #[test]
+    fn test_usages_of_unused_link() {
+        let mut links = TestLinks::new();
+        let a = links.create(2, 3);
+        links.create(3, 2);
+
+        assert_eq!(links.count_usages(a), 0);
+        assert!(collect_usages(&links, a).is_empty());
+    }
+
+    #[test]
+    fn test_each_usage_with_break() {
+        let mut links = TestLinks::new();
+        links.create(1, 1);
+        for _ in 0..5 {
+            links.create(1, 2);
+            links.create(2, 1);
         }
+
+        let mut visited = 0;
+        let flow = links.each_usage(1, |_| {
+            visited += 1;
+            if visited == 7 {
+                Flow::Break
+            } else {
+                Flow::Continue
+            }
+        });
+
+        assert_eq!(flow, Flow::Break);
+        assert_eq!(visited, 7);
     }
 
     #[test]
-    fn test_contains_not_found() {
-        let mut tree = TestTree::new(10);
+    fn test_detach_and_update() {
+        let mut links = TestLinks::new();
+        let a = links.create(1, 2);
+        let b = links.create(1, 3);
+
         unsafe {
-            // Create BST: 5 -> (3, 7)
-            tree.set_left(5, 3);
-            tree.set_right(5, 7);
+            links.detach_link(a);
+        }
+        assert_eq!(links.search(1, 2), 0);
+        assert_eq!(links.count_by_source(1), 1);
 
-            assert!(!tree.contains(2, 5));
-            assert!(!tree.contains(4, 5));
-            assert!(!tree.contains(8, 5));
+        links.links[a] = (4, 5);
+        unsafe {
+            links.attach_link(a);
         }
+        assert_eq!(links.search(4, 5), a);
+        assert_eq!(links.search(1, 3), b);
+        assert_eq!(links.count_by_target(5), 1);
     }
 
     #[test]
-    fn test_contains_empty_tree() {
-        let tree = TestTree::new(10);
-        unsafe {
-            assert!(!tree.contains(1, 0));
+    fn test_many_links_stay_balanced() {
+        let mut links = TestLinks::new();
+        for i in 1..=200 {
+            links.create(i % 7 + 1, i % 11 + 1);
+        }
+
+        let total: usize = (1..=7).map(|source| links.count_by_source(source)).sum();
+        assert_eq!(total, 200);
+        assert_eq!(links.sources[links.sources_root].size, 200);
+        assert_eq!(links.targets[links.targets_root].size, 200);
+        for i in 1..=200 {
+            let link = links.search(i % 7 + 1, i % 11 + 1);
+            assert_eq!(links.get_source(link), i % 7 + 1);
+            assert_eq!(links.get_target(link), i % 11 + 1);
         }
     }
+}
+
+// =============================================================================
+// PackedTree tests
+// =============================================================================
+
+#[cfg(all(test, feature = "alloc"))]
+mod packed_tree_tests {
+    use super::*;
+    use crate::{PackedField, PackedOverflow, PackedTree};
 
     #[test]
-    fn test_clear_node() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            tree.set_left(1, 2);
-            tree.set_right(1, 3);
-            tree.set_size(1, 5);
+    fn test_node_takes_two_words() {
+        assert_eq!(std::mem::size_of::<[u64; 2]>(), 16);
+        let tree = PackedTree::<u64>::new(100, 40).unwrap();
+        assert_eq!(tree.link_bits(), 40);
+        assert_eq!(tree.size_bits(), 48);
+        assert_eq!(tree.get_capacity(), 100);
+    }
 
-            tree.clear_node(1);
+    #[test]
+    fn test_fields_are_independent() {
+        let mut tree = PackedTree::<u64>::new(10, 40).unwrap();
+        let link = (1 << 40) - 1;
+        let size = (1 << 48) - 1;
 
-            assert_eq!(tree.get_left(1), 0);
-            assert_eq!(tree.get_right(1), 0);
-            assert_eq!(tree.get_size(1), 0);
-        }
+        tree.set_left(3, link);
+        tree.set_right(3, 7);
+        tree.set_size(3, size);
+        assert_eq!(tree.get_left(3), link);
+        assert_eq!(tree.get_right(3), 7);
+        assert_eq!(tree.get_size(3), size);
+
+        tree.set_left(3, 0);
+        tree.set_size(3, 5);
+        assert_eq!(tree.get_left(3), 0);
+        assert_eq!(tree.get_right(3), 7);
+        assert_eq!(tree.get_size(3), 5);
     }
 
     #[test]
-    fn test_first_is_to_the_left_of_second() {
-        let tree = TestTree::new(10);
-        unsafe {
-            assert!(tree.first_is_to_the_left_of_second(1, 2));
-            assert!(!tree.first_is_to_the_left_of_second(2, 1));
-            assert!(!tree.first_is_to_the_left_of_second(1, 1));
-        }
+    fn test_small_link_types() {
+        let mut tree = PackedTree::<u16>::new(200, 10).unwrap();
+        assert_eq!(tree.size_bits(), 12);
+
+        tree.set_left(1, 1023);
+        tree.set_right(1, 512);
+        tree.set_size(1, 4095);
+        assert_eq!(tree.get_left(1), 1023);
+        assert_eq!(tree.get_right(1), 512);
+        assert_eq!(tree.get_size(1), 4095);
     }
 
     #[test]
-    fn test_first_is_to_the_right_of_second() {
-        let tree = TestTree::new(10);
-        unsafe {
-            assert!(tree.first_is_to_the_right_of_second(2, 1));
-            assert!(!tree.first_is_to_the_right_of_second(1, 2));
-            assert!(!tree.first_is_to_the_right_of_second(1, 1));
-        }
+    fn test_size_bits_are_capped_by_width() {
+        let mut tree = PackedTree::<u32>::new(10, 8).unwrap();
+        assert_eq!(tree.size_bits(), 32);
+
+        tree.set_size(2, u32::MAX);
+        tree.set_left(2, 255);
+        assert_eq!(tree.get_size(2), u32::MAX);
+        assert_eq!(tree.get_left(2), 255);
     }
 
     #[test]
-    fn test_get_mut_left_reference() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            let ptr = tree.get_mut_left_reference(1);
-            *ptr = 5;
-            assert_eq!(tree.get_left(1), 5);
+    fn test_link_overflow() {
+        let mut tree = PackedTree::<u64>::new(10, 40).unwrap();
+        let value = 1 << 40;
+
+        assert_eq!(
+            tree.try_set_left(1, value),
+            Err(PackedOverflow {
+                field: PackedField::Left,
+                value,
+                bits: 40
+            })
+        );
+        assert_eq!(
+            tree.try_set_right(1, value).unwrap_err().field,
+            PackedField::Right
+        );
+        assert_eq!(tree.get_left(1), 0);
+    }
+
+    #[test]
+    fn test_size_overflow() {
+        let mut tree = PackedTree::<u16>::new(10, 12).unwrap();
+
+        let overflow = tree.try_set_size(1, 256).unwrap_err();
+        assert_eq!(overflow.field, PackedField::Size);
+        assert_eq!(overflow.bits, 8);
+        assert_eq!(
+            overflow.to_string(),
+            "Size value 256 does not fit in 8 bits"
+        );
+        assert_eq!(tree.try_set_size(1, 255), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit")]
+    fn test_setter_panics_on_overflow() {
+        let mut tree = PackedTree::<u16>::new(10, 12).unwrap();
+        tree.set_size(1, 1000);
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        assert!(PackedTree::<u64>::new(10, 64).is_err());
+        assert!(PackedTree::<u64>::new(10, 0).is_err());
+        assert_eq!(
+            PackedTree::<u16>::new(5000, 12).err().unwrap().field,
+            PackedField::Left
+        );
+        assert_eq!(
+            PackedTree::<u16>::new(300, 12).err().unwrap().field,
+            PackedField::Size
+        );
+    }
+
+    #[test]
+    fn test_layout_errors_name_the_narrow_field() {
+        assert_eq!(
+            PackedTree::<u64>::new(10, 0).err(),
+            Some(PackedOverflow {
+                field: PackedField::Left,
+                value: 10,
+                bits: 0
+            })
+        );
+        for link_bits in [64, 70] {
+            assert_eq!(
+                PackedTree::<u64>::new(10, link_bits).err(),
+                Some(PackedOverflow {
+                    field: PackedField::Size,
+                    value: 10,
+                    bits: 0
+                })
+            );
         }
+        let empty = PackedTree::<u64>::new(0, 0).unwrap();
+        assert_eq!(empty.get_capacity(), 0);
+        assert_eq!(empty.size_bits(), 64);
     }
 
     #[test]
-    fn test_get_mut_right_reference() {
-        let mut tree = TestTree::new(10);
-        unsafe {
-            let ptr = tree.get_mut_right_reference(1);
-            *ptr = 7;
-            assert_eq!(tree.get_right(1), 7);
+    fn test_queries_follow_links() {
+        let mut tree = PackedTree::<u64>::new(10, 40).unwrap();
+        // 2 <- 4 -> 6 with 6 having children 5 and 7
+        tree.set_left(4, 2);
+        tree.set_right(4, 6);
+        tree.set_left(6, 5);
+        tree.set_right(6, 7);
+        for node in [2, 4, 5, 6, 7] {
+            assert!(tree.contains(node, 4));
         }
+        for node in [1, 3, 8] {
+            assert!(!tree.contains(node, 4));
+        }
+        assert!(!tree.contains(4, 0));
+        assert_eq!(tree.get_leftest(4), 2);
+        assert_eq!(tree.get_rightest(4), 7);
+        assert_eq!(tree.get_leftest(6), 5);
     }
 
     #[test]
-    fn test_get_left_reference() {
-        let mut tree = TestTree::new(10);
+    fn test_attach_and_detach() {
+        let mut tree = PackedTree::<u32>::new(200, 12).unwrap();
+        let mut root: u32 = 0;
+
         unsafe {
-            tree.set_left(1, 3);
-            let ptr = tree.get_left_reference(1);
-            assert_eq!(*ptr, 3);
+            for i in 1..=200u32 {
+                tree.attach(&mut root, (i * 37) % 200 + 1);
+            }
+            assert_eq!(tree.get_size(root), 200);
+            assert_eq!(tree.get_leftest(root), 1);
+            assert_eq!(tree.get_rightest(root), 200);
+
+            for i in (1..=200u32).filter(|i| i % 3 == 0) {
+                tree.detach(&mut root, i);
+            }
+            assert_eq!(tree.get_size(root), 134);
+            for i in 1..=200u32 {
+                assert_eq!(tree.contains(i, root), i % 3 != 0, "node {}", i);
+            }
+            for i in (1..=200u32).filter(|i| i % 3 != 0) {
+                tree.detach(&mut root, i);
+            }
+            assert_eq!(root, 0);
         }
     }
 
     #[test]
-    fn test_get_right_reference() {
-        let mut tree = TestTree::new(10);
+    fn test_attach_and_detach_in_another_order() {
+        let mut tree = PackedTree::<u32>::new(200, 12).unwrap();
+        let mut root: u32 = 0;
+
         unsafe {
-            tree.set_right(1, 4);
-            let ptr = tree.get_right_reference(1);
-            assert_eq!(*ptr, 4);
+            for i in 1..=200u32 {
+                tree.attach(&mut root, (i * 73) % 200 + 1);
+            }
+            assert_eq!(tree.get_size(root), 200);
+            assert_eq!(tree.get_leftest(root), 1);
+            assert_eq!(tree.get_rightest(root), 200);
+
+            for i in (1..=200u32).filter(|i| i % 2 == 0) {
+                tree.detach(&mut root, i);
+            }
+        }
+        assert_eq!(tree.get_size(root), 100);
+        for i in 1..=200u32 {
+            assert_eq!(tree.contains(i, root), i % 2 == 1, "node {}", i);
         }
     }
 }
 
 // =============================================================================
-// IterativeSizeBalancedTree trait tests
+// Transaction tests
 // =============================================================================
 
-#[cfg(test)]
-mod iterative_size_balanced_tree_tests {
+#[cfg(all(test, feature = "alloc"))]
+mod transaction_tests {
     use super::*;
+    use crate::Transaction;
 
-    #[test]
-    fn test_attach_to_empty_tree() {
-        let mut tree = TestTree::new(10);
-        let mut root: usize = 0;
-
-        unsafe {
-            tree.attach(&mut root, 5);
-        }
-
-        assert_eq!(root, 5);
-        unsafe {
-            assert_eq!(tree.get_size(5), 1);
-        }
+    fn list_state(list: &TestAbsoluteList) -> (usize, usize, usize, Vec<(usize, usize)>) {
+        (
+            list.first,
+            list.last,
+            list.size,
+            list.nodes
+                .iter()
+                .map(|node| (node.prev, node.next))
+                .collect(),
+        )
     }
 
     #[test]
-    fn test_attach_single_node_to_left() {
-        let mut tree = TestTree::new(10);
-        let mut root: usize = 0;
+    fn test_commit_keeps_tree_changes() {
+        let mut tree = TestTree::new(20);
+        let mut root = 0;
 
+        let mut transaction = Transaction::new(&mut tree);
         unsafe {
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 3);
+            for node in 1..=20 {
+                transaction.attach(&mut root, node);
+            }
         }
+        assert!(transaction.logged_changes() > 0);
+        transaction.commit();
 
         unsafe {
-            assert_eq!(tree.get_left(5), 3);
-            assert_eq!(tree.get_size(5), 2);
-            assert_eq!(tree.get_size(3), 1);
+            assert_eq!(tree.get_size(root), 20);
+            for node in 1..=20 {
+                assert!(tree.contains(node, root));
+            }
         }
     }
 
     #[test]
-    fn test_attach_single_node_to_right() {
-        let mut tree = TestTree::new(10);
-        let mut root: usize = 0;
+    fn test_rollback_restores_tree() {
+        let mut tree = TestTree::new(50);
+        let mut root = 0;
+        unsafe {
+            for i in 1..=50 {
+                tree.attach(&mut root, (i * 37) % 50 + 1);
+            }
+        }
+        let nodes = tree.nodes.clone();
+        let mut roots = [root];
 
+        let mut transaction = Transaction::with_roots(&mut tree, &mut roots);
         unsafe {
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 7);
+            for node in (1..=50).filter(|&node| node % 3 == 0 || node == root) {
+                transaction.with_root(0, |transaction, root| transaction.detach(root, node));
+            }
+            assert_ne!(transaction.root(0), root);
+            assert_eq!(transaction.get_size(transaction.root(0)), 33);
         }
+        transaction.rollback();
 
+        assert_eq!(roots, [root]);
+        assert_eq!(tree.nodes, nodes);
         unsafe {
-            assert_eq!(tree.get_right(5), 7);
-            assert_eq!(tree.get_size(5), 2);
-            assert_eq!(tree.get_size(7), 1);
+            assert_eq!(tree.get_size(roots[0]), 50);
         }
     }
 
     #[test]
-    fn test_attach_multiple_nodes() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+    fn test_nested_savepoints() {
+        let mut tree = TestTree::new(30);
+        let mut roots = [0];
 
+        let mut transaction = Transaction::with_roots(&mut tree, &mut roots);
         unsafe {
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
-            tree.attach(&mut root, 3);
-            tree.attach(&mut root, 7);
-        }
+            for node in 1..=10 {
+                transaction.with_root(0, |transaction, root| transaction.attach(root, node));
+            }
+            let outer = transaction.savepoint();
+            let outer_nodes = transaction.storage().nodes.clone();
+            let outer_root = transaction.root(0);
+
+            for node in 11..=20 {
+                transaction.with_root(0, |transaction, root| transaction.slot_attach(root, node));
+            }
+            let inner = transaction.savepoint();
+            let inner_nodes = transaction.storage().nodes.clone();
+            let inner_root = transaction.root(0);
 
+            for node in 21..=30 {
+                transaction.with_root(0, |transaction, root| transaction.attach(root, node));
+            }
+            transaction.rollback_to(inner);
+            assert_eq!(transaction.storage().nodes, inner_nodes);
+            assert_eq!(transaction.root(0), inner_root);
+
+            transaction.with_root(0, |transaction, root| transaction.detach(root, 5));
+            transaction.rollback_to(inner);
+            assert_eq!(transaction.storage().nodes, inner_nodes);
+            assert_eq!(transaction.root(0), inner_root);
+
+            transaction.rollback_to(outer);
+            assert_eq!(transaction.storage().nodes, outer_nodes);
+            assert_eq!(transaction.root(0), outer_root);
+            assert_eq!(transaction.get_size(transaction.root(0)), 10);
+        }
+        transaction.commit();
         unsafe {
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(5, root));
-            assert!(tree.contains(15, root));
-            assert!(tree.contains(3, root));
-            assert!(tree.contains(7, root));
-            assert_eq!(tree.get_size(root), 5);
+            assert_eq!(tree.get_size(roots[0]), 10);
+            assert!(!tree.contains(11, roots[0]));
         }
     }
 
     #[test]
-    fn test_detach_from_single_node_tree() {
+    fn test_drop_rolls_back() {
         let mut tree = TestTree::new(10);
-        let mut root: usize = 0;
-
-        unsafe {
-            tree.attach(&mut root, 5);
-            tree.detach(&mut root, 5);
+        let nodes = tree.nodes.clone();
+        let mut roots = [0];
+        {
+            let mut transaction = Transaction::with_roots(&mut tree, &mut roots);
+            unsafe {
+                for node in 1..=10 {
+                    transaction.with_root(0, |transaction, root| transaction.attach(root, node));
+                }
+            }
+            assert_ne!(transaction.root(0), 0);
         }
-
-        assert_eq!(root, 0);
+        assert_eq!(tree.nodes, nodes);
+        assert_eq!(roots, [0]);
     }
 
     #[test]
-    fn test_detach_leaf_node() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+    fn test_absolute_list_rollback() {
+        let mut list = TestAbsoluteList::new(10);
+        for element in 1..=5 {
+            list.attach_as_last(element);
+        }
+        let state = list_state(&list);
 
-        unsafe {
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
+        let mut transaction = Transaction::new(&mut list);
+        let savepoint = transaction.savepoint();
+        transaction.detach(3);
+        transaction.attach_as_first(6);
+        transaction.attach_after(6, 7);
+        assert_eq!(AbsoluteLinkedList::get_size(&transaction), 6);
+        transaction.rollback_to(savepoint);
+        assert_eq!(transaction.logged_changes(), 0);
+        assert_eq!(list_state(transaction.storage()), state);
 
-            tree.detach(&mut root, 5);
+        transaction.detach(1);
+        transaction.rollback();
+        assert_eq!(list_state(&list), state);
+    }
 
-            assert!(!tree.contains(5, root));
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(15, root));
+    #[test]
+    fn test_relative_list_rollback() {
+        let mut list = TestRelativeList::new(10);
+        for element in 2..=6 {
+            list.attach_as_last(1, element);
         }
+        let heads = list.heads.clone();
+        let links: Vec<_> = list
+            .nodes
+            .iter()
+            .map(|node| (node.prev, node.next))
+            .collect();
+
+        let mut transaction = Transaction::new(&mut list);
+        transaction.detach(1, 2);
+        transaction.detach(1, 6);
+        transaction.attach_as_first(8, 7);
+        assert_eq!(RelativeLinkedList::get_size(&transaction, 1), 3);
+        transaction.rollback();
+
+        assert_eq!(list.heads, heads);
+        assert_eq!(
+            list.nodes
+                .iter()
+                .map(|node| (node.prev, node.next))
+                .collect::<Vec<_>>(),
+            links
+        );
     }
+}
 
-    #[test]
-    fn test_detach_node_with_one_child() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+// =============================================================================
+// ShardedIndex tests
+// =============================================================================
 
-        unsafe {
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 3);
+#[cfg(all(test, feature = "std"))]
+mod sharded_index_tests {
+    use super::*;
+    use crate::ShardedIndex;
 
-            tree.detach(&mut root, 5);
+    fn sharded(shards: usize, capacity: usize) -> ShardedIndex<usize, TestTree> {
+        ShardedIndex::new((0..shards).map(|_| TestTree::new(capacity)).collect())
+    }
 
-            assert!(!tree.contains(5, root));
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(3, root));
-        }
+    fn collect(index: &ShardedIndex<usize, TestTree>) -> Vec<usize> {
+        let mut nodes = Vec::new();
+        index.each(|node| {
+            nodes.push(node);
+            Flow::Continue
+        });
+        nodes
     }
 
     #[test]
-    fn test_detach_node_with_two_children() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
-
+    fn test_nodes_spread_over_shards() {
+        let index = sharded(4, 200);
         unsafe {
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
-            tree.attach(&mut root, 3);
-            tree.attach(&mut root, 7);
-
-            tree.detach(&mut root, 5);
-
-            assert!(!tree.contains(5, root));
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(15, root));
-            assert!(tree.contains(3, root));
-            assert!(tree.contains(7, root));
+            for i in 1..=200 {
+                index.attach((i * 37) % 200 + 1);
+            }
+        }
+        assert_eq!(index.shard_count(), 4);
+        assert_eq!(index.len(), 200);
+        for (storage, root) in index.into_shards() {
+            let size = unsafe { storage.get_size_or_zero(root) };
+            assert!(size > 20 && size < 80, "unbalanced shard of {}", size);
         }
     }
 
     #[test]
-    fn test_detach_root() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
-
+    fn test_each_merges_in_order() {
+        let index = sharded(5, 300);
+        assert!(index.is_empty());
+        assert!(collect(&index).is_empty());
         unsafe {
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
-
-            tree.detach(&mut root, 10);
-
-            assert!(!tree.contains(10, root));
-            assert!(tree.contains(5, root));
-            assert!(tree.contains(15, root));
-            assert_ne!(root, 0);
+            for i in 1..=150 {
+                index.attach(((i * 91) % 150 + 1) * 2);
+            }
         }
+        assert_eq!(
+            collect(&index),
+            (1..=150).map(|i| i * 2).collect::<Vec<_>>()
+        );
+
+        let mut seen = Vec::new();
+        let flow = index.each(|node| {
+            seen.push(node);
+            if seen.len() == 3 {
+                Flow::Break
+            } else {
+                Flow::Continue
+            }
+        });
+        assert_eq!(flow, Flow::Break);
+        assert_eq!(seen, vec![2, 4, 6]);
     }
 
     #[test]
-    fn test_attach_and_detach_sequence() {
-        let mut tree = TestTree::new(30);
-        let mut root: usize = 0;
-
+    fn test_rank_and_select() {
+        let index = sharded(3, 200);
         unsafe {
-            // Build tree
-            for i in [10, 5, 15, 3, 7, 12, 17] {
-                tree.attach(&mut root, i);
+            for i in 1..=100 {
+                index.attach(((i * 73) % 100 + 1) * 2);
             }
-
-            // Verify all nodes present
-            for i in [10, 5, 15, 3, 7, 12, 17] {
-                assert!(tree.contains(i, root), "Node {} should be present", i);
+            for position in 0..100 {
+                let node = (position + 1) * 2;
+                assert_eq!(index.select(position), node);
+                assert_eq!(index.rank(node), position);
+                assert_eq!(index.rank(node + 1), position + 1);
+                assert!(index.contains(node));
+                assert!(!index.contains(node + 1));
             }
+            assert_eq!(index.rank(1), 0);
+            assert_eq!(index.select(100), 0);
+        }
+    }
 
-            // Remove some nodes
-            tree.detach(&mut root, 3);
-            tree.detach(&mut root, 17);
-            tree.detach(&mut root, 10);
+    #[test]
+    fn test_parallel_attach_and_detach() {
+        let index = sharded(4, 1000);
+        let nodes: Vec<usize> = (1..=1000).map(|i| (i * 37) % 1000 + 1).collect();
+        unsafe {
+            index.attach_all(&nodes);
+        }
+        assert_eq!(index.len(), 1000);
+        assert_eq!(collect(&index), (1..=1000).collect::<Vec<_>>());
 
-            // Verify correct nodes present
-            assert!(!tree.contains(3, root));
-            assert!(!tree.contains(17, root));
-            assert!(!tree.contains(10, root));
-            assert!(tree.contains(5, root));
-            assert!(tree.contains(15, root));
-            assert!(tree.contains(7, root));
-            assert!(tree.contains(12, root));
+        let odd: Vec<usize> = nodes.iter().copied().filter(|node| node % 2 == 1).collect();
+        unsafe {
+            index.detach_all(&odd);
         }
+        assert_eq!(index.len(), 500);
+        assert_eq!(
+            collect(&index),
+            (1..=500).map(|i| i * 2).collect::<Vec<_>>()
+        );
+        assert_eq!(index.select(249), 500);
     }
 
     #[test]
-    fn test_tree_balancing() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+    #[should_panic(expected = "needs a shard")]
+    fn test_empty_shard_list_panics() {
+        sharded(0, 10);
+    }
+}
 
-        unsafe {
-            // Insert in order that would cause imbalance without rotations
-            for i in 1..=10 {
-                tree.attach(&mut root, i);
-            }
+// =============================================================================
+// Dump tests
+// =============================================================================
 
-            // All nodes should be reachable
-            for i in 1..=10 {
-                assert!(tree.contains(i, root), "Node {} should be present", i);
+#[cfg(all(test, feature = "serde"))]
+mod dump_tests {
+    use super::*;
+    use crate::{DumpError, ListsDump, PackedTree, TreeDump};
+
+    fn built_tree(count: usize) -> (TestTree, usize) {
+        let mut tree = TestTree::new(count);
+        let mut root = 0;
+        unsafe {
+            for i in 1..=count {
+                tree.attach(&mut root, (i * 37) % count + 1);
             }
+        }
+        (tree, root)
+    }
 
-            assert_eq!(tree.get_size(root), 10);
+    fn built_lists() -> TestRelativeList {
+        let mut lists = TestRelativeList::new(20);
+        for element in 4..=9 {
+            lists.attach_as_last(1, element);
         }
+        for element in [12, 10, 11] {
+            lists.attach_as_first(2, element);
+        }
+        lists
     }
 
     #[test]
-    fn test_detach_all_nodes() {
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+    fn test_tree_round_trip_through_json() {
+        let (tree, root) = built_tree(50);
+        let dump = unsafe { TreeDump::capture(&tree, root) };
+        assert_eq!(dump.root, root);
+        assert_eq!(dump.nodes.len(), 50);
+        assert_eq!(dump.nodes[0].id, root);
+        assert_eq!(dump.validate(), Ok(()));
 
-        unsafe {
-            for i in 1..=5 {
-                tree.attach(&mut root, i);
-            }
+        let json = serde_json::to_string(&dump).unwrap();
+        let loaded: TreeDump<usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, dump);
 
-            for i in 1..=5 {
-                tree.detach(&mut root, i);
-            }
+        let mut restored = TestTree::new(50);
+        assert_eq!(unsafe { loaded.restore(&mut restored) }, Ok(root));
+        assert_eq!(restored.nodes, tree.nodes);
+    }
 
-            assert_eq!(root, 0);
+    #[test]
+    fn test_tree_migrates_between_storages() {
+        let (tree, root) = built_tree(40);
+        let dump = unsafe { TreeDump::capture(&tree, root) };
+
+        // Packed storages lend no references, so they load the records
+        // through their own setters.
+        let mut packed = PackedTree::<usize>::new(40, 8).unwrap();
+        for record in &dump.nodes {
+            packed.set_left(record.id, record.left);
+            packed.set_right(record.id, record.right);
+            packed.set_size(record.id, record.size);
+        }
+        assert_eq!(packed.get_size(dump.root), 40);
+        for node in 1..=40 {
+            assert!(packed.contains(node, dump.root));
+        }
+
+        let mut restored = TestTree::new(40);
+        assert_eq!(unsafe { dump.restore(&mut restored) }, Ok(root));
+        for record in &dump.nodes {
+            let node = restored.nodes[record.id];
+            assert_eq!(
+                (node.left, node.right, node.size),
+                (
+                    packed.get_left(record.id),
+                    packed.get_right(record.id),
+                    packed.get_size(record.id)
+                )
+            );
         }
     }
 
-    // Additional edge case tests for full coverage
+    #[test]
+    fn test_empty_tree_dump() {
+        let tree = TestTree::new(1);
+        let dump = unsafe { TreeDump::capture(&tree, 0) };
+        assert_eq!(dump, TreeDump::default());
+        assert_eq!(dump.validate(), Ok(()));
+    }
 
     #[test]
-    fn test_attach_left_right_rotation_edge_case() {
-        // Tests the path where we need a left-right double rotation on left side
-        let mut tree = TestTree::new(30);
-        let mut root: usize = 0;
+    fn test_tree_validation_errors() {
+        let (tree, root) = built_tree(10);
+        let dump = unsafe { TreeDump::capture(&tree, root) };
+        let edited = |edit: &dyn Fn(&mut TreeDump<usize>)| {
+            let mut dump = dump.clone();
+            edit(&mut dump);
+            dump.validate()
+        };
+
+        assert_eq!(
+            edited(&|dump| dump.nodes[3].id = 0),
+            Err(DumpError::NullNode)
+        );
+        assert_eq!(
+            edited(&|dump| dump.nodes.push(dump.nodes[2])),
+            Err(DumpError::DuplicateNode(dump.nodes[2].id))
+        );
+        assert_eq!(
+            edited(&|dump| dump.root = 11),
+            Err(DumpError::MissingNode(11))
+        );
+        let leaf = dump.nodes.iter().position(|node| node.size == 1).unwrap();
+        assert_eq!(
+            edited(&|dump| dump.nodes[leaf].left = root),
+            Err(DumpError::SharedNode(root))
+        );
+        assert_eq!(
+            edited(&|dump| dump.nodes[0].left = 0),
+            Err(DumpError::UnreachableNode(dump.nodes[1].id))
+        );
+        assert_eq!(
+            edited(&|dump| dump.nodes[leaf].size = 2),
+            Err(DumpError::WrongSize {
+                node: dump.nodes[leaf].id,
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            DumpError::WrongSize {
+                node: 3,
+                expected: 1,
+                found: 2
+            }
+            .to_string(),
+            "node 3 has size 2 but 1 nodes were reached"
+        );
+    }
 
+    #[test]
+    fn test_restore_rejects_foreign_order() {
+        let mut tree = TestTree::new(3);
+        let mut root = 0;
         unsafe {
-            // Build a tree that will trigger left-right rotation
-            // Start with 10, then 5, then insert 8 (between 5 and 10)
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 8); // This goes right of 5, should trigger LR rotation
-
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(5, root));
-            assert!(tree.contains(8, root));
-            assert_eq!(tree.get_size(root), 3);
+            for node in 1..=3 {
+                tree.attach(&mut root, node);
+            }
+        }
+        let mut dump = unsafe { TreeDump::capture(&tree, root) };
+        // Swapping two ids keeps the shape but breaks the storage order.
+        for node in &mut dump.nodes {
+            for link in [&mut node.id, &mut node.left, &mut node.right] {
+                *link = match *link {
+                    1 => 3,
+                    3 => 1,
+                    other => other,
+                };
+            }
         }
+        assert_eq!(dump.validate(), Ok(()));
+
+        let mut target = TestTree::new(3);
+        assert_eq!(
+            unsafe { dump.restore(&mut target) },
+            Err(DumpError::OutOfOrder { left: 3, right: 2 })
+        );
+        assert_eq!(target.nodes, TestTree::new(3).nodes);
     }
 
     #[test]
-    fn test_attach_descend_left_without_rotation() {
-        // Tests the case where we descend left without rotation
-        let mut tree = TestTree::new(40);
-        let mut root: usize = 0;
+    fn test_lists_round_trip_through_json() {
+        let lists = built_lists();
+        let dump = ListsDump::capture(&lists, &[1, 2, 3]);
+        assert_eq!(dump.heads.len(), 3);
+        assert_eq!(
+            dump.nodes.iter().map(|node| node.id).collect::<Vec<_>>(),
+            vec![4, 5, 6, 7, 8, 9, 11, 10, 12]
+        );
+        assert_eq!(dump.validate(), Ok(()));
+
+        let json = serde_json::to_string(&dump).unwrap();
+        let loaded: ListsDump<usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, dump);
+
+        let mut restored = TestRelativeList::new(20);
+        assert_eq!(loaded.restore(&mut restored), Ok(()));
+        assert_eq!(restored.heads, lists.heads);
+        let links = |list: &TestRelativeList| -> Vec<(usize, usize)> {
+            list.nodes
+                .iter()
+                .map(|node| (node.prev, node.next))
+                .collect()
+        };
+        assert_eq!(links(&restored), links(&lists));
+    }
 
-        unsafe {
-            // Build a more balanced tree first
-            tree.attach(&mut root, 20);
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 30);
-            tree.attach(&mut root, 25);
-            tree.attach(&mut root, 35);
-            // Now insert something that needs to go left but doesn't trigger rotation
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 2);
+    #[test]
+    fn test_lists_validation_errors() {
+        let dump = ListsDump::capture(&built_lists(), &[1, 2]);
+        let edited = |edit: &dyn Fn(&mut ListsDump<usize>)| {
+            let mut dump = dump.clone();
+            edit(&mut dump);
+            dump.validate()
+        };
+
+        assert_eq!(
+            edited(&|dump| dump.nodes[2].previous = 4),
+            Err(DumpError::BrokenLink(5))
+        );
+        assert_eq!(
+            edited(&|dump| dump.heads[0].size = 5),
+            Err(DumpError::WrongSize {
+                node: 1,
+                expected: 6,
+                found: 5
+            })
+        );
+        assert_eq!(
+            edited(&|dump| dump.heads[1] = dump.heads[0]),
+            Err(DumpError::SharedNode(4))
+        );
+        assert_eq!(
+            edited(&|dump| dump.heads.truncate(1)),
+            Err(DumpError::UnreachableNode(11))
+        );
+        assert_eq!(
+            edited(&|dump| dump.heads[1].last = 10),
+            Err(DumpError::BrokenLink(12))
+        );
+
+        let mut target = TestRelativeList::new(20);
+        let mut broken = dump.clone();
+        broken.nodes[0].next = 19;
+        assert_eq!(broken.restore(&mut target), Err(DumpError::MissingNode(19)));
+        assert!(target.heads.iter().all(|&head| head == (0, 0, 0)));
+    }
+}
+
+// =============================================================================
+// Snapshot tests
+// =============================================================================
+
+#[cfg(all(test, feature = "std"))]
+mod snapshot_tests {
+    use super::*;
+    use crate::{SnapshotError, SnapshotReader, SnapshotWriter, SNAPSHOT_MAGIC};
+    use std::io::ErrorKind;
 
-            assert!(tree.contains(2, root));
-            assert_eq!(tree.get_size(root), 7);
+    fn tree_snapshot(count: usize) -> (TestTree, usize, Vec<u8>) {
+        let mut tree = TestTree::new(count);
+        let mut root = 0;
+        unsafe {
+            for i in 1..=count {
+                tree.attach(&mut root, (i * 37) % count + 1);
+            }
         }
+        let bytes =
+            unsafe { SnapshotWriter::write_tree_storage(Vec::new(), &tree, count, &[root, 0]) }
+                .unwrap();
+        (tree, root, bytes)
     }
 
     #[test]
-    fn test_attach_special_case_left_right_empty() {
-        // Tests the special case in attach_core where left_right_size == 0 and right_size == 0
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+    fn test_tree_storage_round_trip() {
+        let count = 5000;
+        let (tree, root, bytes) = tree_snapshot(count);
+        // Header, two node blocks and the root table, each with a checksum.
+        assert_eq!(
+            bytes.len(),
+            (28 + 4) + (count * 3 * 8 + 2 * 4) + (2 * 8 + 4)
+        );
+
+        let mut restored = TestTree::new(count);
+        let roots =
+            unsafe { SnapshotReader::read_tree_storage(&bytes[..], &mut restored, count) }.unwrap();
+        assert_eq!(roots, vec![root, 0]);
+        assert_eq!(restored.nodes, tree.nodes);
+
+        let again =
+            unsafe { SnapshotWriter::write_tree_storage(Vec::new(), &restored, count, &roots) }
+                .unwrap();
+        assert_eq!(again, bytes);
+    }
 
-        unsafe {
-            // Create specific tree structure
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            // Now insert 7 which is right of 5 but left of 10
-            // With just 2 nodes, this triggers the special case
-            tree.attach(&mut root, 7);
+    #[test]
+    fn test_layout_is_little_endian() {
+        let mut writer = SnapshotWriter::<_, u16>::new(Vec::new(), 2, 1, 1).unwrap();
+        writer.write_node(&[0x0102, 0x0304]).unwrap();
+        let bytes = writer.finish(&[0x0506]).unwrap();
 
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(5, root));
-            assert!(tree.contains(7, root));
+        assert_eq!(bytes[0..8], SNAPSHOT_MAGIC);
+        assert_eq!(bytes[8..12], [1, 0, 2, 2]);
+        assert_eq!(bytes[12..20], 1u64.to_le_bytes());
+        assert_eq!(bytes[20..24], 1u32.to_le_bytes());
+        assert_eq!(bytes[24..28], 4096u32.to_le_bytes());
+        assert_eq!(bytes[32..36], [0x02, 0x01, 0x04, 0x03]);
+        assert_eq!(bytes[40..42], [0x06, 0x05]);
+        assert_eq!(bytes.len(), 46);
+    }
+
+    #[test]
+    fn test_streaming_records() {
+        let mut writer = SnapshotWriter::<_, u32>::new(Vec::new(), 5, 10, 0).unwrap();
+        assert_eq!(writer.header().fields, 5);
+        for node in 0..10u32 {
+            writer
+                .write_node(&[node, node + 1, node * 2, u32::MAX - node, 7])
+                .unwrap();
+        }
+        let bytes = writer.finish(&[]).unwrap();
+
+        let mut reader = SnapshotReader::<_, u32>::new(&bytes[..]).unwrap();
+        assert_eq!(reader.header().node_count, 10);
+        let mut fields = [0; 5];
+        for node in 0..10u32 {
+            assert!(reader.read_node(&mut fields).unwrap());
+            assert_eq!(fields, [node, node + 1, node * 2, u32::MAX - node, 7]);
         }
+        assert!(!reader.read_node(&mut fields).unwrap());
+        assert!(reader.finish().unwrap().is_empty());
     }
 
     #[test]
-    fn test_attach_right_left_rotation_edge_case() {
-        // Tests the path where we need a right-left double rotation on right side
-        let mut tree = TestTree::new(30);
-        let mut root: usize = 0;
+    fn test_corruption_is_detected_per_block() {
+        let (_, _, bytes) = tree_snapshot(5000);
+        let second_block = 32 + 4096 * 24 + 4;
+        for (offset, block) in [
+            (3, 0),
+            (100, 1),
+            (second_block + 5, 2),
+            (bytes.len() - 6, 3),
+        ] {
+            let mut corrupted = bytes.clone();
+            corrupted[offset] ^= 0x40;
+            let mut restored = TestTree::new(5000);
+            let error =
+                unsafe { SnapshotReader::read_tree_storage(&corrupted[..], &mut restored, 5000) }
+                    .unwrap_err();
+            let expected = if block == 0 {
+                matches!(error, SnapshotError::BadMagic)
+            } else {
+                matches!(error, SnapshotError::Checksum { block: found } if found == block)
+            };
+            assert!(expected, "offset {}: {:?}", offset, error);
+        }
 
-        unsafe {
-            // Build a tree that will trigger right-left rotation
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 20);
-            tree.attach(&mut root, 15); // Between 10 and 20, should trigger RL rotation
+        let mut corrupted = bytes.clone();
+        corrupted[9] ^= 1;
+        assert!(matches!(
+            SnapshotReader::<_, usize>::new(&corrupted[..]),
+            Err(SnapshotError::Checksum { block: 0 })
+        ));
+    }
 
-            assert!(tree.contains(10, root));
-            assert!(tree.contains(15, root));
-            assert!(tree.contains(20, root));
-            assert_eq!(tree.get_size(root), 3);
+    #[test]
+    fn test_reader_errors() {
+        let (_, _, bytes) = tree_snapshot(10);
+        assert!(matches!(
+            SnapshotReader::<_, u32>::new(&bytes[..]),
+            Err(SnapshotError::LinkWidth {
+                expected: 4,
+                found: 8
+            })
+        ));
+
+        let mut reader = SnapshotReader::<_, usize>::new(&bytes[..]).unwrap();
+        assert!(matches!(
+            reader.read_node(&mut [0; 2]),
+            Err(SnapshotError::FieldCount {
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert_eq!(
+            reader.read_node(&mut [0; 2]).unwrap_err().to_string(),
+            "snapshot nodes have 3 links, 2 were given"
+        );
+
+        let truncated = &bytes[..bytes.len() - 1];
+        let error = SnapshotReader::<_, usize>::new(truncated)
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        assert!(
+            matches!(&error, SnapshotError::Io(error) if error.kind() == ErrorKind::UnexpectedEof)
+        );
+        assert!(std::error::Error::source(&error).is_some());
+    }
+
+    #[test]
+    fn test_restore_rejects_untrusted_ids() {
+        // More nodes than the storage holds: nothing is written.
+        let (_, _, bytes) = tree_snapshot(10);
+        let mut restored = TestTree::new(5);
+        let error =
+            unsafe { SnapshotReader::read_tree_storage(&bytes[..], &mut restored, 5) }.unwrap_err();
+        assert!(matches!(
+            error,
+            SnapshotError::Capacity {
+                capacity: 5,
+                found: 10
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "snapshot holds 10 nodes, storage has room for 5"
+        );
+        assert_eq!(restored.nodes, TestTree::new(5).nodes);
+
+        // Links past the last node, in a record or in the root table.
+        for (record, root) in [([3, 0, 1], 1), ([0, 0, 1], 7)] {
+            let mut writer = SnapshotWriter::<_, usize>::new(Vec::new(), 3, 2, 1).unwrap();
+            writer.write_node(&[0, 0, 1]).unwrap();
+            writer.write_node(&record).unwrap();
+            let bytes = writer.finish(&[root]).unwrap();
+            let mut restored = TestTree::new(10);
+            let error = unsafe { SnapshotReader::read_tree_storage(&bytes[..], &mut restored, 10) }
+                .unwrap_err();
+            let link = if root == 7 { 7 } else { 3 };
+            assert!(
+                matches!(error, SnapshotError::DanglingLink { link: found } if found == link),
+                "{:?}",
+                error
+            );
         }
     }
 
     #[test]
-    fn test_attach_descend_right_without_rotation() {
-        // Tests the case where we descend right without rotation
-        let mut tree = TestTree::new(40);
-        let mut root: usize = 0;
+    fn test_announced_counts_are_not_preallocated() {
+        // A valid header announcing far more roots than the stream holds.
+        let mut bytes = Vec::new();
+        drop(SnapshotWriter::<_, u64>::new(&mut bytes, 3, 0, u32::MAX).unwrap());
+        bytes.extend_from_slice(&[0; 64]);
+        let error = SnapshotReader::<_, u64>::new(&bytes[..])
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        assert!(
+            matches!(&error, SnapshotError::Io(error) if error.kind() == ErrorKind::UnexpectedEof)
+        );
+    }
 
-        unsafe {
-            // Build a balanced tree
-            tree.attach(&mut root, 20);
-            tree.attach(&mut root, 30);
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
-            // Insert on the right side, descending
-            tree.attach(&mut root, 35);
-            tree.attach(&mut root, 40);
+    #[test]
+    fn test_writer_counts() {
+        let mut writer = SnapshotWriter::<_, u64>::new(Vec::new(), 1, 1, 1).unwrap();
+        assert!(matches!(
+            writer.write_node(&[1, 2]),
+            Err(SnapshotError::FieldCount {
+                expected: 1,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            writer.write_node(&[0; 300]),
+            Err(SnapshotError::FieldCount {
+                expected: 1,
+                found: 255
+            })
+        ));
+        writer.write_node(&[1]).unwrap();
+        assert!(matches!(
+            writer.write_node(&[2]),
+            Err(SnapshotError::Count {
+                expected: 1,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            writer.finish(&[]),
+            Err(SnapshotError::Count {
+                expected: 1,
+                found: 0
+            })
+        ));
+
+        let writer = SnapshotWriter::<_, u64>::new(Vec::new(), 1, 3, 0).unwrap();
+        let error = writer.finish(&[]).unwrap_err();
+        assert_eq!(error.to_string(), "expected 3 records, found 0");
+    }
+}
 
-            assert!(tree.contains(40, root));
-            assert_eq!(tree.get_size(root), 7);
+// =============================================================================
+// Conformance suite tests
+// =============================================================================
+
+#[cfg(all(test, feature = "testing"))]
+mod testing_tests {
+    use super::*;
+    use crate::testing::{
+        check_absolute_circular_list, check_relative_circular_list, check_size_balanced_tree,
+        Conformance, ListOperation,
+    };
+
+    /// Absolute list that stops counting at two elements.
+    struct CappedList(TestAbsoluteList);
+
+    impl LinkedList<usize> for CappedList {
+        fn get_previous(&self, element: usize) -> usize {
+            self.0.get_previous(element)
+        }
+
+        fn get_next(&self, element: usize) -> usize {
+            self.0.get_next(element)
+        }
+
+        fn set_previous(&mut self, element: usize, previous: usize) {
+            self.0.set_previous(element, previous);
+        }
+
+        fn set_next(&mut self, element: usize, next: usize) {
+            self.0.set_next(element, next);
         }
     }
 
-    #[test]
-    fn test_detach_with_double_rotation_left() {
-        // Tests detach causing double rotation on left side
-        let mut tree = TestTree::new(40);
-        let mut root: usize = 0;
+    impl AbsoluteLinkedList<usize> for CappedList {
+        fn get_first(&self) -> usize {
+            self.0.first
+        }
 
-        unsafe {
-            // Build larger tree
-            for i in [20, 10, 30, 5, 15, 25, 35, 3, 8, 13, 17] {
-                tree.attach(&mut root, i);
-            }
+        fn get_last(&self) -> usize {
+            self.0.last
+        }
 
-            // Detach to trigger specific paths
-            tree.detach(&mut root, 3);
-            tree.detach(&mut root, 5);
-            tree.detach(&mut root, 8);
+        fn get_size(&self) -> usize {
+            self.0.size
+        }
 
-            assert!(!tree.contains(3, root));
-            assert!(!tree.contains(5, root));
-            assert!(!tree.contains(8, root));
+        fn set_first(&mut self, element: usize) {
+            self.0.first = element;
+        }
+
+        fn set_last(&mut self, element: usize) {
+            self.0.last = element;
+        }
+
+        fn set_size(&mut self, size: usize) {
+            self.0.size = size.min(2);
         }
     }
 
-    #[test]
-    fn test_detach_with_double_rotation_right() {
-        // Tests detach causing double rotation on right side
-        let mut tree = TestTree::new(50);
-        let mut root: usize = 0;
+    impl AbsoluteCircularLinkedList<usize> for CappedList {}
 
-        unsafe {
-            // Build larger tree
-            for i in [20, 10, 30, 5, 15, 25, 35, 32, 38, 28, 27] {
-                tree.attach(&mut root, i);
-            }
+    /// Tree whose attach links the new root to itself as its left child.
+    struct SelfLoopTree(TestTree);
 
-            // Detach to trigger specific paths
-            tree.detach(&mut root, 35);
-            tree.detach(&mut root, 38);
-            tree.detach(&mut root, 32);
+    impl RecursiveSizeBalancedTree<usize> for SelfLoopTree {
+        unsafe fn get_mut_left_reference(&mut self, node: usize) -> *mut usize {
+            self.0.get_mut_left_reference(node)
+        }
 
-            assert!(!tree.contains(35, root));
-            assert!(!tree.contains(38, root));
-            assert!(!tree.contains(32, root));
+        unsafe fn get_mut_right_reference(&mut self, node: usize) -> *mut usize {
+            self.0.get_mut_right_reference(node)
         }
-    }
 
-    #[test]
-    fn test_detach_with_left_only_child() {
-        // Tests detach where replacement comes from left subtree
-        let mut tree = TestTree::new(30);
-        let mut root: usize = 0;
+        unsafe fn get_left_reference(&self, node: usize) -> *const usize {
+            self.0.get_left_reference(node)
+        }
 
-        unsafe {
-            // Create tree where left subtree is larger
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
-            tree.attach(&mut root, 3);
-            tree.attach(&mut root, 7);
-            tree.attach(&mut root, 2);
-            tree.attach(&mut root, 4);
+        unsafe fn get_right_reference(&self, node: usize) -> *const usize {
+            self.0.get_right_reference(node)
+        }
 
-            // Detach a node that will use replacement from left (larger) subtree
-            tree.detach(&mut root, 5);
+        unsafe fn get_left(&self, node: usize) -> usize {
+            self.0.get_left(node)
+        }
 
-            assert!(!tree.contains(5, root));
-            assert!(tree.contains(3, root));
-            assert!(tree.contains(7, root));
+        unsafe fn get_right(&self, node: usize) -> usize {
+            self.0.get_right(node)
         }
-    }
 
-    #[test]
-    fn test_detach_node_with_left_child_only() {
-        // Tests the path where we set *root = *left
-        let mut tree = TestTree::new(20);
-        let mut root: usize = 0;
+        unsafe fn get_size(&self, node: usize) -> usize {
+            self.0.get_size(node)
+        }
 
-        unsafe {
-            tree.attach(&mut root, 10);
-            tree.attach(&mut root, 5);
-            tree.attach(&mut root, 15);
-            tree.attach(&mut root, 3);
+        unsafe fn set_left(&mut self, node: usize, left: usize) {
+            self.0.set_left(node, left);
+        }
 
-            // Remove 5, which has only left child (3)
-            tree.detach(&mut root, 5);
+        unsafe fn set_right(&mut self, node: usize, right: usize) {
+            self.0.set_right(node, right);
+        }
 
-            assert!(!tree.contains(5, root));
-            assert!(tree.contains(3, root));
+        unsafe fn set_size(&mut self, node: usize, size: usize) {
+            self.0.set_size(node, size);
         }
-    }
 
-    #[test]
-    fn test_large_sequential_insert() {
-        // Tests inserting many nodes in ascending order
-        let mut tree = TestTree::new(50);
-        let mut root: usize = 0;
+        unsafe fn first_is_to_the_left_of_second(&self, first: usize, second: usize) -> bool {
+            first < second
+        }
 
-        unsafe {
-            for i in 1..=20 {
-                tree.attach(&mut root, i);
-            }
+        unsafe fn first_is_to_the_right_of_second(&self, first: usize, second: usize) -> bool {
+            first > second
+        }
+    }
 
-            for i in 1..=20 {
-                assert!(tree.contains(i, root), "Node {} missing", i);
-            }
-            assert_eq!(tree.get_size(root), 20);
+    impl IterativeSizeBalancedTree<usize> for SelfLoopTree {
+        unsafe fn attach(&mut self, root: *mut usize, node: usize) {
+            IterativeSizeBalancedTree::attach(&mut self.0, root, node);
+            self.0.set_left(*root, *root);
         }
     }
 
     #[test]
-    fn test_large_reverse_insert() {
-        // Tests inserting many nodes in descending order
-        let mut tree = TestTree::new(50);
-        let mut root: usize = 0;
+    fn test_size_balanced_tree_conformance() {
+        let config = Conformance::default();
+        check_size_balanced_tree(config, || TestTree::new(config.capacity)).unwrap();
+    }
 
-        unsafe {
-            for i in (1..=20).rev() {
-                tree.attach(&mut root, i);
-            }
+    #[test]
+    fn test_absolute_circular_list_conformance() {
+        let config = Conformance::default();
+        check_absolute_circular_list(config, || TestAbsoluteList::new(config.capacity)).unwrap();
+    }
 
-            for i in 1..=20 {
-                assert!(tree.contains(i, root), "Node {} missing", i);
-            }
-            assert_eq!(tree.get_size(root), 20);
-        }
+    #[test]
+    fn test_relative_circular_list_conformance() {
+        let config = Conformance {
+            cases: 16,
+            ..Conformance::default()
+        };
+        check_relative_circular_list(config, 3, || TestRelativeList::new(config.capacity)).unwrap();
     }
 
     #[test]
-    fn test_alternating_insert() {
-        // Tests inserting nodes in alternating pattern
-        let mut tree = TestTree::new(50);
-        let mut root: usize = 0;
+    fn test_failure_is_shrunk() {
+        let config = Conformance::default();
+        let failure = check_absolute_circular_list(config, || {
+            CappedList(TestAbsoluteList::new(config.capacity))
+        })
+        .unwrap_err();
+        assert_eq!(failure.operations.len(), 3);
+        assert_eq!(failure.step, 2);
+        assert!(failure
+            .operations
+            .iter()
+            .all(|operation| !matches!(operation, ListOperation::Detach { .. })));
+        assert_eq!(failure.message, "size is 2, model has 3");
+        assert!(failure
+            .to_string()
+            .starts_with(&std::format!("seed {}: size is 2", failure.seed)));
+    }
 
-        unsafe {
-            // Insert 10, 1, 20, 2, 19, 3, 18, etc.
-            let mut low = 1;
-            let mut high = 20;
-            for i in 0..20 {
-                if i % 2 == 0 {
-                    tree.attach(&mut root, high);
-                    high -= 1;
-                } else {
-                    tree.attach(&mut root, low);
-                    low += 1;
-                }
-            }
+    #[test]
+    fn test_self_loop_is_reported() {
+        let config = Conformance::default();
+        let failure =
+            check_size_balanced_tree(config, || SelfLoopTree(TestTree::new(config.capacity)))
+                .unwrap_err();
+        assert_eq!(failure.operations.len(), 1);
+        assert_eq!(
+            failure.message,
+            "more nodes are reachable than the 1 attached"
+        );
+    }
 
-            for i in 1..=20 {
-                assert!(tree.contains(i, root), "Node {} missing", i);
-            }
-        }
+    #[test]
+    fn test_empty_config_passes() {
+        let config = Conformance {
+            capacity: 0,
+            ..Conformance::default()
+        };
+        check_size_balanced_tree(config, || TestTree::new(0)).unwrap();
+        check_absolute_circular_list(config, || TestAbsoluteList::new(0)).unwrap();
+        let config = Conformance::default();
+        check_relative_circular_list(config, 0, || TestRelativeList::new(config.capacity)).unwrap();
     }
 
     #[test]
-    fn test_detach_from_large_tree() {
-        // Tests various detach patterns on large tree
-        let mut tree = TestTree::new(100);
-        let mut root: usize = 0;
+    fn test_panic_is_reported() {
+        let config = Conformance {
+            capacity: 8,
+            ..Conformance::default()
+        };
+        // Nodes 5..=8 are out of bounds, so attaching any of them panics.
+        let failure = check_size_balanced_tree(config, || TestTree::new(4)).unwrap_err();
+        assert_eq!(failure.operations.len(), 1);
+        assert!(failure.message.starts_with("panicked: "));
+    }
+}
 
+// =============================================================================
+// FrozenTree tests
+// =============================================================================
+
+#[cfg(all(test, feature = "alloc"))]
+mod frozen_tree_tests {
+    use super::*;
+    use crate::FrozenTree;
+
+    /// Freezes a tree of the even nodes `2..=2 * count`, attached in a
+    /// scrambled order.
+    fn frozen_evens(count: usize) -> (TestTree, FrozenTree<usize>) {
+        let mut tree = TestTree::new(2 * count + 1);
+        let mut root = 0;
         unsafe {
-            // Build tree with 50 nodes
-            for i in 1..=50 {
-                tree.attach(&mut root, i);
+            for i in 1..=count {
+                tree.attach(&mut root, 2 * ((i * 37) % count + 1));
             }
+            let frozen = FrozenTree::freeze(&tree, root);
+            (tree, frozen)
+        }
+    }
 
-            // Detach every other node
-            for i in (1..=50).step_by(2) {
-                tree.detach(&mut root, i);
-            }
+    #[test]
+    fn test_layout() {
+        let frozen = FrozenTree::from_sorted(&[1usize, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(frozen.as_slice(), &[4, 2, 6, 1, 3, 5, 7]);
+        let frozen = FrozenTree::from_sorted(&[1usize, 2, 3, 4, 5]);
+        assert_eq!(frozen.as_slice(), &[4, 2, 5, 1, 3]);
+
+        let empty = FrozenTree::<usize>::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.iter().next(), None);
+        assert_eq!(empty.select(0), 0);
+        unsafe {
+            let tree = TestTree::new(1);
+            assert_eq!(FrozenTree::freeze(&tree, 0), empty);
+            assert_eq!(empty.lower_bound(&tree, 1), 0);
+            assert!(!empty.contains(&tree, 1));
+            assert_eq!(empty.rank(&tree, 1), 0);
+        }
+    }
 
-            // Check remaining nodes
-            for i in (2..=50).step_by(2) {
-                assert!(tree.contains(i, root), "Node {} should be present", i);
+    #[test]
+    fn test_queries_match_sorted_order() {
+        for count in [1, 2, 3, 7, 8, 9, 100] {
+            let (tree, frozen) = frozen_evens(count);
+            let sorted: Vec<usize> = (1..=count).map(|i| 2 * i).collect();
+            assert_eq!(frozen.len(), count);
+            assert_eq!(frozen.iter().len(), count);
+            assert_eq!(frozen.iter().collect::<Vec<_>>(), sorted);
+            for (index, &node) in sorted.iter().enumerate() {
+                assert_eq!(frozen.select(index), node);
             }
-            for i in (1..=49).step_by(2) {
-                assert!(!tree.contains(i, root), "Node {} should be absent", i);
+            assert_eq!(frozen.select(count), 0);
+            unsafe {
+                for probe in 1..=2 * count + 1 {
+                    let expected = sorted.iter().position(|&node| node >= probe);
+                    assert_eq!(
+                        frozen.lower_bound(&tree, probe),
+                        expected.map_or(0, |index| sorted[index])
+                    );
+                    assert_eq!(frozen.rank(&tree, probe), expected.unwrap_or(count));
+                    assert_eq!(frozen.contains(&tree, probe), probe % 2 == 0);
+                }
             }
         }
     }
+
+    #[test]
+    fn test_each_in_range() {
+        let (tree, frozen) = frozen_evens(50);
+        let mut seen = Vec::new();
+        unsafe {
+            let flow = frozen.each_in_range(&tree, 15, 31, |node| {
+                seen.push(node);
+                Flow::Continue
+            });
+            assert_eq!(flow, Flow::Continue);
+            assert_eq!(seen, vec![16, 18, 20, 22, 24, 26, 28, 30]);
+
+            seen.clear();
+            let flow = frozen.each_in_range(&tree, 96, 200, |node| {
+                seen.push(node);
+                Flow::Continue
+            });
+            assert_eq!(flow, Flow::Continue);
+            assert_eq!(seen, vec![96, 98, 100]);
+
+            seen.clear();
+            let flow = frozen.each_in_range(&tree, 1, 100, |node| {
+                seen.push(node);
+                if node == 6 {
+                    Flow::Break
+                } else {
+                    Flow::Continue
+                }
+            });
+            assert_eq!(flow, Flow::Break);
+            assert_eq!(seen, vec![2, 4, 6]);
+        }
+    }
 }
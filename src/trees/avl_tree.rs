@@ -0,0 +1,239 @@
+use super::child_links::{replace_child, ChildLinks};
+use crate::LinkType;
+
+/// Upper bound on the height of an AVL tree addressed by 64-bit ids
+/// (`1.4405 * log2(2^64 + 2)` rounded up with margin).
+const MAX_DEPTH: usize = 96;
+
+/// Height-balanced (AVL) binary tree over externally stored nodes.
+///
+/// Storages provide getters and setters for `left`, `right` and `height`
+/// (`1` for a leaf, `0` is never stored) plus the two comparison methods.
+/// `attach` and `detach` are iterative: the path from the root is kept in a
+/// fixed array on the stack, so they neither recurse nor allocate.
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`](crate::RecursiveSizeBalancedTree):
+/// node ids other than `0` must be valid nodes of the storage.
+#[allow(clippy::missing_safety_doc)]
+pub trait AvlTree<T: LinkType> {
+    unsafe fn get_left(&self, node: T) -> T;
+
+    unsafe fn get_right(&self, node: T) -> T;
+
+    unsafe fn get_height(&self, node: T) -> T;
+
+    unsafe fn set_left(&mut self, node: T, left: T);
+
+    unsafe fn set_right(&mut self, node: T, right: T);
+
+    unsafe fn set_height(&mut self, node: T, height: T);
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool;
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool;
+
+    unsafe fn get_height_or_zero(&self, node: T) -> T {
+        if node == T::funty(0) {
+            T::funty(0)
+        } else {
+            self.get_height(node)
+        }
+    }
+
+    unsafe fn fix_height(&mut self, node: T) {
+        let left_height = self.get_height_or_zero(self.get_left(node));
+        let right_height = self.get_height_or_zero(self.get_right(node));
+        let height = if left_height > right_height {
+            left_height
+        } else {
+            right_height
+        };
+        self.set_height(node, height + T::funty(1));
+    }
+
+    unsafe fn avl_left_rotate(&mut self, root: T) -> T {
+        let right = self.get_right(root);
+        self.set_right(root, self.get_left(right));
+        self.set_left(right, root);
+        self.fix_height(root);
+        self.fix_height(right);
+        right
+    }
+
+    unsafe fn avl_right_rotate(&mut self, root: T) -> T {
+        let left = self.get_left(root);
+        self.set_left(root, self.get_right(left));
+        self.set_right(left, root);
+        self.fix_height(root);
+        self.fix_height(left);
+        left
+    }
+
+    /// Restores the height invariant at `node`, whose subtrees are balanced
+    /// and differ in height by at most two, and returns the new subtree root.
+    unsafe fn rebalance(&mut self, node: T) -> T {
+        let left = self.get_left(node);
+        let right = self.get_right(node);
+        let left_height = self.get_height_or_zero(left);
+        let right_height = self.get_height_or_zero(right);
+        if left_height > right_height + T::funty(1) {
+            if self.get_height_or_zero(self.get_left(left))
+                < self.get_height_or_zero(self.get_right(left))
+            {
+                let rotated = self.avl_left_rotate(left);
+                self.set_left(node, rotated);
+            }
+            self.avl_right_rotate(node)
+        } else if right_height > left_height + T::funty(1) {
+            if self.get_height_or_zero(self.get_right(right))
+                < self.get_height_or_zero(self.get_left(right))
+            {
+                let rotated = self.avl_right_rotate(right);
+                self.set_right(node, rotated);
+            }
+            self.avl_left_rotate(node)
+        } else {
+            self.fix_height(node);
+            node
+        }
+    }
+
+    unsafe fn attach(&mut self, root: &mut T, node: T) {
+        self.set_left(node, T::funty(0));
+        self.set_right(node, T::funty(0));
+        self.set_height(node, T::funty(1));
+        if *root == T::funty(0) {
+            *root = node;
+            return;
+        }
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = *root;
+        loop {
+            path[depth] = current;
+            depth += 1;
+            if self.first_is_to_the_left_of_second(node, current) {
+                let left = self.get_left(current);
+                if left == T::funty(0) {
+                    self.set_left(current, node);
+                    break;
+                }
+                current = left;
+            } else {
+                let right = self.get_right(current);
+                if right == T::funty(0) {
+                    self.set_right(current, node);
+                    break;
+                }
+                current = right;
+            }
+        }
+        rebalance_path(self, root, &path[..depth]);
+    }
+
+    unsafe fn detach(&mut self, root: &mut T, node: T) {
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = *root;
+        while current != node {
+            path[depth] = current;
+            depth += 1;
+            current = if self.first_is_to_the_left_of_second(node, current) {
+                self.get_left(current)
+            } else {
+                self.get_right(current)
+            };
+        }
+        let left = self.get_left(node);
+        let right = self.get_right(node);
+        if left != T::funty(0) && right != T::funty(0) {
+            let node_depth = depth;
+            path[depth] = node;
+            depth += 1;
+            let mut successor = right;
+            while self.get_left(successor) != T::funty(0) {
+                path[depth] = successor;
+                depth += 1;
+                successor = self.get_left(successor);
+            }
+            let successor_parent = path[depth - 1];
+            if successor_parent == node {
+                self.set_right(node, self.get_right(successor));
+            } else {
+                self.set_left(successor_parent, self.get_right(successor));
+            }
+            self.set_left(successor, left);
+            self.set_right(successor, self.get_right(node));
+            self.set_height(successor, self.get_height(node));
+            path[node_depth] = successor;
+            replace_child(
+                &mut AvlLinks(self),
+                root,
+                &path[..node_depth],
+                node,
+                successor,
+            );
+        } else {
+            let child = if left != T::funty(0) { left } else { right };
+            replace_child(&mut AvlLinks(self), root, &path[..depth], node, child);
+        }
+        rebalance_path(self, root, &path[..depth]);
+        self.set_left(node, T::funty(0));
+        self.set_right(node, T::funty(0));
+        self.set_height(node, T::funty(0));
+    }
+
+    unsafe fn contains(&self, node: T, mut root: T) -> bool {
+        while root != T::funty(0) {
+            if self.first_is_to_the_left_of_second(node, root) {
+                root = self.get_left(root);
+            } else if self.first_is_to_the_right_of_second(node, root) {
+                root = self.get_right(root);
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// [`ChildLinks`] of an AVL storage, whose trait is separate from
+/// [`RecursiveSizeBalancedTree`](crate::RecursiveSizeBalancedTree).
+struct AvlLinks<'a, S: ?Sized>(&'a mut S);
+
+impl<'a, T: LinkType, S: AvlTree<T> + ?Sized> ChildLinks<T> for AvlLinks<'a, S> {
+    unsafe fn left_of(&self, node: T) -> T {
+        self.0.get_left(node)
+    }
+
+    unsafe fn set_left_of(&mut self, node: T, left: T) {
+        self.0.set_left(node, left);
+    }
+
+    unsafe fn set_right_of(&mut self, node: T, right: T) {
+        self.0.set_right(node, right);
+    }
+}
+
+/// Rebalances every node of `path` from the deepest one up to the root.
+unsafe fn rebalance_path<T: LinkType, S: AvlTree<T> + ?Sized>(
+    storage: &mut S,
+    root: &mut T,
+    path: &[T],
+) {
+    for depth in (0..path.len()).rev() {
+        let node = path[depth];
+        let balanced = storage.rebalance(node);
+        if balanced != node {
+            replace_child(
+                &mut AvlLinks(&mut *storage),
+                root,
+                &path[..depth],
+                node,
+                balanced,
+            );
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{Flow, LinkType};
+
+/// B+ tree over pages addressed by link ids, for disk-resident indexes.
+///
+/// Storages provide page accessors (leaf flag, key count, keys, children and
+/// the next-leaf link), page allocation and the two key comparisons. Every
+/// key lives in a leaf and leaves are chained in key order, so range scans
+/// walk the chain instead of the tree. Pages hold at most `get_max_keys()`
+/// keys (at least `3`); internal pages hold one more child than keys, and
+/// every page except the root keeps at least `(max_keys - 1) / 2` keys.
+///
+/// `insert` and `delete` split, borrow and merge pages on the way down, so
+/// they neither recurse nor keep a path.
+///
+/// # Safety
+///
+/// Page ids other than `0` must be valid pages of the storage, and key and
+/// child indices are only passed within the bounds described above.
+#[allow(clippy::missing_safety_doc)]
+pub trait BPlusTree<T: LinkType> {
+    fn get_max_keys(&self) -> usize;
+
+    unsafe fn is_leaf(&self, page: T) -> bool;
+
+    unsafe fn set_leaf(&mut self, page: T, leaf: bool);
+
+    unsafe fn get_key_count(&self, page: T) -> usize;
+
+    unsafe fn set_key_count(&mut self, page: T, count: usize);
+
+    unsafe fn get_key(&self, page: T, index: usize) -> T;
+
+    unsafe fn set_key(&mut self, page: T, index: usize, key: T);
+
+    unsafe fn get_child(&self, page: T, index: usize) -> T;
+
+    unsafe fn set_child(&mut self, page: T, index: usize, child: T);
+
+    unsafe fn get_next_leaf(&self, page: T) -> T;
+
+    unsafe fn set_next_leaf(&mut self, page: T, next: T);
+
+    unsafe fn allocate_page(&mut self) -> T;
+
+    unsafe fn free_page(&mut self, page: T);
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool;
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool;
+
+    fn get_min_keys(&self) -> usize {
+        (self.get_max_keys() - 1) / 2
+    }
+
+    /// Index of the first key of `page` that is not to the left of `key`.
+    unsafe fn lower_bound(&self, page: T, key: T) -> usize {
+        let mut low = 0;
+        let mut high = self.get_key_count(page);
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.first_is_to_the_left_of_second(self.get_key(page, middle), key) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    /// Index of the first key of `page` that is to the right of `key`, which
+    /// is also the index of the child to descend into.
+    unsafe fn upper_bound(&self, page: T, key: T) -> usize {
+        let mut low = 0;
+        let mut high = self.get_key_count(page);
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.first_is_to_the_right_of_second(self.get_key(page, middle), key) {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+        low
+    }
+
+    /// Returns the leaf whose key range covers `key`, or `0` for an empty tree.
+    unsafe fn find_leaf(&self, root: T, key: T) -> T {
+        let mut page = root;
+        while page != T::funty(0) && !self.is_leaf(page) {
+            page = self.get_child(page, self.upper_bound(page, key));
+        }
+        page
+    }
+
+    unsafe fn search(&self, root: T, key: T) -> bool {
+        let leaf = self.find_leaf(root, key);
+        if leaf == T::funty(0) {
+            return false;
+        }
+        let index = self.lower_bound(leaf, key);
+        index < self.get_key_count(leaf)
+            && !self.first_is_to_the_right_of_second(self.get_key(leaf, index), key)
+    }
+
+    /// Calls `handler` with every key from `from` to `to` inclusive, in order.
+    unsafe fn each_in_range<H: FnMut(T) -> Flow>(
+        &self,
+        root: T,
+        from: T,
+        to: T,
+        mut handler: H,
+    ) -> Flow {
+        let mut leaf = self.find_leaf(root, from);
+        let mut index = if leaf == T::funty(0) {
+            0
+        } else {
+            self.lower_bound(leaf, from)
+        };
+        while leaf != T::funty(0) {
+            while index < self.get_key_count(leaf) {
+                let key = self.get_key(leaf, index);
+                if self.first_is_to_the_right_of_second(key, to) {
+                    return Flow::Continue;
+                }
+                if handler(key).is_break() {
+                    return Flow::Break;
+                }
+                index += 1;
+            }
+            leaf = self.get_next_leaf(leaf);
+            index = 0;
+        }
+        Flow::Continue
+    }
+
+    /// Inserts `key`, returning `false` if an equal key is already present.
+    unsafe fn insert(&mut self, root: &mut T, key: T) -> bool {
+        if *root == T::funty(0) {
+            let page = self.allocate_page();
+            self.set_leaf(page, true);
+            self.set_key_count(page, 1);
+            self.set_key(page, 0, key);
+            self.set_next_leaf(page, T::funty(0));
+            *root = page;
+            return true;
+        }
+        if self.get_key_count(*root) == self.get_max_keys() {
+            let page = self.allocate_page();
+            self.set_leaf(page, false);
+            self.set_key_count(page, 0);
+            self.set_child(page, 0, *root);
+            self.split_child(page, 0);
+            *root = page;
+        }
+        let mut page = *root;
+        while !self.is_leaf(page) {
+            let mut index = self.upper_bound(page, key);
+            if self.get_key_count(self.get_child(page, index)) == self.get_max_keys() {
+                self.split_child(page, index);
+                if !self.first_is_to_the_left_of_second(key, self.get_key(page, index)) {
+                    index += 1;
+                }
+            }
+            page = self.get_child(page, index);
+        }
+        let index = self.lower_bound(page, key);
+        let count = self.get_key_count(page);
+        if index < count && !self.first_is_to_the_right_of_second(self.get_key(page, index), key) {
+            return false;
+        }
+        for i in (index..count).rev() {
+            self.set_key(page, i + 1, self.get_key(page, i));
+        }
+        self.set_key(page, index, key);
+        self.set_key_count(page, count + 1);
+        true
+    }
+
+    /// Deletes `key`, returning `false` if it is not present.
+    unsafe fn delete(&mut self, root: &mut T, key: T) -> bool {
+        if *root == T::funty(0) {
+            return false;
+        }
+        let mut page = *root;
+        while !self.is_leaf(page) {
+            let mut index = self.upper_bound(page, key);
+            if self.get_key_count(self.get_child(page, index)) <= self.get_min_keys() {
+                index = self.fill_child(page, index);
+            }
+            let child = self.get_child(page, index);
+            if page == *root && self.get_key_count(page) == 0 {
+                self.free_page(page);
+                *root = child;
+            }
+            page = child;
+        }
+        let index = self.lower_bound(page, key);
+        let count = self.get_key_count(page);
+        if index == count || self.first_is_to_the_right_of_second(self.get_key(page, index), key) {
+            return false;
+        }
+        for i in index + 1..count {
+            self.set_key(page, i - 1, self.get_key(page, i));
+        }
+        self.set_key_count(page, count - 1);
+        if count == 1 && page == *root {
+            self.free_page(page);
+            *root = T::funty(0);
+        }
+        true
+    }
+
+    /// Splits the full child at `index` of `parent`, which must not be full.
+    unsafe fn split_child(&mut self, parent: T, index: usize) {
+        let child = self.get_child(parent, index);
+        let count = self.get_key_count(child);
+        let sibling = self.allocate_page();
+        let leaf = self.is_leaf(child);
+        self.set_leaf(sibling, leaf);
+        let separator;
+        if leaf {
+            let keep = count / 2;
+            for i in keep..count {
+                self.set_key(sibling, i - keep, self.get_key(child, i));
+            }
+            self.set_key_count(sibling, count - keep);
+            self.set_key_count(child, keep);
+            self.set_next_leaf(sibling, self.get_next_leaf(child));
+            self.set_next_leaf(child, sibling);
+            separator = self.get_key(sibling, 0);
+        } else {
+            let keep = count / 2;
+            separator = self.get_key(child, keep);
+            for i in keep + 1..count {
+                self.set_key(sibling, i - keep - 1, self.get_key(child, i));
+            }
+            for i in keep + 1..=count {
+                self.set_child(sibling, i - keep - 1, self.get_child(child, i));
+            }
+            self.set_key_count(sibling, count - keep - 1);
+            self.set_key_count(child, keep);
+        }
+        let parent_count = self.get_key_count(parent);
+        for i in (index..parent_count).rev() {
+            self.set_key(parent, i + 1, self.get_key(parent, i));
+            self.set_child(parent, i + 2, self.get_child(parent, i + 1));
+        }
+        self.set_key(parent, index, separator);
+        self.set_child(parent, index + 1, sibling);
+        self.set_key_count(parent, parent_count + 1);
+    }
+
+    /// Gives the child at `index` of `parent` more than the minimum number of
+    /// keys by borrowing from or merging with a sibling, and returns the
+    /// index of the child that now covers its keys.
+    unsafe fn fill_child(&mut self, parent: T, index: usize) -> usize {
+        let min_keys = self.get_min_keys();
+        if index > 0 && self.get_key_count(self.get_child(parent, index - 1)) > min_keys {
+            self.borrow_from_left(parent, index);
+            index
+        } else if index < self.get_key_count(parent)
+            && self.get_key_count(self.get_child(parent, index + 1)) > min_keys
+        {
+            self.borrow_from_right(parent, index);
+            index
+        } else if index < self.get_key_count(parent) {
+            self.merge_children(parent, index);
+            index
+        } else {
+            self.merge_children(parent, index - 1);
+            index - 1
+        }
+    }
+
+    unsafe fn borrow_from_left(&mut self, parent: T, index: usize) {
+        let child = self.get_child(parent, index);
+        let left = self.get_child(parent, index - 1);
+        let count = self.get_key_count(child);
+        let left_count = self.get_key_count(left);
+        for i in (0..count).rev() {
+            self.set_key(child, i + 1, self.get_key(child, i));
+        }
+        if self.is_leaf(child) {
+            self.set_key(child, 0, self.get_key(left, left_count - 1));
+            self.set_key(parent, index - 1, self.get_key(child, 0));
+        } else {
+            for i in (0..=count).rev() {
+                self.set_child(child, i + 1, self.get_child(child, i));
+            }
+            self.set_key(child, 0, self.get_key(parent, index - 1));
+            self.set_child(child, 0, self.get_child(left, left_count));
+            self.set_key(parent, index - 1, self.get_key(left, left_count - 1));
+        }
+        self.set_key_count(child, count + 1);
+        self.set_key_count(left, left_count - 1);
+    }
+
+    unsafe fn borrow_from_right(&mut self, parent: T, index: usize) {
+        let child = self.get_child(parent, index);
+        let right = self.get_child(parent, index + 1);
+        let count = self.get_key_count(child);
+        let right_count = self.get_key_count(right);
+        if self.is_leaf(child) {
+            self.set_key(child, count, self.get_key(right, 0));
+            self.set_key(parent, index, self.get_key(right, 1));
+        } else {
+            self.set_key(child, count, self.get_key(parent, index));
+            self.set_child(child, count + 1, self.get_child(right, 0));
+            self.set_key(parent, index, self.get_key(right, 0));
+            for i in 1..=right_count {
+                self.set_child(right, i - 1, self.get_child(right, i));
+            }
+        }
+        for i in 1..right_count {
+            self.set_key(right, i - 1, self.get_key(right, i));
+        }
+        self.set_key_count(child, count + 1);
+        self.set_key_count(right, right_count - 1);
+    }
+
+    /// Merges the child at `index + 1` of `parent` into the child at `index`
+    /// and frees it.
+    unsafe fn merge_children(&mut self, parent: T, index: usize) {
+        let left = self.get_child(parent, index);
+        let right = self.get_child(parent, index + 1);
+        let mut count = self.get_key_count(left);
+        let right_count = self.get_key_count(right);
+        if self.is_leaf(left) {
+            self.set_next_leaf(left, self.get_next_leaf(right));
+        } else {
+            self.set_key(left, count, self.get_key(parent, index));
+            count += 1;
+            for i in 0..=right_count {
+                self.set_child(left, count + i, self.get_child(right, i));
+            }
+        }
+        for i in 0..right_count {
+            self.set_key(left, count + i, self.get_key(right, i));
+        }
+        self.set_key_count(left, count + right_count);
+        let parent_count = self.get_key_count(parent);
+        for i in index + 1..parent_count {
+            self.set_key(parent, i - 1, self.get_key(parent, i));
+            self.set_child(parent, i, self.get_child(parent, i + 1));
+        }
+        self.set_key_count(parent, parent_count - 1);
+        self.free_page(right);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{IterativeSizeBalancedTree, LinkType};
+use core::fmt;
+
+/// Error returned by [`CheckedTree`] when a node id cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeError<T> {
+    /// The null node (`0`) was passed where a real node is required.
+    NullNode,
+    /// The node id is greater than the declared capacity.
+    OutOfBounds { node: T, capacity: T },
+}
+
+impl<T: fmt::Display> fmt::Display for TreeError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::NullNode => write!(f, "null node cannot be used as a tree node"),
+            TreeError::OutOfBounds { node, capacity } => {
+                write!(
+                    f,
+                    "node {} is out of bounds (capacity is {})",
+                    node, capacity
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug + fmt::Display> std::error::Error for TreeError<T> {}
+
+/// Safe facade over an [`IterativeSizeBalancedTree`] implementor.
+///
+/// Owns the root of a single tree, borrows the node storage and checks every
+/// node id against the capacity declared at construction.
+pub struct CheckedTree<'a, T, S> {
+    storage: &'a mut S,
+    root: T,
+    capacity: T,
+}
+
+impl<'a, T: LinkType, S: IterativeSizeBalancedTree<T>> CheckedTree<'a, T, S> {
+    /// Creates an empty tree over `storage`.
+    ///
+    /// # Safety
+    ///
+    /// Every id in `1..=capacity` must be a valid node of `storage`, the
+    /// comparison methods of `storage` must define a total order on those
+    /// nodes, and none of them may be used by another tree while this one
+    /// is alive.
+    pub unsafe fn new(storage: &'a mut S, capacity: T) -> Self {
+        Self::with_root(storage, capacity, T::funty(0))
+    }
+
+    /// Resumes a tree that was previously built over `storage`.
+    ///
+    /// # Safety
+    ///
+    /// Same requirements as [`CheckedTree::new`]; additionally `root` must be
+    /// `0` or the root of a well-formed tree made only of nodes in
+    /// `1..=capacity`.
+    pub unsafe fn with_root(storage: &'a mut S, capacity: T, root: T) -> Self {
+        Self {
+            storage,
+            root,
+            capacity,
+        }
+    }
+
+    pub fn root(&self) -> T {
+        self.root
+    }
+
+    pub fn capacity(&self) -> T {
+        self.capacity
+    }
+
+    pub fn len(&self) -> T {
+        unsafe { self.storage.get_size_or_zero(self.root) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == T::funty(0)
+    }
+
+    /// Attaches `node`, returning `false` if an equal node is already present.
+    pub fn insert(&mut self, node: T) -> Result<bool, TreeError<T>> {
+        self.check(node)?;
+        if self.find(node) != T::funty(0) {
+            return Ok(false);
+        }
+        unsafe {
+            self.storage.clear_node(node);
+            self.storage.attach(&mut self.root, node);
+        }
+        Ok(true)
+    }
+
+    /// Detaches `node`, returning `false` if it is not part of the tree.
+    pub fn remove(&mut self, node: T) -> Result<bool, TreeError<T>> {
+        self.check(node)?;
+        if self.find(node) != node {
+            return Ok(false);
+        }
+        unsafe {
+            self.storage.detach(&mut self.root, node);
+        }
+        Ok(true)
+    }
+
+    pub fn contains(&self, node: T) -> Result<bool, TreeError<T>> {
+        self.check(node)?;
+        Ok(self.find(node) == node)
+    }
+
+    pub fn first(&self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(unsafe { self.storage.get_leftest(self.root) })
+        }
+    }
+
+    pub fn last(&self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(unsafe { self.storage.get_rightest(self.root) })
+        }
+    }
+
+    /// Iterates over the nodes in order, from leftest to rightest.
+    pub fn iter(&self) -> CheckedTreeIter<'_, T, S> {
+        CheckedTreeIter {
+            storage: self.storage,
+            root: self.root,
+            current: self.first().unwrap_or_else(|| T::funty(0)),
+            remaining: self.len(),
+        }
+    }
+
+    /// Releases the storage and returns the root so the tree can be resumed
+    /// later with [`CheckedTree::with_root`].
+    pub fn into_root(self) -> T {
+        self.root
+    }
+
+    fn check(&self, node: T) -> Result<(), TreeError<T>> {
+        if node == T::funty(0) {
+            Err(TreeError::NullNode)
+        } else if node > self.capacity {
+            Err(TreeError::OutOfBounds {
+                node,
+                capacity: self.capacity,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn find(&self, node: T) -> T {
+        let mut current = self.root;
+        unsafe {
+            while current != T::funty(0) {
+                if self.storage.first_is_to_the_left_of_second(node, current) {
+                    current = self.storage.get_left(current);
+                } else if self.storage.first_is_to_the_right_of_second(node, current) {
+                    current = self.storage.get_right(current);
+                } else {
+                    break;
+                }
+            }
+        }
+        current
+    }
+}
+
+/// In-order iterator over the nodes of a [`CheckedTree`].
+pub struct CheckedTreeIter<'a, T, S> {
+    storage: &'a S,
+    root: T,
+    current: T,
+    remaining: T,
+}
+
+impl<'a, T: LinkType, S: IterativeSizeBalancedTree<T>> Iterator for CheckedTreeIter<'a, T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.current == T::funty(0) {
+            return None;
+        }
+        let node = self.current;
+        let mut successor = T::funty(0);
+        let mut current = self.root;
+        unsafe {
+            while current != T::funty(0) {
+                if self.storage.first_is_to_the_left_of_second(node, current) {
+                    successor = current;
+                    current = self.storage.get_left(current);
+                } else {
+                    current = self.storage.get_right(current);
+                }
+            }
+        }
+        self.current = successor;
+        self.remaining -= T::funty(1);
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining.as_usize();
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: LinkType, S: IterativeSizeBalancedTree<T>> ExactSizeIterator
+    for CheckedTreeIter<'a, T, S>
+{
+}
@@ -0,0 +1,49 @@
+use crate::{LinkType, RecursiveSizeBalancedTree};
+
+/// Child links of a binary tree node, shared by the helpers of tree traits
+/// whose storages name their fields differently.
+pub(crate) trait ChildLinks<T> {
+    unsafe fn left_of(&self, node: T) -> T;
+
+    unsafe fn set_left_of(&mut self, node: T, left: T);
+
+    unsafe fn set_right_of(&mut self, node: T, right: T);
+}
+
+/// [`ChildLinks`] of a [`RecursiveSizeBalancedTree`] storage.
+pub(crate) struct TreeLinks<'a, S: ?Sized>(pub(crate) &'a mut S);
+
+impl<'a, T: LinkType, S: RecursiveSizeBalancedTree<T> + ?Sized> ChildLinks<T> for TreeLinks<'a, S> {
+    unsafe fn left_of(&self, node: T) -> T {
+        self.0.get_left(node)
+    }
+
+    unsafe fn set_left_of(&mut self, node: T, left: T) {
+        self.0.set_left(node, left);
+    }
+
+    unsafe fn set_right_of(&mut self, node: T, right: T) {
+        self.0.set_right(node, right);
+    }
+}
+
+/// Points the parent of `old` (the last node of `ancestors`, or the root when
+/// there is none) at `new`.
+pub(crate) unsafe fn replace_child<T: LinkType, S: ChildLinks<T> + ?Sized>(
+    storage: &mut S,
+    root: &mut T,
+    ancestors: &[T],
+    old: T,
+    new: T,
+) {
+    match ancestors.last() {
+        None => *root = new,
+        Some(&parent) => {
+            if storage.left_of(parent) == old {
+                storage.set_left_of(parent, new);
+            } else {
+                storage.set_right_of(parent, new);
+            }
+        }
+    }
+}
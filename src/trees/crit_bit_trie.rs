@@ -0,0 +1,252 @@
+use crate::{Flow, LinkType};
+
+/// Child slot of a [`CritBitTrie`] node, and the root of a trie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CritBitChild<T> {
+    Empty,
+    /// A stored key.
+    Leaf(T),
+    /// An internal node id.
+    Node(T),
+}
+
+/// Crit-bit (Patricia) trie over integer keys such as link ids.
+///
+/// Keys are compared bit by bit instead of through comparison methods:
+/// every internal node stores the highest bit in which its two subtrees
+/// differ, so a lookup reads at most one node per distinct key bit and an
+/// in-order walk yields keys in ascending order. Internal nodes are
+/// allocated from the storage; keys themselves are stored only in child
+/// slots, so they need no node of their own.
+pub trait CritBitTrie<T: LinkType> {
+    /// Returns the left (`right == false`) or right child of `node`.
+    fn get_child(&self, node: T, right: bool) -> CritBitChild<T>;
+
+    fn set_child(&mut self, node: T, right: bool, child: CritBitChild<T>);
+
+    fn get_crit_bit(&self, node: T) -> u32;
+
+    fn set_crit_bit(&mut self, node: T, bit: u32);
+
+    fn allocate_node(&mut self) -> T;
+
+    fn free_node(&mut self, node: T);
+
+    /// Returns the leaf a lookup for `key` ends at, which shares the longest
+    /// prefix with `key` among the leaves on its path.
+    fn get_best_leaf(&self, root: CritBitChild<T>, key: T) -> Option<T> {
+        let mut current = root;
+        loop {
+            match current {
+                CritBitChild::Empty => return None,
+                CritBitChild::Leaf(leaf) => return Some(leaf),
+                CritBitChild::Node(node) => {
+                    current = self.get_child(node, bit_is_set(key, self.get_crit_bit(node)));
+                }
+            }
+        }
+    }
+
+    fn lookup(&self, root: CritBitChild<T>, key: T) -> bool {
+        self.get_best_leaf(root, key) == Some(key)
+    }
+
+    /// Inserts `key`, returning `false` if it is already present.
+    fn insert(&mut self, root: &mut CritBitChild<T>, key: T) -> bool {
+        let best = match self.get_best_leaf(*root, key) {
+            None => {
+                *root = CritBitChild::Leaf(key);
+                return true;
+            }
+            Some(best) if best == key => return false,
+            Some(best) => best,
+        };
+        let bit = highest_bit(best ^ key);
+        let right = bit_is_set(key, bit);
+
+        let mut parent = None;
+        let mut current = *root;
+        while let CritBitChild::Node(node) = current {
+            let crit_bit = self.get_crit_bit(node);
+            if crit_bit < bit {
+                break;
+            }
+            let direction = bit_is_set(key, crit_bit);
+            parent = Some((node, direction));
+            current = self.get_child(node, direction);
+        }
+
+        let node = self.allocate_node();
+        self.set_crit_bit(node, bit);
+        self.set_child(node, right, CritBitChild::Leaf(key));
+        self.set_child(node, !right, current);
+        match parent {
+            None => *root = CritBitChild::Node(node),
+            Some((parent, direction)) => {
+                self.set_child(parent, direction, CritBitChild::Node(node))
+            }
+        }
+        true
+    }
+
+    /// Removes `key`, returning `false` if it is not present.
+    fn remove(&mut self, root: &mut CritBitChild<T>, key: T) -> bool {
+        let mut grandparent = None;
+        let mut parent = None;
+        let mut current = *root;
+        while let CritBitChild::Node(node) = current {
+            let direction = bit_is_set(key, self.get_crit_bit(node));
+            grandparent = parent;
+            parent = Some((node, direction));
+            current = self.get_child(node, direction);
+        }
+        if current != CritBitChild::Leaf(key) {
+            return false;
+        }
+        match parent {
+            None => *root = CritBitChild::Empty,
+            Some((parent, direction)) => {
+                let sibling = self.get_child(parent, !direction);
+                match grandparent {
+                    None => *root = sibling,
+                    Some((grandparent, direction)) => {
+                        self.set_child(grandparent, direction, sibling)
+                    }
+                }
+                self.free_node(parent);
+            }
+        }
+        true
+    }
+
+    fn get_min(&self, mut current: CritBitChild<T>) -> Option<T> {
+        loop {
+            match current {
+                CritBitChild::Empty => return None,
+                CritBitChild::Leaf(leaf) => return Some(leaf),
+                CritBitChild::Node(node) => current = self.get_child(node, false),
+            }
+        }
+    }
+
+    fn get_max(&self, mut current: CritBitChild<T>) -> Option<T> {
+        loop {
+            match current {
+                CritBitChild::Empty => return None,
+                CritBitChild::Leaf(leaf) => return Some(leaf),
+                CritBitChild::Node(node) => current = self.get_child(node, true),
+            }
+        }
+    }
+
+    /// Returns the smallest key greater than `key`.
+    fn get_successor(&self, root: CritBitChild<T>, key: T) -> Option<T> {
+        let (subtree, bit, _, right_turn) = self.find_neighbors(root, key)?;
+        match bit {
+            Some(bit) if !bit_is_set(key, bit) => self.get_min(subtree),
+            _ => self.get_min(right_turn),
+        }
+    }
+
+    /// Returns the largest key less than `key`.
+    fn get_predecessor(&self, root: CritBitChild<T>, key: T) -> Option<T> {
+        let (subtree, bit, left_turn, _) = self.find_neighbors(root, key)?;
+        match bit {
+            Some(bit) if bit_is_set(key, bit) => self.get_max(subtree),
+            _ => self.get_max(left_turn),
+        }
+    }
+
+    /// Descends to where `key` is or would be inserted. Returns the subtree
+    /// found there, the bit in which `key` first differs from it (`None` for
+    /// an exact match), and the nearest subtrees wholly to the left and to
+    /// the right of that position.
+    #[allow(clippy::type_complexity)]
+    fn find_neighbors(
+        &self,
+        root: CritBitChild<T>,
+        key: T,
+    ) -> Option<(
+        CritBitChild<T>,
+        Option<u32>,
+        CritBitChild<T>,
+        CritBitChild<T>,
+    )> {
+        let best = self.get_best_leaf(root, key)?;
+        let bit = if best == key {
+            None
+        } else {
+            Some(highest_bit(best ^ key))
+        };
+        let mut left_turn = CritBitChild::Empty;
+        let mut right_turn = CritBitChild::Empty;
+        let mut current = root;
+        while let CritBitChild::Node(node) = current {
+            let crit_bit = self.get_crit_bit(node);
+            if bit.is_some_and(|bit| crit_bit < bit) {
+                break;
+            }
+            if bit_is_set(key, crit_bit) {
+                left_turn = self.get_child(node, false);
+                current = self.get_child(node, true);
+            } else {
+                right_turn = self.get_child(node, true);
+                current = self.get_child(node, false);
+            }
+        }
+        Some((current, bit, left_turn, right_turn))
+    }
+
+    /// Calls `handler` with every key of the subtree at `current` in
+    /// ascending order, recursing once per crit bit.
+    fn each<H: FnMut(T) -> Flow>(&self, current: CritBitChild<T>, handler: &mut H) -> Flow {
+        match current {
+            CritBitChild::Empty => Flow::Continue,
+            CritBitChild::Leaf(leaf) => handler(leaf),
+            CritBitChild::Node(node) => {
+                if self.each(self.get_child(node, false), handler).is_break() {
+                    Flow::Break
+                } else {
+                    self.each(self.get_child(node, true), handler)
+                }
+            }
+        }
+    }
+
+    /// Calls `handler` in ascending order with every key whose highest
+    /// `length` bits equal those of `prefix`.
+    fn each_with_prefix<H: FnMut(T) -> Flow>(
+        &self,
+        root: CritBitChild<T>,
+        prefix: T,
+        length: u32,
+        mut handler: H,
+    ) -> Flow {
+        if length == 0 {
+            return self.each(root, &mut handler);
+        }
+        let lowest = T::BITS - length.min(T::BITS);
+        let mut current = root;
+        while let CritBitChild::Node(node) = current {
+            let crit_bit = self.get_crit_bit(node);
+            if crit_bit < lowest {
+                break;
+            }
+            current = self.get_child(node, bit_is_set(prefix, crit_bit));
+        }
+        match self.get_min(current) {
+            Some(key) if (key ^ prefix) >> lowest == T::funty(0) => {
+                self.each(current, &mut handler)
+            }
+            _ => Flow::Continue,
+        }
+    }
+}
+
+fn bit_is_set<T: LinkType>(key: T, bit: u32) -> bool {
+    (key >> bit) & T::funty(1) != T::funty(0)
+}
+
+fn highest_bit<T: LinkType>(value: T) -> u32 {
+    T::BITS - 1 - value.leading_zeros()
+}
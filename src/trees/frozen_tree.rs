@@ -0,0 +1,268 @@
+use crate::{Flow, LinkType, RecursiveSizeBalancedTree};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Positions ahead of the current one whose cache line is prefetched: the
+/// first of its descendants four levels down.
+const PREFETCH_DISTANCE: usize = 16;
+
+/// Read-only copy of a size-balanced tree in an implicit Eytzinger layout.
+///
+/// Position `1` holds the root and the children of position `k` are at
+/// `2k` and `2k + 1`, so the top levels of the tree share a few cache lines
+/// and no links or sizes are stored: subtree sizes follow from the node
+/// count. Searches descend without branching on the comparison and
+/// prefetch the descendants several levels ahead.
+///
+/// Keys stay in the storage the tree was frozen from; searches take that
+/// storage for its comparison methods.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenTree<T> {
+    /// Nodes by position; position `0` is unused.
+    layout: Vec<T>,
+}
+
+impl<T: LinkType> Default for FrozenTree<T> {
+    fn default() -> Self {
+        Self::from_sorted(&[])
+    }
+}
+
+impl<T: LinkType> FrozenTree<T> {
+    /// Freezes the tree at `root` of `storage`.
+    ///
+    /// # Safety
+    ///
+    /// `root` must be `0` or the root of a well-formed tree of `storage`.
+    pub unsafe fn freeze<S: RecursiveSizeBalancedTree<T>>(storage: &S, root: T) -> Self {
+        let mut nodes = Vec::with_capacity(storage.get_size_or_zero(root).as_usize());
+        let mut stack = Vec::new();
+        let mut current = root;
+        while current != T::funty(0) || !stack.is_empty() {
+            while current != T::funty(0) {
+                stack.push(current);
+                current = storage.get_left(current);
+            }
+            let node = stack.pop().unwrap();
+            nodes.push(node);
+            current = storage.get_right(node);
+        }
+        Self::from_sorted(&nodes)
+    }
+
+    /// Lays out `nodes`, which must already be in tree order.
+    pub fn from_sorted(nodes: &[T]) -> Self {
+        let mut layout = vec![T::funty(0); nodes.len() + 1];
+        let mut position = first_position(nodes.len());
+        for &node in nodes {
+            layout[position] = node;
+            position = next_position(position, nodes.len());
+        }
+        Self { layout }
+    }
+
+    pub fn len(&self) -> usize {
+        self.layout.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Nodes in layout order, root first.
+    pub fn as_slice(&self) -> &[T] {
+        &self.layout[1..]
+    }
+
+    /// Returns the position of the first node not to the left of `node`,
+    /// or `0` when there is none.
+    unsafe fn lower_position<S: RecursiveSizeBalancedTree<T>>(
+        &self,
+        storage: &S,
+        node: T,
+    ) -> usize {
+        let base = self.layout.as_ptr();
+        let mut position = 1;
+        while position < self.layout.len() {
+            prefetch(base.wrapping_add(position * PREFETCH_DISTANCE));
+            let is_left = storage.first_is_to_the_left_of_second(self.layout[position], node);
+            position = 2 * position + usize::from(is_left);
+        }
+        // Every right step after the last left one leads past the answer.
+        position >> (position.trailing_ones() + 1)
+    }
+
+    /// Returns the first node that is not to the left of `node`, or `0`.
+    ///
+    /// # Safety
+    ///
+    /// `node` and the frozen nodes must be valid for the comparison methods
+    /// of `storage`.
+    pub unsafe fn lower_bound<S: RecursiveSizeBalancedTree<T>>(&self, storage: &S, node: T) -> T {
+        self.layout[self.lower_position(storage, node)]
+    }
+
+    /// # Safety
+    ///
+    /// Same contract as [`FrozenTree::lower_bound`].
+    pub unsafe fn contains<S: RecursiveSizeBalancedTree<T>>(&self, storage: &S, node: T) -> bool {
+        let position = self.lower_position(storage, node);
+        position != 0 && !storage.first_is_to_the_right_of_second(self.layout[position], node)
+    }
+
+    /// Number of frozen nodes that are to the left of `node`, which need not
+    /// be frozen.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as [`FrozenTree::lower_bound`].
+    pub unsafe fn rank<S: RecursiveSizeBalancedTree<T>>(&self, storage: &S, node: T) -> usize {
+        let count = self.len();
+        let mut rank = 0;
+        let mut position = 1;
+        while position <= count {
+            if storage.first_is_to_the_left_of_second(self.layout[position], node) {
+                rank += subtree_size(2 * position, count) + 1;
+                position = 2 * position + 1;
+            } else {
+                position *= 2;
+            }
+        }
+        rank
+    }
+
+    /// Returns the node with `index` nodes to its left, or `0` when `index`
+    /// is out of range.
+    pub fn select(&self, mut index: usize) -> T {
+        let count = self.len();
+        let mut position = 1;
+        while position <= count {
+            let left_size = subtree_size(2 * position, count);
+            if index < left_size {
+                position *= 2;
+            } else if index > left_size {
+                index -= left_size + 1;
+                position = 2 * position + 1;
+            } else {
+                return self.layout[position];
+            }
+        }
+        T::funty(0)
+    }
+
+    /// In-order iterator over the frozen nodes.
+    pub fn iter(&self) -> FrozenTreeIter<'_, T> {
+        FrozenTreeIter {
+            layout: &self.layout,
+            position: first_position(self.len()),
+            remaining: self.len(),
+        }
+    }
+
+    /// Calls `handler` with every frozen node from `from` to `to` inclusive,
+    /// in order.
+    ///
+    /// # Safety
+    ///
+    /// `from`, `to` and the frozen nodes must be valid for the comparison
+    /// methods of `storage`.
+    pub unsafe fn each_in_range<S: RecursiveSizeBalancedTree<T>, H: FnMut(T) -> Flow>(
+        &self,
+        storage: &S,
+        from: T,
+        to: T,
+        mut handler: H,
+    ) -> Flow {
+        let mut position = self.lower_position(storage, from);
+        while position != 0 {
+            let node = self.layout[position];
+            if storage.first_is_to_the_right_of_second(node, to) {
+                break;
+            }
+            if handler(node).is_break() {
+                return Flow::Break;
+            }
+            position = next_position(position, self.len());
+        }
+        Flow::Continue
+    }
+}
+
+/// In-order iterator over the nodes of a [`FrozenTree`].
+pub struct FrozenTreeIter<'a, T> {
+    layout: &'a [T],
+    position: usize,
+    remaining: usize,
+}
+
+impl<'a, T: LinkType> Iterator for FrozenTreeIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.position == 0 {
+            return None;
+        }
+        let node = self.layout[self.position];
+        self.position = next_position(self.position, self.layout.len() - 1);
+        self.remaining -= 1;
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: LinkType> ExactSizeIterator for FrozenTreeIter<'a, T> {}
+
+/// Position of the leftmost of `count` nodes, or `0` when there are none.
+fn first_position(count: usize) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    let mut position = 1;
+    while 2 * position <= count {
+        position *= 2;
+    }
+    position
+}
+
+/// In-order successor of `position` among `count` nodes, or `0`.
+fn next_position(mut position: usize, count: usize) -> usize {
+    if 2 * position < count {
+        position = 2 * position + 1;
+        while 2 * position <= count {
+            position *= 2;
+        }
+        position
+    } else {
+        // Climb past the ancestors this subtree is the right child of.
+        position >> (position.trailing_ones() + 1)
+    }
+}
+
+/// Number of positions up to `count` in the subtree at `position`: one
+/// contiguous run per level.
+fn subtree_size(mut position: usize, count: usize) -> usize {
+    let mut size = 0;
+    let mut width = 1;
+    while position <= count {
+        size += width.min(count - position + 1);
+        position *= 2;
+        width *= 2;
+    }
+    size
+}
+
+/// Hints the processor to load the cache line at `pointer`, which need not
+/// be in bounds.
+#[inline(always)]
+fn prefetch<T>(pointer: *const T) {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse"))]
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(pointer as *const i8);
+    }
+    #[cfg(not(all(target_arch = "x86_64", target_feature = "sse")))]
+    let _ = pointer;
+}
@@ -1,5 +1,11 @@
 use crate::{LinkType, RecursiveSizeBalancedTree};
 
+/// Loop-based `attach` and `detach` for a [`RecursiveSizeBalancedTree`].
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`].
+#[allow(clippy::missing_safety_doc)]
 pub trait IterativeSizeBalancedTree<T: LinkType>: RecursiveSizeBalancedTree<T> {
     unsafe fn attach(&mut self, root: *mut T, node: T) {
         if *root == T::funty(0) {
@@ -0,0 +1,169 @@
+use crate::{IterativeSizeBalancedTree, LinkType};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// One copy of the tree: its node storage and root.
+struct Side<T, S> {
+    storage: S,
+    root: T,
+}
+
+/// Size-balanced tree shared by many readers and one writer at a time,
+/// using left-right double buffering.
+///
+/// The tree is kept twice. Readers enter the published copy, announced by a
+/// per-copy reader counter, and never wait or retry more than once per
+/// concurrent publish. The writer applies each change to the hidden copy,
+/// publishes it, waits until the readers of the old copy have left, and
+/// then replays the change on it. Readers therefore only ever see a tree
+/// between two complete writes, never a torn one.
+///
+/// Writes are closures that run once on each copy, so they must be
+/// deterministic. A writer that panics releases the writer lock and the
+/// copy it was changing is replaced by a clone of the other one: a panic on
+/// the first copy undoes the write, a panic on the second completes it.
+pub struct LeftRightTree<T, S> {
+    sides: [UnsafeCell<Side<T, S>>; 2],
+    published: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    writing: AtomicBool,
+}
+
+// SAFETY: readers share a copy only while it is published and not written;
+// the writer changes a copy only after its readers have left.
+unsafe impl<T: Send + Sync, S: Send + Sync> Sync for LeftRightTree<T, S> {}
+
+impl<T: LinkType, S: IterativeSizeBalancedTree<T> + Clone> LeftRightTree<T, S> {
+    /// Shares `storage`, holding the tree at `root`.
+    pub fn new(storage: S, root: T) -> Self {
+        let copy = Side {
+            storage: storage.clone(),
+            root,
+        };
+        Self {
+            sides: [
+                UnsafeCell::new(Side { storage, root }),
+                UnsafeCell::new(copy),
+            ],
+            published: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writing: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs `reader` on the published storage and root.
+    pub fn read<R, F: FnOnce(&S, T) -> R>(&self, reader: F) -> R {
+        let index = loop {
+            let index = self.published.load(Ordering::SeqCst);
+            self.readers[index].fetch_add(1, Ordering::SeqCst);
+            if self.published.load(Ordering::SeqCst) == index {
+                break index;
+            }
+            self.readers[index].fetch_sub(1, Ordering::SeqCst);
+        };
+        // SAFETY: the copy stays published or keeps this reader counted
+        // until the counter is released, so the writer does not touch it.
+        let side = unsafe { &*self.sides[index].get() };
+        let result = reader(&side.storage, side.root);
+        self.readers[index].fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+
+    /// Runs `writer` on both copies in turn, waiting for other writers and
+    /// for the readers of the copy it changes second.
+    pub fn write<F: Fn(&mut S, &mut T)>(&self, writer: F) {
+        while self
+            .writing
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            pause();
+        }
+        let mut guard = WriteGuard {
+            tree: self,
+            dirty: None,
+        };
+        let published = self.published.load(Ordering::SeqCst);
+        let hidden = 1 - published;
+        // SAFETY: readers only enter the published copy, and the writer lock
+        // is held.
+        let side = unsafe { &mut *self.sides[hidden].get() };
+        guard.dirty = Some(hidden);
+        writer(&mut side.storage, &mut side.root);
+        guard.dirty = None;
+
+        self.published.store(hidden, Ordering::SeqCst);
+        while self.readers[published].load(Ordering::SeqCst) != 0 {
+            pause();
+        }
+        // SAFETY: the copy is hidden and its last reader has left.
+        let side = unsafe { &mut *self.sides[published].get() };
+        guard.dirty = Some(published);
+        writer(&mut side.storage, &mut side.root);
+        guard.dirty = None;
+    }
+
+    /// Attaches `node` to the shared tree.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as [`IterativeSizeBalancedTree::attach`].
+    pub unsafe fn attach(&self, node: T) {
+        self.write(|storage, root| storage.attach(root, node));
+    }
+
+    /// Detaches `node` from the shared tree.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as [`IterativeSizeBalancedTree::detach`].
+    pub unsafe fn detach(&self, node: T) {
+        self.write(|storage, root| storage.detach(root, node));
+    }
+
+    pub fn root(&self) -> T {
+        self.read(|_, root| root)
+    }
+
+    pub fn into_inner(self) -> (S, T) {
+        let [side, _] = self.sides;
+        let side = side.into_inner();
+        (side.storage, side.root)
+    }
+}
+
+/// Writer lock of a [`LeftRightTree`], released when the write ends or
+/// unwinds.
+struct WriteGuard<'a, T: LinkType, S: IterativeSizeBalancedTree<T> + Clone> {
+    tree: &'a LeftRightTree<T, S>,
+    /// Hidden copy a writer is running on; a write that unwinds leaves it
+    /// set.
+    dirty: Option<usize>,
+}
+
+impl<'a, T: LinkType, S: IterativeSizeBalancedTree<T> + Clone> Drop for WriteGuard<'a, T, S> {
+    fn drop(&mut self) {
+        if let Some(dirty) = self.dirty {
+            // SAFETY: the dirty copy is hidden without readers and the writer
+            // lock is held; the other copy is only read, like readers do.
+            unsafe {
+                let source = &*self.tree.sides[1 - dirty].get();
+                let copy = Side {
+                    storage: source.storage.clone(),
+                    root: source.root,
+                };
+                *self.tree.sides[dirty].get() = copy;
+            }
+        }
+        self.tree.writing.store(false, Ordering::Release);
+    }
+}
+
+/// Waits briefly for another thread, giving up the time slice when threads
+/// are available so a preempted reader can leave.
+fn pause() {
+    #[cfg(any(feature = "std", test))]
+    std::thread::yield_now();
+    #[cfg(not(any(feature = "std", test)))]
+    core::hint::spin_loop();
+}
@@ -1,5 +1,32 @@
+mod avl_tree;
+mod b_plus_tree;
+mod checked_tree;
+mod child_links;
+mod crit_bit_trie;
+#[cfg(feature = "alloc")]
+mod frozen_tree;
 mod iterative_size_balanced_tree;
+mod left_right_tree;
+mod persistent_size_balanced_tree;
 mod recursive_size_balanced_tree;
+mod red_black_tree;
+mod scapegoat_tree;
+mod slot_size_balanced_tree;
+mod splay_tree;
+mod treap;
 
+pub use avl_tree::AvlTree;
+pub use b_plus_tree::BPlusTree;
+pub use checked_tree::{CheckedTree, CheckedTreeIter, TreeError};
+pub use crit_bit_trie::{CritBitChild, CritBitTrie};
+#[cfg(feature = "alloc")]
+pub use frozen_tree::{FrozenTree, FrozenTreeIter};
 pub use iterative_size_balanced_tree::IterativeSizeBalancedTree;
+pub use left_right_tree::LeftRightTree;
+pub use persistent_size_balanced_tree::PersistentSizeBalancedTree;
 pub use recursive_size_balanced_tree::RecursiveSizeBalancedTree;
+pub use red_black_tree::RedBlackTree;
+pub use scapegoat_tree::{ScapegoatRecord, ScapegoatTree};
+pub use slot_size_balanced_tree::{Slot, SlotSizeBalancedTree};
+pub use splay_tree::SplayTree;
+pub use treap::Treap;
@@ -0,0 +1,326 @@
+use crate::{LinkType, RecursiveSizeBalancedTree};
+
+/// Upper bound on the height of a size-balanced tree addressed by 64-bit ids
+/// (`log_φ(√5 · (2^64 + 1.5)) - 2`, about 92), with room for the levels
+/// rotations add while an update is rebalanced.
+const MAX_DEPTH: usize = 128;
+
+/// Persistent size-balanced tree: mutations copy nodes instead of changing
+/// them, so every earlier root keeps describing its own version.
+///
+/// `persistent_attach` and `persistent_detach` copy the nodes on the root
+/// path (and the ones rotations touch) into ids from `copy_node` and return
+/// the root of a new version; untouched subtrees are shared between
+/// versions. Sharing is tracked with a reference count per node: a version
+/// root counts one reference for its holder and each link counts one for the
+/// child. A node referenced once is owned by the version being built and is
+/// changed in place, so a version can be updated without copies after the
+/// old root is released.
+///
+/// Releasing a root with `release` frees every node that no other version
+/// references, which garbage-collects unreachable versions eagerly.
+///
+/// The operations are iterative: updates record the copied root path in a
+/// fixed array and rebalance it bottom-up, and the maintain step and
+/// `release` keep their pending work in fixed arrays too, like the
+/// red-black tree.
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`]; in addition reference
+/// counts are only changed through the methods of this trait, and a root is
+/// released at most once per reference held.
+#[allow(clippy::missing_safety_doc)]
+pub trait PersistentSizeBalancedTree<T: LinkType>: RecursiveSizeBalancedTree<T> {
+    unsafe fn get_references(&self, node: T) -> T;
+
+    unsafe fn set_references(&mut self, node: T, references: T);
+
+    /// Allocates a node that compares equal to `node`. Its links, size and
+    /// reference count are set by the caller.
+    unsafe fn copy_node(&mut self, node: T) -> T;
+
+    unsafe fn free_node(&mut self, node: T);
+
+    /// Adds a reference to `node`, which may be `0`.
+    unsafe fn retain(&mut self, node: T) {
+        if node != T::funty(0) {
+            self.set_references(node, self.get_references(node) + T::funty(1));
+        }
+    }
+
+    /// Drops a reference to `node`, which may be `0`, freeing it and
+    /// releasing its children when it was the last one.
+    unsafe fn release(&mut self, node: T) {
+        // Every freed node leaves at most its right child pending per level.
+        let mut pending = [T::funty(0); MAX_DEPTH + 1];
+        pending[0] = node;
+        let mut count = 1;
+        while count > 0 {
+            count -= 1;
+            let node = pending[count];
+            if node == T::funty(0) {
+                continue;
+            }
+            let references = self.get_references(node) - T::funty(1);
+            if references != T::funty(0) {
+                self.set_references(node, references);
+                continue;
+            }
+            let left = self.get_left(node);
+            let right = self.get_right(node);
+            self.free_node(node);
+            pending[count] = right;
+            pending[count + 1] = left;
+            count += 2;
+        }
+    }
+
+    /// Returns a node equal to `node` that the caller may change: `node`
+    /// itself when the caller holds its only reference, and otherwise a copy
+    /// that takes over the caller's reference.
+    unsafe fn unshare(&mut self, node: T) -> T {
+        let references = self.get_references(node);
+        if references == T::funty(1) {
+            return node;
+        }
+        let copy = self.copy_node(node);
+        let left = self.get_left(node);
+        let right = self.get_right(node);
+        self.set_left(copy, left);
+        self.set_right(copy, right);
+        self.set_size(copy, self.get_size(node));
+        self.set_references(copy, T::funty(1));
+        self.retain(left);
+        self.retain(right);
+        self.set_references(node, references - T::funty(1));
+        copy
+    }
+
+    unsafe fn persistent_left_rotate(&mut self, root: T) -> T {
+        let root = self.unshare(root);
+        let right = self.unshare(self.get_right(root));
+        self.set_right(root, self.get_left(right));
+        self.set_left(right, root);
+        self.set_size(right, self.get_size(root));
+        self.fix_size(root);
+        right
+    }
+
+    unsafe fn persistent_right_rotate(&mut self, root: T) -> T {
+        let root = self.unshare(root);
+        let left = self.unshare(self.get_left(root));
+        self.set_left(root, self.get_right(left));
+        self.set_right(left, root);
+        self.set_size(left, self.get_size(root));
+        self.fix_size(root);
+        left
+    }
+
+    /// Restores the size-balanced invariant at `root` after its right
+    /// (`right_grew`) or left subtree became relatively larger by one node,
+    /// and returns the new subtree root. Only rotated nodes are unshared.
+    unsafe fn persistent_maintain(&mut self, mut root: T, right_grew: bool) -> T {
+        // Checks still to run, last first: the link of `parent` (its left
+        // one when `is_left`) or `root` when `parent` is `0`, and the side
+        // that grew. A slot has at most two checks pending and a level also
+        // holds its right sibling's.
+        let mut pending = [(T::funty(0), false, false); 3 * MAX_DEPTH];
+        pending[0] = (T::funty(0), false, right_grew);
+        let mut count = 1;
+        while count > 0 {
+            count -= 1;
+            let (parent, is_left, right_grew) = pending[count];
+            let node = if parent == T::funty(0) {
+                root
+            } else if is_left {
+                self.get_left(parent)
+            } else {
+                self.get_right(parent)
+            };
+            if node == T::funty(0) {
+                continue;
+            }
+            let rotated = if right_grew {
+                let right = self.get_right(node);
+                let left_size = self.get_left_size(node);
+                if self.get_right_size(right) > left_size {
+                    self.persistent_left_rotate(node)
+                } else if self.get_left_size(right) > left_size {
+                    let node = self.unshare(node);
+                    let rotated = self.persistent_right_rotate(self.get_right(node));
+                    self.set_right(node, rotated);
+                    self.persistent_left_rotate(node)
+                } else {
+                    continue;
+                }
+            } else {
+                let left = self.get_left(node);
+                let right_size = self.get_right_size(node);
+                if self.get_left_size(left) > right_size {
+                    self.persistent_right_rotate(node)
+                } else if self.get_right_size(left) > right_size {
+                    let node = self.unshare(node);
+                    let rotated = self.persistent_left_rotate(self.get_left(node));
+                    self.set_left(node, rotated);
+                    self.persistent_right_rotate(node)
+                } else {
+                    continue;
+                }
+            };
+            if parent == T::funty(0) {
+                root = rotated;
+            } else if is_left {
+                self.set_left(parent, rotated);
+            } else {
+                self.set_right(parent, rotated);
+            }
+            // Maintain both children, then the slot from either side. A
+            // right check already pending on the slot would follow the new
+            // one directly and find nothing to do.
+            let slot_right = (parent, is_left, true);
+            if count == 0 || pending[count - 1] != slot_right {
+                pending[count] = slot_right;
+                count += 1;
+            }
+            pending[count] = (parent, is_left, false);
+            pending[count + 1] = (rotated, false, true);
+            pending[count + 2] = (rotated, true, false);
+            count += 3;
+        }
+        root
+    }
+
+    /// Returns the root of a new version holding the nodes of `root` and
+    /// `node`, which must be a fresh node not linked into any version.
+    ///
+    /// `root` stays valid; both roots hold their own reference.
+    unsafe fn persistent_attach(&mut self, root: T, node: T) -> T {
+        self.retain(root);
+        self.persistent_attach_core(root, node)
+    }
+
+    /// Returns the root of a new version without the node equal to `node`,
+    /// or another reference to `root` when there is none.
+    ///
+    /// `root` stays valid; both roots hold their own reference.
+    unsafe fn persistent_detach(&mut self, root: T, node: T) -> T {
+        self.retain(root);
+        if !self.contains(node, root) {
+            return root;
+        }
+        self.persistent_detach_core(root, node)
+    }
+
+    /// Attaches `node` to the subtree at `root`, taking over the caller's
+    /// reference to `root` and returning one to the new subtree root.
+    unsafe fn persistent_attach_core(&mut self, root: T, node: T) -> T {
+        self.set_left(node, T::funty(0));
+        self.set_right(node, T::funty(0));
+        self.set_size(node, T::funty(1));
+        self.set_references(node, T::funty(1));
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = root;
+        while current != T::funty(0) {
+            let copy = self.unshare(current);
+            self.inc_size(copy);
+            path[depth] = copy;
+            depth += 1;
+            current = if self.first_is_to_the_left_of_second(node, copy) {
+                self.get_left(copy)
+            } else {
+                self.get_right(copy)
+            };
+        }
+        rebalance_path(self, &path[..depth], node, node, true)
+    }
+
+    /// Detaches the node equal to `node`, which must be in the subtree at
+    /// `root`, taking over the caller's reference to `root` and returning
+    /// one to the new subtree root.
+    unsafe fn persistent_detach_core(&mut self, root: T, node: T) -> T {
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = root;
+        loop {
+            let is_left = self.first_is_to_the_left_of_second(node, current);
+            if !is_left && !self.first_is_to_the_right_of_second(node, current) {
+                break;
+            }
+            let copy = self.unshare(current);
+            self.dec_size(copy);
+            path[depth] = copy;
+            depth += 1;
+            current = if is_left {
+                self.get_left(copy)
+            } else {
+                self.get_right(copy)
+            };
+        }
+        let left = self.get_left(current);
+        let right = self.get_right(current);
+        self.retain(left);
+        self.retain(right);
+        self.release(current);
+        let child = if left == T::funty(0) {
+            right
+        } else if right == T::funty(0) {
+            left
+        } else {
+            let (right, replacement) = self.persistent_detach_leftest(right);
+            self.set_left(replacement, left);
+            self.set_right(replacement, right);
+            self.fix_size(replacement);
+            self.persistent_maintain(replacement, false)
+        };
+        rebalance_path(self, &path[..depth], node, child, false)
+    }
+
+    /// Detaches the leftmost node of the subtree at `root`, taking over the
+    /// caller's reference to `root`. Returns a reference to the new subtree
+    /// root and the detached node, which the caller owns with links to be
+    /// overwritten.
+    unsafe fn persistent_detach_leftest(&mut self, root: T) -> (T, T) {
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut leftest = self.unshare(root);
+        let mut left = self.get_left(leftest);
+        while left != T::funty(0) {
+            self.dec_size(leftest);
+            path[depth] = leftest;
+            depth += 1;
+            leftest = self.unshare(left);
+            left = self.get_left(leftest);
+        }
+        let mut child = self.get_right(leftest);
+        self.set_right(leftest, T::funty(0));
+        for &parent in path[..depth].iter().rev() {
+            self.set_left(parent, child);
+            child = self.persistent_maintain(parent, true);
+        }
+        (child, leftest)
+    }
+}
+
+/// Links `child` into the last node of the copied search `path` for `node`,
+/// and the result of each step into the node above, maintaining every node
+/// on the side that grew (`attached`) or shrank. Returns the new root.
+unsafe fn rebalance_path<T: LinkType, S: PersistentSizeBalancedTree<T> + ?Sized>(
+    storage: &mut S,
+    path: &[T],
+    node: T,
+    mut child: T,
+    attached: bool,
+) -> T {
+    for &parent in path.iter().rev() {
+        let is_left = storage.first_is_to_the_left_of_second(node, parent);
+        if is_left {
+            storage.set_left(parent, child);
+        } else {
+            storage.set_right(parent, child);
+        }
+        child = storage.persistent_maintain(parent, is_left != attached);
+    }
+    child
+}
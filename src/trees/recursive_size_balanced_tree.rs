@@ -1,5 +1,13 @@
 use crate::LinkType;
 
+/// Size-balanced tree over externally stored nodes addressed by id.
+///
+/// # Safety
+///
+/// Every method shares one contract: node ids other than `0` must be valid
+/// nodes of the storage, and root pointers must be valid for reads and
+/// writes. The accessors are `unsafe` so implementors may skip bounds checks.
+#[allow(clippy::missing_safety_doc)]
 pub trait RecursiveSizeBalancedTree<T: LinkType> {
     unsafe fn get_mut_left_reference(&mut self, node: T) -> *mut T;
 
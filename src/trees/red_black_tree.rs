@@ -0,0 +1,287 @@
+use super::child_links::{replace_child, TreeLinks};
+use crate::{LinkType, RecursiveSizeBalancedTree};
+
+/// Upper bound on the height of a red-black tree addressed by 64-bit ids
+/// (`2 * log2(2^64 + 1)`).
+const MAX_DEPTH: usize = 130;
+
+/// Red-black tree sharing the node storage and navigation helpers of
+/// [`RecursiveSizeBalancedTree`].
+///
+/// The color lives in a bit chosen by the storage (for example a stolen high
+/// bit of the size or of a child link) and is exposed through `is_red` and
+/// `set_red`. `insert` and `delete` are iterative, keep the path from the root
+/// in a fixed array and maintain subtree sizes, so the order-statistic
+/// helpers keep working. They rotate at most twice (insert) or three times
+/// (delete) per update.
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`].
+#[allow(clippy::missing_safety_doc)]
+pub trait RedBlackTree<T: LinkType>: RecursiveSizeBalancedTree<T> {
+    unsafe fn is_red(&self, node: T) -> bool;
+
+    unsafe fn set_red(&mut self, node: T, red: bool);
+
+    /// Returns `false` for the null node, which counts as black.
+    unsafe fn is_red_or_default(&self, node: T) -> bool {
+        node != T::funty(0) && self.is_red(node)
+    }
+
+    unsafe fn insert(&mut self, root: &mut T, node: T) {
+        self.clear_node(node);
+        self.set_size(node, T::funty(1));
+        self.set_red(node, true);
+        if *root == T::funty(0) {
+            self.set_red(node, false);
+            *root = node;
+            return;
+        }
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = *root;
+        loop {
+            path[depth] = current;
+            depth += 1;
+            self.inc_size(current);
+            if self.first_is_to_the_left_of_second(node, current) {
+                let left = self.get_left(current);
+                if left == T::funty(0) {
+                    self.set_left(current, node);
+                    break;
+                }
+                current = left;
+            } else {
+                let right = self.get_right(current);
+                if right == T::funty(0) {
+                    self.set_right(current, node);
+                    break;
+                }
+                current = right;
+            }
+        }
+
+        let mut node = node;
+        while depth >= 2 && self.is_red(path[depth - 1]) {
+            let mut parent = path[depth - 1];
+            let grandparent = path[depth - 2];
+            if parent == self.get_left(grandparent) {
+                let uncle = self.get_right(grandparent);
+                if self.is_red_or_default(uncle) {
+                    self.set_red(parent, false);
+                    self.set_red(uncle, false);
+                    self.set_red(grandparent, true);
+                    node = grandparent;
+                    depth -= 2;
+                    continue;
+                }
+                if node == self.get_right(parent) {
+                    let rotated = self.left_rotate_core(parent);
+                    self.set_left(grandparent, rotated);
+                    parent = rotated;
+                }
+                self.set_red(parent, false);
+                self.set_red(grandparent, true);
+                let rotated = self.right_rotate_core(grandparent);
+                replace_child(
+                    &mut TreeLinks(self),
+                    root,
+                    &path[..depth - 2],
+                    grandparent,
+                    rotated,
+                );
+            } else {
+                let uncle = self.get_left(grandparent);
+                if self.is_red_or_default(uncle) {
+                    self.set_red(parent, false);
+                    self.set_red(uncle, false);
+                    self.set_red(grandparent, true);
+                    node = grandparent;
+                    depth -= 2;
+                    continue;
+                }
+                if node == self.get_left(parent) {
+                    let rotated = self.right_rotate_core(parent);
+                    self.set_right(grandparent, rotated);
+                    parent = rotated;
+                }
+                self.set_red(parent, false);
+                self.set_red(grandparent, true);
+                let rotated = self.left_rotate_core(grandparent);
+                replace_child(
+                    &mut TreeLinks(self),
+                    root,
+                    &path[..depth - 2],
+                    grandparent,
+                    rotated,
+                );
+            }
+            break;
+        }
+        self.set_red(*root, false);
+    }
+
+    unsafe fn delete(&mut self, root: &mut T, node: T) {
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = *root;
+        while current != node {
+            path[depth] = current;
+            depth += 1;
+            current = if self.first_is_to_the_left_of_second(node, current) {
+                self.get_left(current)
+            } else {
+                self.get_right(current)
+            };
+        }
+        let left = self.get_left(node);
+        let right = self.get_right(node);
+        let removed_red;
+        let mut child;
+        if left != T::funty(0) && right != T::funty(0) {
+            let node_depth = depth;
+            path[depth] = node;
+            depth += 1;
+            let mut successor = right;
+            while self.get_left(successor) != T::funty(0) {
+                path[depth] = successor;
+                depth += 1;
+                successor = self.get_left(successor);
+            }
+            removed_red = self.is_red(successor);
+            child = self.get_right(successor);
+            let successor_parent = path[depth - 1];
+            if successor_parent == node {
+                self.set_right(node, child);
+            } else {
+                self.set_left(successor_parent, child);
+            }
+            self.set_left(successor, left);
+            self.set_right(successor, self.get_right(node));
+            self.set_red(successor, self.is_red(node));
+            path[node_depth] = successor;
+            replace_child(
+                &mut TreeLinks(self),
+                root,
+                &path[..node_depth],
+                node,
+                successor,
+            );
+            for &ancestor in path[node_depth..depth].iter().rev() {
+                self.fix_size(ancestor);
+            }
+            for &ancestor in &path[..node_depth] {
+                self.dec_size(ancestor);
+            }
+        } else {
+            removed_red = self.is_red(node);
+            child = if left != T::funty(0) { left } else { right };
+            replace_child(&mut TreeLinks(self), root, &path[..depth], node, child);
+            for &ancestor in &path[..depth] {
+                self.dec_size(ancestor);
+            }
+        }
+        self.clear_node(node);
+        self.set_red(node, false);
+        if removed_red {
+            return;
+        }
+
+        while depth > 0 && !self.is_red_or_default(child) {
+            let parent = path[depth - 1];
+            if child == self.get_left(parent) {
+                let mut sibling = self.get_right(parent);
+                if self.is_red(sibling) {
+                    self.set_red(sibling, false);
+                    self.set_red(parent, true);
+                    let rotated = self.left_rotate_core(parent);
+                    replace_child(
+                        &mut TreeLinks(self),
+                        root,
+                        &path[..depth - 1],
+                        parent,
+                        rotated,
+                    );
+                    path[depth - 1] = rotated;
+                    path[depth] = parent;
+                    depth += 1;
+                    sibling = self.get_right(parent);
+                }
+                if !self.is_red_or_default(self.get_left(sibling))
+                    && !self.is_red_or_default(self.get_right(sibling))
+                {
+                    self.set_red(sibling, true);
+                    child = parent;
+                    depth -= 1;
+                    continue;
+                }
+                if !self.is_red_or_default(self.get_right(sibling)) {
+                    self.set_red(self.get_left(sibling), false);
+                    self.set_red(sibling, true);
+                    sibling = self.right_rotate_core(sibling);
+                    self.set_right(parent, sibling);
+                }
+                self.set_red(sibling, self.is_red(parent));
+                self.set_red(parent, false);
+                self.set_red(self.get_right(sibling), false);
+                let rotated = self.left_rotate_core(parent);
+                replace_child(
+                    &mut TreeLinks(self),
+                    root,
+                    &path[..depth - 1],
+                    parent,
+                    rotated,
+                );
+            } else {
+                let mut sibling = self.get_left(parent);
+                if self.is_red(sibling) {
+                    self.set_red(sibling, false);
+                    self.set_red(parent, true);
+                    let rotated = self.right_rotate_core(parent);
+                    replace_child(
+                        &mut TreeLinks(self),
+                        root,
+                        &path[..depth - 1],
+                        parent,
+                        rotated,
+                    );
+                    path[depth - 1] = rotated;
+                    path[depth] = parent;
+                    depth += 1;
+                    sibling = self.get_left(parent);
+                }
+                if !self.is_red_or_default(self.get_left(sibling))
+                    && !self.is_red_or_default(self.get_right(sibling))
+                {
+                    self.set_red(sibling, true);
+                    child = parent;
+                    depth -= 1;
+                    continue;
+                }
+                if !self.is_red_or_default(self.get_left(sibling)) {
+                    self.set_red(self.get_right(sibling), false);
+                    self.set_red(sibling, true);
+                    sibling = self.left_rotate_core(sibling);
+                    self.set_left(parent, sibling);
+                }
+                self.set_red(sibling, self.is_red(parent));
+                self.set_red(parent, false);
+                self.set_red(self.get_left(sibling), false);
+                let rotated = self.right_rotate_core(parent);
+                replace_child(
+                    &mut TreeLinks(self),
+                    root,
+                    &path[..depth - 1],
+                    parent,
+                    rotated,
+                );
+            }
+            child = *root;
+            break;
+        }
+        if child != T::funty(0) {
+            self.set_red(child, false);
+        }
+    }
+}
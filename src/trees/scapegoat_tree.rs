@@ -0,0 +1,281 @@
+use crate::LinkType;
+
+/// Upper bound on the depth of a scapegoat tree addressed by 64-bit ids
+/// (`log(2^64) / log(3 / 2) + 1` rounded up with margin).
+const MAX_DEPTH: usize = 128;
+
+/// Per-tree state of a [`ScapegoatTree`], kept by the caller instead of in
+/// the nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScapegoatRecord<T> {
+    pub root: T,
+    /// Number of nodes in the tree.
+    pub size: T,
+    /// Largest `size` since the last full rebuild.
+    pub max_size: T,
+}
+
+/// Scapegoat tree (`alpha = 2/3`) that needs no balance metadata per node.
+///
+/// Storages only provide `left` and `right` accessors plus the two
+/// comparison methods; the node count lives in a [`ScapegoatRecord`]. An
+/// insert deeper than `log_{3/2}(size)` rebuilds the subtree of the first
+/// ancestor with a child holding more than two thirds of its nodes, and
+/// removals rebuild the whole tree once it shrinks below two thirds of its
+/// peak size. Rebuilds use the in-place Day-Stout-Warren algorithm, so
+/// operations are `O(log n)` amortized without allocating.
+///
+/// # Safety
+///
+/// Node ids other than `0` must be valid nodes of the storage, and the
+/// record must describe the tree it is passed with.
+#[allow(clippy::missing_safety_doc)]
+pub trait ScapegoatTree<T: LinkType> {
+    unsafe fn get_left(&self, node: T) -> T;
+
+    unsafe fn get_right(&self, node: T) -> T;
+
+    unsafe fn set_left(&mut self, node: T, left: T);
+
+    unsafe fn set_right(&mut self, node: T, right: T);
+
+    unsafe fn first_is_to_the_left_of_second(&self, first: T, second: T) -> bool;
+
+    unsafe fn first_is_to_the_right_of_second(&self, first: T, second: T) -> bool;
+
+    /// Counts the nodes of the subtree at `node`, recursing to its depth.
+    unsafe fn count_nodes(&self, node: T) -> T {
+        if node == T::funty(0) {
+            T::funty(0)
+        } else {
+            self.count_nodes(self.get_left(node))
+                + self.count_nodes(self.get_right(node))
+                + T::funty(1)
+        }
+    }
+
+    /// Rebuilds the subtree at `root` into a complete tree in place and
+    /// returns its new root.
+    unsafe fn rebuild(&mut self, root: T) -> T {
+        let mut head = root;
+        let size = tree_to_vine(self, &mut head);
+        let mut full = T::funty(0);
+        while full < size - full {
+            full = full + full + T::funty(1);
+        }
+        compress(self, &mut head, size - full);
+        let mut remaining = full;
+        while remaining > T::funty(1) {
+            remaining /= T::funty(2);
+            compress(self, &mut head, remaining);
+        }
+        head
+    }
+
+    unsafe fn attach(&mut self, record: &mut ScapegoatRecord<T>, node: T) {
+        self.set_left(node, T::funty(0));
+        self.set_right(node, T::funty(0));
+        record.size += T::funty(1);
+        if record.size > record.max_size {
+            record.max_size = record.size;
+        }
+        if record.root == T::funty(0) {
+            record.root = node;
+            return;
+        }
+        let mut path = [T::funty(0); MAX_DEPTH];
+        let mut depth = 0;
+        let mut current = record.root;
+        loop {
+            path[depth] = current;
+            depth += 1;
+            if self.first_is_to_the_left_of_second(node, current) {
+                let left = self.get_left(current);
+                if left == T::funty(0) {
+                    self.set_left(current, node);
+                    break;
+                }
+                current = left;
+            } else {
+                let right = self.get_right(current);
+                if right == T::funty(0) {
+                    self.set_right(current, node);
+                    break;
+                }
+                current = right;
+            }
+        }
+        if depth <= depth_limit(record.size) {
+            return;
+        }
+
+        let mut child = node;
+        let mut child_size = T::funty(1);
+        for index in (0..depth).rev() {
+            let ancestor = path[index];
+            let sibling = if self.get_left(ancestor) == child {
+                self.get_right(ancestor)
+            } else {
+                self.get_left(ancestor)
+            };
+            let rest = self.count_nodes(sibling) + T::funty(1);
+            if child_size > rest && child_size - rest > rest {
+                let rebuilt = self.rebuild(ancestor);
+                if index == 0 {
+                    record.root = rebuilt;
+                } else if self.get_left(path[index - 1]) == ancestor {
+                    self.set_left(path[index - 1], rebuilt);
+                } else {
+                    self.set_right(path[index - 1], rebuilt);
+                }
+                return;
+            }
+            child = ancestor;
+            child_size += rest;
+        }
+    }
+
+    unsafe fn detach(&mut self, record: &mut ScapegoatRecord<T>, node: T) {
+        let mut parent = T::funty(0);
+        let mut current = record.root;
+        while current != node {
+            parent = current;
+            current = if self.first_is_to_the_left_of_second(node, current) {
+                self.get_left(current)
+            } else {
+                self.get_right(current)
+            };
+        }
+        let left = self.get_left(node);
+        let right = self.get_right(node);
+        let replacement = if left == T::funty(0) {
+            right
+        } else if right == T::funty(0) {
+            left
+        } else {
+            let mut successor_parent = node;
+            let mut successor = right;
+            while self.get_left(successor) != T::funty(0) {
+                successor_parent = successor;
+                successor = self.get_left(successor);
+            }
+            if successor_parent != node {
+                self.set_left(successor_parent, self.get_right(successor));
+                self.set_right(successor, right);
+            }
+            self.set_left(successor, left);
+            successor
+        };
+        if parent == T::funty(0) {
+            record.root = replacement;
+        } else if self.get_left(parent) == node {
+            self.set_left(parent, replacement);
+        } else {
+            self.set_right(parent, replacement);
+        }
+        self.set_left(node, T::funty(0));
+        self.set_right(node, T::funty(0));
+
+        record.size -= T::funty(1);
+        let shrunk = record.max_size - record.size;
+        if record.size < shrunk || record.size - shrunk < shrunk {
+            record.root = self.rebuild(record.root);
+            record.max_size = record.size;
+        }
+    }
+
+    unsafe fn contains(&self, node: T, mut root: T) -> bool {
+        while root != T::funty(0) {
+            if self.first_is_to_the_left_of_second(node, root) {
+                root = self.get_left(root);
+            } else if self.first_is_to_the_right_of_second(node, root) {
+                root = self.get_right(root);
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Largest depth allowed for a tree of `size` nodes: `floor(log_{3/2}(size))`.
+fn depth_limit<T: LinkType>(size: T) -> usize {
+    let size = size.as_u64() as f64;
+    let mut limit = 0;
+    let mut power = 1.5;
+    while power <= size {
+        power *= 1.5;
+        limit += 1;
+    }
+    limit
+}
+
+/// Reads the right link of `node`, where the null node stands for a
+/// pseudo-root whose right link is `head`.
+unsafe fn get_vine_right<T: LinkType, S: ScapegoatTree<T> + ?Sized>(
+    storage: &S,
+    head: T,
+    node: T,
+) -> T {
+    if node == T::funty(0) {
+        head
+    } else {
+        storage.get_right(node)
+    }
+}
+
+unsafe fn set_vine_right<T: LinkType, S: ScapegoatTree<T> + ?Sized>(
+    storage: &mut S,
+    head: &mut T,
+    node: T,
+    right: T,
+) {
+    if node == T::funty(0) {
+        *head = right;
+    } else {
+        storage.set_right(node, right);
+    }
+}
+
+/// Turns the subtree at `head` into a right-leaning vine and returns its
+/// node count.
+unsafe fn tree_to_vine<T: LinkType, S: ScapegoatTree<T> + ?Sized>(
+    storage: &mut S,
+    head: &mut T,
+) -> T {
+    let mut size = T::funty(0);
+    let mut tail = T::funty(0);
+    let mut rest = *head;
+    while rest != T::funty(0) {
+        let left = storage.get_left(rest);
+        if left == T::funty(0) {
+            size += T::funty(1);
+            tail = rest;
+            rest = storage.get_right(rest);
+        } else {
+            storage.set_left(rest, storage.get_right(left));
+            storage.set_right(left, rest);
+            rest = left;
+            set_vine_right(storage, head, tail, left);
+        }
+    }
+    size
+}
+
+/// Left-rotates every other node of the first `2 * count` nodes of the vine.
+unsafe fn compress<T: LinkType, S: ScapegoatTree<T> + ?Sized>(
+    storage: &mut S,
+    head: &mut T,
+    count: T,
+) {
+    let mut scanner = T::funty(0);
+    let mut index = T::funty(0);
+    while index < count {
+        let child = get_vine_right(storage, *head, scanner);
+        set_vine_right(storage, head, scanner, storage.get_right(child));
+        scanner = get_vine_right(storage, *head, scanner);
+        storage.set_right(child, storage.get_left(scanner));
+        storage.set_left(scanner, child);
+        index += T::funty(1);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{LinkType, RecursiveSizeBalancedTree};
+
+/// Position of a link inside a tree, named by value instead of by pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot<T> {
+    /// The root link owned by the caller.
+    Root,
+    /// The left child link of a node.
+    LeftOf(T),
+    /// The right child link of a node.
+    RightOf(T),
+}
+
+/// Pointer-free `attach` and `detach` for a [`RecursiveSizeBalancedTree`].
+///
+/// Positions are addressed with [`Slot`] values and every link is read and
+/// written through `get_left`/`set_left` and `get_right`/`set_right`, so the
+/// `get_*_reference` methods are never called. This suits storages that
+/// cannot lend references to their links, such as bit-packed or
+/// non-contiguous ones.
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`].
+#[allow(clippy::missing_safety_doc)]
+pub trait SlotSizeBalancedTree<T: LinkType>: RecursiveSizeBalancedTree<T> {
+    unsafe fn get_slot(&self, root: T, slot: Slot<T>) -> T {
+        match slot {
+            Slot::Root => root,
+            Slot::LeftOf(node) => self.get_left(node),
+            Slot::RightOf(node) => self.get_right(node),
+        }
+    }
+
+    unsafe fn set_slot(&mut self, root: &mut T, slot: Slot<T>, value: T) {
+        match slot {
+            Slot::Root => *root = value,
+            Slot::LeftOf(node) => self.set_left(node, value),
+            Slot::RightOf(node) => self.set_right(node, value),
+        }
+    }
+
+    unsafe fn slot_left_rotate(&mut self, root: &mut T, slot: Slot<T>) {
+        let rotated = self.left_rotate_core(self.get_slot(*root, slot));
+        self.set_slot(root, slot, rotated);
+    }
+
+    unsafe fn slot_right_rotate(&mut self, root: &mut T, slot: Slot<T>) {
+        let rotated = self.right_rotate_core(self.get_slot(*root, slot));
+        self.set_slot(root, slot, rotated);
+    }
+
+    unsafe fn slot_attach(&mut self, root: &mut T, node: T) {
+        if *root == T::funty(0) {
+            self.set_size(node, T::funty(1));
+            *root = node;
+            return;
+        }
+        self.slot_attach_core(root, Slot::Root, node);
+    }
+
+    unsafe fn slot_detach(&mut self, root: &mut T, node: T) {
+        self.slot_detach_core(root, Slot::Root, node);
+    }
+
+    unsafe fn slot_attach_core(&mut self, root: &mut T, mut slot: Slot<T>, node: T) {
+        loop {
+            let current = self.get_slot(*root, slot);
+            let left = self.get_left(current);
+            let left_size = self.get_size_or_zero(left);
+            let right = self.get_right(current);
+            let right_size = self.get_size_or_zero(right);
+            if self.first_is_to_the_left_of_second(node, current) {
+                if left == T::funty(0) {
+                    self.inc_size(current);
+                    self.set_size(node, T::funty(1));
+                    self.set_left(current, node);
+                    return;
+                }
+                if self.first_is_to_the_left_of_second(node, left) {
+                    if (left_size + T::funty(1)) > right_size {
+                        self.slot_right_rotate(root, slot);
+                    } else {
+                        self.inc_size(current);
+                        slot = Slot::LeftOf(current);
+                    }
+                } else {
+                    let left_right_size = self.get_size_or_zero(self.get_right(left));
+                    if (left_right_size + T::funty(1)) > right_size {
+                        if left_right_size == T::funty(0) && right_size == T::funty(0) {
+                            self.set_left(node, left);
+                            self.set_right(node, current);
+                            self.set_size(node, left_size + T::funty(1) + T::funty(1));
+                            self.set_left(current, T::funty(0));
+                            self.set_size(current, T::funty(1));
+                            self.set_slot(root, slot, node);
+                            return;
+                        }
+                        self.slot_left_rotate(root, Slot::LeftOf(current));
+                        self.slot_right_rotate(root, slot);
+                    } else {
+                        self.inc_size(current);
+                        slot = Slot::LeftOf(current);
+                    }
+                }
+            } else {
+                if right == T::funty(0) {
+                    self.inc_size(current);
+                    self.set_size(node, T::funty(1));
+                    self.set_right(current, node);
+                    return;
+                }
+                if self.first_is_to_the_right_of_second(node, right) {
+                    if (right_size + T::funty(1)) > left_size {
+                        self.slot_left_rotate(root, slot);
+                    } else {
+                        self.inc_size(current);
+                        slot = Slot::RightOf(current);
+                    }
+                } else {
+                    let right_left_size = self.get_size_or_zero(self.get_left(right));
+                    if (right_left_size + T::funty(1)) > left_size {
+                        if right_left_size == T::funty(0) && left_size == T::funty(0) {
+                            self.set_left(node, current);
+                            self.set_right(node, right);
+                            self.set_size(node, right_size + T::funty(1) + T::funty(1));
+                            self.set_right(current, T::funty(0));
+                            self.set_size(current, T::funty(1));
+                            self.set_slot(root, slot, node);
+                            return;
+                        }
+                        self.slot_right_rotate(root, Slot::RightOf(current));
+                        self.slot_left_rotate(root, slot);
+                    } else {
+                        self.inc_size(current);
+                        slot = Slot::RightOf(current);
+                    }
+                }
+            }
+        }
+    }
+
+    unsafe fn slot_detach_core(&mut self, root: &mut T, mut slot: Slot<T>, node: T) {
+        loop {
+            let current = self.get_slot(*root, slot);
+            let left = self.get_left(current);
+            let left_size = self.get_size_or_zero(left);
+            let right = self.get_right(current);
+            let right_size = self.get_size_or_zero(right);
+            if self.first_is_to_the_left_of_second(node, current) {
+                let decremented_left_size = left_size - T::funty(1);
+                if self.get_size_or_zero(self.get_right_or_default(right)) > decremented_left_size {
+                    self.slot_left_rotate(root, slot);
+                } else if self.get_size_or_zero(self.get_left_or_default(right))
+                    > decremented_left_size
+                {
+                    self.slot_right_rotate(root, Slot::RightOf(current));
+                    self.slot_left_rotate(root, slot);
+                } else {
+                    self.dec_size(current);
+                    slot = Slot::LeftOf(current);
+                }
+            } else if self.first_is_to_the_right_of_second(node, current) {
+                let decremented_right_size = right_size - T::funty(1);
+                if self.get_size_or_zero(self.get_left_or_default(left)) > decremented_right_size {
+                    self.slot_right_rotate(root, slot);
+                } else if self.get_size_or_zero(self.get_right_or_default(left))
+                    > decremented_right_size
+                {
+                    self.slot_left_rotate(root, Slot::LeftOf(current));
+                    self.slot_right_rotate(root, slot);
+                } else {
+                    self.dec_size(current);
+                    slot = Slot::RightOf(current);
+                }
+            } else {
+                let replacement = if left_size > T::funty(0) && right_size > T::funty(0) {
+                    let replacement;
+                    if left_size > right_size {
+                        replacement = self.get_rightest(left);
+                        self.slot_detach_core(root, Slot::LeftOf(current), replacement);
+                    } else {
+                        replacement = self.get_leftest(right);
+                        self.slot_detach_core(root, Slot::RightOf(current), replacement);
+                    }
+                    self.set_left(replacement, self.get_left(current));
+                    self.set_right(replacement, self.get_right(current));
+                    self.set_size(replacement, left_size + right_size);
+                    replacement
+                } else if left_size > T::funty(0) {
+                    left
+                } else if right_size > T::funty(0) {
+                    right
+                } else {
+                    T::funty(0)
+                };
+                self.set_slot(root, slot, replacement);
+                self.clear_node(node);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{LinkType, RecursiveSizeBalancedTree};
+
+/// Splay tree sharing the node storage of [`RecursiveSizeBalancedTree`].
+///
+/// Every operation moves the accessed node to the root, so a small hot set
+/// of nodes stays near the top. Splaying is top-down (Sleator's
+/// size-maintaining variant): it never recurses and keeps the `size` field
+/// correct, so `splay_rank` and `splay_select` work on the result.
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`].
+#[allow(clippy::missing_safety_doc)]
+pub trait SplayTree<T: LinkType>: RecursiveSizeBalancedTree<T> {
+    /// Splays `node` (or the last node on its search path when it is absent)
+    /// to the root of the tree at `root` and returns the new root.
+    unsafe fn splay(&mut self, root: T, node: T) -> T {
+        if root == T::funty(0) {
+            return root;
+        }
+        // Roots and tails of the trees of nodes known to be to the left and
+        // to the right of `node`; `0` tails stand for an empty tree.
+        let mut left_root = T::funty(0);
+        let mut right_root = T::funty(0);
+        let mut left_tail = T::funty(0);
+        let mut right_tail = T::funty(0);
+        let mut left_size = T::funty(0);
+        let mut right_size = T::funty(0);
+        let mut current = root;
+        loop {
+            if self.first_is_to_the_left_of_second(node, current) {
+                let mut left = self.get_left(current);
+                if left == T::funty(0) {
+                    break;
+                }
+                if self.first_is_to_the_left_of_second(node, left) {
+                    self.set_left(current, self.get_right(left));
+                    self.set_right(left, current);
+                    self.fix_size(current);
+                    current = left;
+                    left = self.get_left(current);
+                    if left == T::funty(0) {
+                        break;
+                    }
+                }
+                if right_tail == T::funty(0) {
+                    right_root = current;
+                } else {
+                    self.set_left(right_tail, current);
+                }
+                right_tail = current;
+                right_size += self.get_right_size(current) + T::funty(1);
+                current = left;
+            } else if self.first_is_to_the_right_of_second(node, current) {
+                let mut right = self.get_right(current);
+                if right == T::funty(0) {
+                    break;
+                }
+                if self.first_is_to_the_right_of_second(node, right) {
+                    self.set_right(current, self.get_left(right));
+                    self.set_left(right, current);
+                    self.fix_size(current);
+                    current = right;
+                    right = self.get_right(current);
+                    if right == T::funty(0) {
+                        break;
+                    }
+                }
+                if left_tail == T::funty(0) {
+                    left_root = current;
+                } else {
+                    self.set_right(left_tail, current);
+                }
+                left_tail = current;
+                left_size += self.get_left_size(current) + T::funty(1);
+                current = right;
+            } else {
+                break;
+            }
+        }
+        left_size += self.get_left_size(current);
+        right_size += self.get_right_size(current);
+        self.set_size(current, left_size + right_size + T::funty(1));
+
+        if left_tail != T::funty(0) {
+            self.set_right(left_tail, T::funty(0));
+        }
+        if right_tail != T::funty(0) {
+            self.set_left(right_tail, T::funty(0));
+        }
+        let mut walk = left_root;
+        while walk != T::funty(0) {
+            self.set_size(walk, left_size);
+            left_size -= self.get_left_size(walk) + T::funty(1);
+            walk = self.get_right(walk);
+        }
+        let mut walk = right_root;
+        while walk != T::funty(0) {
+            self.set_size(walk, right_size);
+            right_size -= self.get_right_size(walk) + T::funty(1);
+            walk = self.get_left(walk);
+        }
+
+        if left_tail == T::funty(0) {
+            left_root = self.get_left(current);
+        } else {
+            self.set_right(left_tail, self.get_left(current));
+        }
+        if right_tail == T::funty(0) {
+            right_root = self.get_right(current);
+        } else {
+            self.set_left(right_tail, self.get_right(current));
+        }
+        self.set_left(current, left_root);
+        self.set_right(current, right_root);
+        current
+    }
+
+    unsafe fn splay_attach(&mut self, root: &mut T, node: T) {
+        self.clear_node(node);
+        if *root != T::funty(0) {
+            let top = self.splay(*root, node);
+            if self.first_is_to_the_left_of_second(node, top) {
+                self.set_left(node, self.get_left(top));
+                self.set_right(node, top);
+                self.set_left(top, T::funty(0));
+            } else {
+                self.set_right(node, self.get_right(top));
+                self.set_left(node, top);
+                self.set_right(top, T::funty(0));
+            }
+            self.fix_size(top);
+        }
+        self.fix_size(node);
+        *root = node;
+    }
+
+    unsafe fn splay_detach(&mut self, root: &mut T, node: T) {
+        let top = self.splay(*root, node);
+        if top != node || node == T::funty(0) {
+            *root = top;
+            return;
+        }
+        let left = self.get_left(top);
+        let right = self.get_right(top);
+        *root = if left == T::funty(0) {
+            right
+        } else {
+            let joined = self.splay(left, node);
+            self.set_right(joined, right);
+            self.fix_size(joined);
+            joined
+        };
+        self.clear_node(node);
+    }
+
+    /// Splays toward `node` and returns whether it is in the tree.
+    unsafe fn splay_search(&mut self, root: &mut T, node: T) -> bool {
+        *root = self.splay(*root, node);
+        *root == node && node != T::funty(0)
+    }
+
+    /// Splays `node` to the root and returns the number of nodes to its left.
+    unsafe fn splay_rank(&mut self, root: &mut T, node: T) -> T {
+        *root = self.splay(*root, node);
+        self.get_left_size(*root)
+    }
+
+    /// Splays the node with `index` nodes to its left to the root and returns
+    /// it, or returns `0` when `index` is out of range.
+    unsafe fn splay_select(&mut self, root: &mut T, mut index: T) -> T {
+        let mut current = *root;
+        while current != T::funty(0) {
+            let left_size = self.get_left_size(current);
+            if index < left_size {
+                current = self.get_left(current);
+            } else if index > left_size {
+                index -= left_size + T::funty(1);
+                current = self.get_right(current);
+            } else {
+                *root = self.splay(*root, current);
+                return current;
+            }
+        }
+        T::funty(0)
+    }
+}
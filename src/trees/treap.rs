@@ -0,0 +1,109 @@
+use crate::{hash, LinkType, RecursiveSizeBalancedTree};
+
+/// Treap sharing the node storage of [`RecursiveSizeBalancedTree`].
+///
+/// Priorities are not stored: `get_priority` hashes the whole node id, and
+/// the hash never ties on ids that fit in a `u64`. The `size` field is kept
+/// up to date, so the order-statistic helpers keep working. Nodes are
+/// attached and detached with `split` and `merge`, which recurse to the
+/// expected `O(log n)` depth of the treap.
+///
+/// # Safety
+///
+/// Same contract as [`RecursiveSizeBalancedTree`].
+#[allow(clippy::missing_safety_doc)]
+pub trait Treap<T: LinkType>: RecursiveSizeBalancedTree<T> {
+    fn get_priority(&self, node: T) -> u64 {
+        hash::link(node)
+    }
+
+    /// Splits the tree at `root` into the nodes to the left of `node` and
+    /// the rest, returning both roots.
+    unsafe fn split(&mut self, root: T, node: T) -> (T, T) {
+        if root == T::funty(0) {
+            return (T::funty(0), T::funty(0));
+        }
+        if self.first_is_to_the_left_of_second(root, node) {
+            let (left, right) = self.split(self.get_right(root), node);
+            self.set_right(root, left);
+            self.fix_size(root);
+            (root, right)
+        } else {
+            let (left, right) = self.split(self.get_left(root), node);
+            self.set_left(root, right);
+            self.fix_size(root);
+            (left, root)
+        }
+    }
+
+    /// Joins two trees where every node of `left` is to the left of every
+    /// node of `right`, returning the new root.
+    unsafe fn merge(&mut self, left: T, right: T) -> T {
+        if left == T::funty(0) {
+            return right;
+        }
+        if right == T::funty(0) {
+            return left;
+        }
+        if self.get_priority(left) > self.get_priority(right) {
+            let merged = self.merge(self.get_right(left), right);
+            self.set_right(left, merged);
+            self.fix_size(left);
+            left
+        } else {
+            let merged = self.merge(left, self.get_left(right));
+            self.set_left(right, merged);
+            self.fix_size(right);
+            right
+        }
+    }
+
+    unsafe fn treap_attach(&mut self, root: &mut T, node: T) {
+        let priority = self.get_priority(node);
+        let mut parent = T::funty(0);
+        let mut current = *root;
+        while current != T::funty(0) && self.get_priority(current) > priority {
+            self.inc_size(current);
+            parent = current;
+            current = if self.first_is_to_the_left_of_second(node, current) {
+                self.get_left(current)
+            } else {
+                self.get_right(current)
+            };
+        }
+        let (left, right) = self.split(current, node);
+        self.set_left(node, left);
+        self.set_right(node, right);
+        self.fix_size(node);
+        if parent == T::funty(0) {
+            *root = node;
+        } else if self.first_is_to_the_left_of_second(node, parent) {
+            self.set_left(parent, node);
+        } else {
+            self.set_right(parent, node);
+        }
+    }
+
+    unsafe fn treap_detach(&mut self, root: &mut T, node: T) {
+        let mut parent = T::funty(0);
+        let mut current = *root;
+        while current != node {
+            self.dec_size(current);
+            parent = current;
+            current = if self.first_is_to_the_left_of_second(node, current) {
+                self.get_left(current)
+            } else {
+                self.get_right(current)
+            };
+        }
+        let merged = self.merge(self.get_left(node), self.get_right(node));
+        if parent == T::funty(0) {
+            *root = merged;
+        } else if self.get_left(parent) == node {
+            self.set_left(parent, merged);
+        } else {
+            self.set_right(parent, merged);
+        }
+        self.clear_node(node);
+    }
+}